@@ -0,0 +1,613 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use rust8::constants::MEMORY_SIZE;
+use rust8::data_register::DataRegister;
+use rust8::debugger::{Breakpoint, Debugger, StopReason};
+use rust8::disassembler::disassemble;
+use rust8::expression::{Expression, LogMessage};
+use rust8::graphic::Pixel;
+use rust8::keyboard::Key;
+use rust8::keymap::{KeyConfig, KeyMap};
+use rust8::loader::LoadedProgram;
+use rust8::symbols::SymbolTable;
+use rust8::Chip8;
+
+const DEFAULT_MAX_CYCLES: usize = 10_000_000;
+const DEFAULT_DISASSEMBLY_COUNT: usize = 10;
+// Scripts may source other scripts, but not endlessly themselves
+const MAX_SOURCE_DEPTH: usize = 16;
+
+const HELP: &str = "\
+commands:
+  break <address> [if <condition>]   set a breakpoint, optionally conditional
+  logpoint <address> <message>       log a message with {expression} placeholders
+  delete <address>                   remove a breakpoint
+  watch <expression>                 stop when the value of an expression changes
+  unwatch <index>                    remove a watch
+  info break|watch                   list breakpoints or watches
+  step [count]                       execute instructions, entering subroutines
+  next                               execute one instruction, stepping over calls
+  finish                             run until the current subroutine returns
+  continue [max cycles]              run until a breakpoint, watch or error
+  regs                               print the registers and timers
+  backtrace                          print the call stack
+  print <expression>                 evaluate an expression
+  x/<count><format> <address>        examine memory, format x, d or i
+  disas [address] [count]            disassemble instructions
+  screen                             print the framebuffer
+  key down|up <key>                  press or release a key (0-f or [@]<host key> of -k)
+  save <file> / load <file>          save or restore the machine state
+  symbols <file>                     load labels and source lines for addresses
+  source <file>                      execute commands from a file
+  history / !<index> / !!            list or repeat earlier commands
+  quit                               exit the debugger
+an empty line repeats the previous command";
+
+enum Control {
+    Continue,
+    Quit,
+}
+
+struct Session {
+    chip8: Chip8,
+    debugger: Debugger,
+    symbols: SymbolTable,
+    keymap: Option<KeyMap>,
+    history: Vec<String>,
+    source_depth: usize,
+}
+
+fn parse_number(value: &str) -> Result<usize, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+
+    parsed.map_err(|_| format!("invalid number '{}'", value))
+}
+
+impl Session {
+    fn evaluate(&self, expression: &str) -> Result<i64, String> {
+        Expression::parse(expression)
+            .map_err(|err| err.to_string())?
+            .evaluate(&self.chip8)
+            .map_err(|err| err.to_string())
+    }
+
+    fn evaluate_address(&self, expression: &str) -> Result<usize, String> {
+        if let Some(address) = self.symbols.address(expression.trim()) {
+            return Ok(address);
+        }
+
+        let value = self.evaluate(expression)?;
+
+        usize::try_from(value).map_err(|_| format!("invalid address {}", value))
+    }
+
+    fn print_location(&self) {
+        let listing = disassemble(
+            &self.chip8.memory.raw_data,
+            self.chip8.program_counter..self.chip8.program_counter + 2,
+        );
+
+        match listing.first() {
+            Some(instruction) => println!(
+                "{}: {:04x}  {}",
+                self.symbols.format_address(instruction.address),
+                instruction.opcode,
+                self.symbols.format_instruction(instruction)
+            ),
+            None => println!("{:#05x}: <outside of memory>", self.chip8.program_counter),
+        }
+    }
+
+    fn report_stop(&mut self, stop_reason: StopReason) {
+        for message in self.debugger.take_log_messages() {
+            println!("{}", message);
+        }
+
+        match stop_reason {
+            StopReason::Step => {}
+            StopReason::Breakpoint(address) => {
+                println!("breakpoint at {}", self.symbols.format_address(address))
+            }
+            StopReason::Blocked => {
+                println!("waiting for a key press, use 'key down <key>' and 'key up <key>'")
+            }
+            StopReason::CycleLimit => println!("stopped after reaching the cycle limit"),
+            StopReason::Watch {
+                index,
+                old_value,
+                new_value,
+            } => {
+                let format = |value: Option<i64>| {
+                    value.map_or_else(|| "<error>".to_string(), |value| format!("{:#x}", value))
+                };
+                println!(
+                    "watch {} '{}' changed from {} to {}",
+                    index,
+                    self.debugger.watches()[index].expression,
+                    format(old_value),
+                    format(new_value)
+                );
+            }
+            StopReason::Error(err) => {
+                println!("error: {}", self.symbols.describe_error(&err, &self.chip8))
+            }
+        }
+
+        self.print_location();
+    }
+
+    fn print_registers(&self) {
+        for row in 0..2u8 {
+            let registers: Vec<String> = (row * 8..row * 8 + 8)
+                .map(|register| {
+                    let value =
+                        self.chip8.data_registers[DataRegister::try_from(register).unwrap()];
+                    format!("v{:x}={:02x}", register, value)
+                })
+                .collect();
+            println!("{}", registers.join(" "));
+        }
+
+        println!(
+            "i={:03x} pc={:03x} sp={} dt={} st={}",
+            self.chip8.address_register,
+            self.chip8.program_counter,
+            self.chip8.stack.len(),
+            self.chip8.delay_timer,
+            self.chip8.sound_timer
+        );
+
+        let stack: Vec<String> = self
+            .chip8
+            .stack
+            .iter()
+            .map(|address| format!("{:03x}", address))
+            .collect();
+        println!("stack=[{}]", stack.join(" "));
+    }
+
+    fn examine(&self, format: &str, address: &str) -> Result<(), String> {
+        let count_digits: String = format.chars().take_while(char::is_ascii_digit).collect();
+        let count = match count_digits.is_empty() {
+            true => 1,
+            false => parse_number(&count_digits)?,
+        };
+        let format = &format[count_digits.len()..];
+        let address = self.evaluate_address(address)?;
+
+        // The unit is always a byte, a trailing 'b' is accepted for gdb compatibility
+        match format.trim_end_matches('b') {
+            "i" => {
+                self.disassemble(address, count);
+                Ok(())
+            }
+            "x" | "" => {
+                self.print_memory(address, count, |byte| format!("{:02x}", byte));
+                Ok(())
+            }
+            "d" => {
+                self.print_memory(address, count, |byte| format!("{:3}", byte));
+                Ok(())
+            }
+            _ => Err(format!("unsupported format '{}'", format)),
+        }
+    }
+
+    fn print_memory<F: Fn(u8) -> String>(&self, address: usize, count: usize, format: F) {
+        let memory = &self.chip8.memory.raw_data;
+        let end = address.saturating_add(count).min(memory.len());
+
+        for row_start in (address.min(end)..end).step_by(8) {
+            let row_end = (row_start + 8).min(end);
+            let bytes: Vec<String> = memory[row_start..row_end]
+                .iter()
+                .map(|byte| format(*byte))
+                .collect();
+            println!("{:#05x}: {}", row_start, bytes.join(" "));
+        }
+    }
+
+    fn disassemble(&self, address: usize, count: usize) {
+        let end = address
+            .saturating_add(count.saturating_mul(2))
+            .min(MEMORY_SIZE);
+        for instruction in disassemble(&self.chip8.memory.raw_data, address.min(end)..end) {
+            let marker = match instruction.address == self.chip8.program_counter {
+                true => "=>",
+                false => "  ",
+            };
+            if let Some(label) = self.symbols.label(instruction.address) {
+                println!(": {}", label);
+            }
+            println!(
+                "{} {:#05x}: {:04x}  {}",
+                marker,
+                instruction.address,
+                instruction.opcode,
+                self.symbols.format_instruction(&instruction)
+            );
+        }
+    }
+
+    fn print_screen(&self) {
+        for row in self.chip8.screen.framebuffer() {
+            let line: String = row
+                .iter()
+                .map(|pixel| match pixel {
+                    Pixel::On => '#',
+                    Pixel::Off => '.',
+                })
+                .collect();
+            println!("{}", line);
+        }
+    }
+
+    fn host_key(&self, key: &str) -> Option<Key> {
+        self.keymap.as_ref().and_then(|keymap| keymap.key(key))
+    }
+
+    fn source(&mut self, path: &str) -> Result<Control, String> {
+        if self.source_depth >= MAX_SOURCE_DEPTH {
+            return Err(format!(
+                "{}: scripts nested deeper than {}",
+                path, MAX_SOURCE_DEPTH
+            ));
+        }
+
+        let script = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        self.source_depth += 1;
+        let control = self.execute_script(&script);
+        self.source_depth -= 1;
+
+        control
+    }
+
+    fn execute_script(&mut self, script: &str) -> Result<Control, String> {
+        for line in script.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            println!("(rust8-dbg) {}", line);
+            if let Control::Quit = self.execute(line)? {
+                return Ok(Control::Quit);
+            }
+        }
+
+        Ok(Control::Continue)
+    }
+
+    fn execute_line(&mut self, line: &str) -> Result<Control, String> {
+        let line = line.trim();
+
+        // History expansion happens before the command is recorded
+        let command = if line.is_empty() || line == "!!" {
+            self.history.last().cloned().unwrap_or_default()
+        } else if let Some(index) = line.strip_prefix('!') {
+            let index = parse_number(index)?;
+            self.history
+                .get(index)
+                .cloned()
+                .ok_or(format!("no history entry {}", index))?
+        } else {
+            line.to_string()
+        };
+
+        if command.is_empty() {
+            return Ok(Control::Continue);
+        }
+        if !line.is_empty() {
+            self.history.push(command.clone());
+        }
+
+        self.execute(&command)
+    }
+
+    fn execute(&mut self, command: &str) -> Result<Control, String> {
+        let (name, arguments) = command.split_once(' ').unwrap_or((command, ""));
+        let arguments = arguments.trim();
+
+        if let Some(format) = name.strip_prefix("x/") {
+            self.examine(format, arguments)?;
+            return Ok(Control::Continue);
+        }
+
+        match name {
+            "break" | "b" => {
+                let (address, condition) = match arguments.split_once(" if ") {
+                    Some((address, condition)) => (address, Some(condition)),
+                    None => (arguments, None),
+                };
+                let address = self.evaluate_address(address)?;
+                let condition = condition
+                    .map(Expression::parse)
+                    .transpose()
+                    .map_err(|err| err.to_string())?;
+
+                self.debugger.set_breakpoint(
+                    address,
+                    Breakpoint {
+                        condition,
+                        ..Breakpoint::default()
+                    },
+                );
+                println!("breakpoint at {}", self.symbols.format_address(address));
+            }
+            "logpoint" => {
+                let (address, message) = arguments
+                    .split_once(' ')
+                    .ok_or("usage: logpoint <address> <message>")?;
+                let address = self.evaluate_address(address)?;
+                let log_message = LogMessage::parse(message).map_err(|err| err.to_string())?;
+
+                self.debugger.set_breakpoint(
+                    address,
+                    Breakpoint {
+                        log_message: Some(log_message),
+                        ..Breakpoint::default()
+                    },
+                );
+                println!("logpoint at {:#05x}", address);
+            }
+            "delete" | "d" => {
+                let address = self.evaluate_address(arguments)?;
+                if !self.debugger.remove_breakpoint(address) {
+                    return Err(format!("no breakpoint at {:#05x}", address));
+                }
+            }
+            "watch" => {
+                let expression = Expression::parse(arguments).map_err(|err| err.to_string())?;
+                let index = self.debugger.add_watch(expression, &self.chip8);
+                println!("watch {}: {}", index, arguments);
+            }
+            "unwatch" => {
+                let index = parse_number(arguments)?;
+                self.debugger
+                    .remove_watch(index)
+                    .ok_or(format!("no watch {}", index))?;
+            }
+            "info" => match arguments {
+                "break" | "breakpoints" => {
+                    for address in self.debugger.breakpoints() {
+                        let breakpoint = self.debugger.breakpoint(address).unwrap();
+                        let mut description = format!(
+                            "{} hits={}",
+                            self.symbols.format_address(address),
+                            breakpoint.hit_count
+                        );
+                        if let Some(condition) = &breakpoint.condition {
+                            description.push_str(&format!(" if {}", condition));
+                        }
+                        if breakpoint.log_message.is_some() {
+                            description.push_str(" (logpoint)");
+                        }
+                        println!("{}", description);
+                    }
+                }
+                "watch" | "watches" => {
+                    for (index, watch) in self.debugger.watches().iter().enumerate() {
+                        let value = watch
+                            .value
+                            .map_or_else(|| "<error>".to_string(), |value| format!("{:#x}", value));
+                        println!("{}: {} = {}", index, watch.expression, value);
+                    }
+                }
+                _ => return Err("usage: info break|watch".to_string()),
+            },
+            "step" | "s" => {
+                let count = match arguments {
+                    "" => 1,
+                    count => parse_number(count)?,
+                };
+                let mut stop_reason = StopReason::Step;
+                for _ in 0..count {
+                    stop_reason = self.debugger.step(&mut self.chip8);
+                    if !matches!(stop_reason, StopReason::Step) {
+                        break;
+                    }
+                }
+                self.report_stop(stop_reason);
+            }
+            "next" | "n" => {
+                let stop_reason = self.debugger.step_over(&mut self.chip8, DEFAULT_MAX_CYCLES);
+                self.report_stop(stop_reason);
+            }
+            "finish" => {
+                let stop_reason = self.debugger.step_out(&mut self.chip8, DEFAULT_MAX_CYCLES);
+                self.report_stop(stop_reason);
+            }
+            "continue" | "c" => {
+                let max_cycles = match arguments {
+                    "" => DEFAULT_MAX_CYCLES,
+                    max_cycles => parse_number(max_cycles)?,
+                };
+                let stop_reason = self.debugger.resume(&mut self.chip8, max_cycles);
+                self.report_stop(stop_reason);
+            }
+            "regs" | "registers" => self.print_registers(),
+            "backtrace" | "bt" => {
+                for (index, frame) in self.symbols.backtrace(&self.chip8).iter().enumerate() {
+                    println!("#{} {}", index, frame);
+                }
+            }
+            "symbols" => {
+                let symbols = fs::read_to_string(arguments)
+                    .map_err(|err| format!("{}: {}", arguments, err))?;
+                self.symbols = SymbolTable::parse(&symbols).map_err(|err| err.to_string())?;
+                println!("{} labels loaded", self.symbols.labels().count());
+            }
+            "print" | "p" => {
+                let value = self.evaluate(arguments)?;
+                println!("{} ({:#x})", value, value);
+            }
+            "disas" => {
+                let mut arguments = arguments.split_whitespace();
+                let address = match arguments.next() {
+                    Some(address) => self.evaluate_address(address)?,
+                    None => self.chip8.program_counter,
+                };
+                let count = match arguments.next() {
+                    Some(count) => parse_number(count)?,
+                    None => DEFAULT_DISASSEMBLY_COUNT,
+                };
+                self.disassemble(address, count);
+            }
+            "screen" => self.print_screen(),
+            "key" => {
+                let (direction, key) = arguments
+                    .split_once(' ')
+                    .ok_or("usage: key down|up <key>")?;
+                let key = key.trim();
+                // Hex digits are always CHIP-8 keys, host keys of the keymap can be forced with @
+                let mut characters = key.chars();
+                let key = match (characters.next(), characters.next(), key.strip_prefix('@')) {
+                    (_, _, Some(host_key)) => self.host_key(host_key),
+                    (Some(key), None, None) if key.is_ascii_hexdigit() => Key::try_from(key).ok(),
+                    _ => self.host_key(key),
+                }
+                .ok_or(format!("invalid key '{}'", key))?;
+
+                match direction {
+                    "down" => self.chip8.key_down(key),
+                    "up" => self.chip8.key_up(key),
+                    _ => return Err("usage: key down|up <key>".to_string()),
+                }
+            }
+            "save" => {
+                fs::write(arguments, self.chip8.save_state())
+                    .map_err(|err| format!("{}: {}", arguments, err))?;
+                println!("state saved to {}", arguments);
+            }
+            "load" => {
+                let state = fs::read(arguments).map_err(|err| format!("{}: {}", arguments, err))?;
+                self.chip8
+                    .load_state(&state)
+                    .map_err(|err| err.to_string())?;
+                self.print_location();
+            }
+            "source" => return self.source(arguments),
+            "history" => {
+                for (index, command) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", index, command);
+                }
+            }
+            "help" | "h" => println!("{}", HELP),
+            "quit" | "q" => return Ok(Control::Quit),
+            _ => return Err(format!("unknown command '{}', try 'help'", name)),
+        }
+
+        Ok(Control::Continue)
+    }
+}
+
+fn main() -> ExitCode {
+    let mut arguments = env::args().skip(1);
+    let mut rom_path = None;
+    let mut script_path = None;
+    let mut symbols_path = None;
+    let mut keymap_path = None;
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-x" => script_path = arguments.next(),
+            "-s" => symbols_path = arguments.next(),
+            "-k" => keymap_path = arguments.next(),
+            _ => rom_path = Some(argument),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        eprintln!("usage: rust8-dbg [-x <script>] [-s <symbols>] [-k <keymap>] <rom>");
+        return ExitCode::FAILURE;
+    };
+
+    let program = match LoadedProgram::open(&rom_path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}: {}", rom_path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut session = Session {
+        chip8: Chip8::new(),
+        debugger: Debugger::new(),
+        symbols: SymbolTable::new(),
+        keymap: None,
+        history: Vec::new(),
+        source_depth: 0,
+    };
+
+    if let Some(symbols_path) = symbols_path {
+        let symbols = fs::read_to_string(&symbols_path)
+            .map_err(|err| err.to_string())
+            .and_then(|symbols| SymbolTable::parse(&symbols).map_err(|err| err.to_string()));
+        match symbols {
+            Ok(symbols) => session.symbols = symbols,
+            Err(err) => {
+                eprintln!("{}: {}", symbols_path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(keymap_path) = keymap_path {
+        let config = fs::read_to_string(&keymap_path)
+            .map_err(|err| err.to_string())
+            .and_then(|config| KeyConfig::parse(&config).map_err(|err| err.to_string()));
+        match config {
+            Ok(config) => {
+                let rom_name = Path::new(&rom_path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_default();
+                session.keymap = Some(config.keymap_for_rom(&rom_name));
+            }
+            Err(err) => {
+                eprintln!("{}: {}", keymap_path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Err(err) = session.chip8.load(&program) {
+        eprintln!("{}: {}", rom_path, err);
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(script_path) = script_path {
+        match session.source(&script_path) {
+            Ok(Control::Quit) => return ExitCode::SUCCESS,
+            Ok(Control::Continue) => {}
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    session.print_location();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(rust8-dbg) ");
+        let _ = io::stdout().flush();
+
+        let Some(Ok(line)) = lines.next() else {
+            return ExitCode::SUCCESS;
+        };
+
+        match session.execute_line(&line) {
+            Ok(Control::Continue) => {}
+            Ok(Control::Quit) => return ExitCode::SUCCESS,
+            Err(err) => println!("{}", err),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use rust8::patch::{apply_patch, PatchFormat};
+
+const USAGE: &str = "\
+usage:
+  rust8-patch apply <rom> <patch> <output>
+  rust8-patch create [--ips|--bps] <original rom> <modified rom> <patch>
+
+create picks the format from the extension of the patch, defaulting to BPS";
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn write(path: &str, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn apply(arguments: &[String]) -> Result<(), String> {
+    let [rom_path, patch_path, output_path] = arguments else {
+        return Err(USAGE.to_string());
+    };
+
+    let patched = apply_patch(&read(rom_path)?, &read(patch_path)?)
+        .map_err(|err| format!("{}: {}", patch_path, err))?;
+    write(output_path, &patched)
+}
+
+fn create(arguments: &[String]) -> Result<(), String> {
+    let (format, arguments) = match arguments.first().map(String::as_str) {
+        Some("--ips") => (Some(PatchFormat::Ips), &arguments[1..]),
+        Some("--bps") => (Some(PatchFormat::Bps), &arguments[1..]),
+        _ => (None, arguments),
+    };
+    let [original_path, modified_path, patch_path] = arguments else {
+        return Err(USAGE.to_string());
+    };
+
+    let format = format
+        .or_else(|| {
+            patch_path
+                .rsplit_once('.')
+                .and_then(|(_, extension)| extension.parse().ok())
+        })
+        .unwrap_or(PatchFormat::Bps);
+
+    let patch = format
+        .create(&read(original_path)?, &read(modified_path)?)
+        .map_err(|err| format!("{}: {}", modified_path, err))?;
+    write(patch_path, &patch)
+}
+
+fn main() -> ExitCode {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    let result = match arguments.first().map(String::as_str) {
+        Some("apply") => apply(&arguments[1..]),
+        Some("create") => create(&arguments[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::disassembler::RecursiveDisassembly;
+use super::graphic::Display;
+use super::quirks::Quirks;
+use super::Chip8;
+
+// Octo hides the payload in the two low bits of every palette index
+const BITS_PER_PIXEL: usize = 2;
+const PIXELS_PER_BYTE: usize = 8 / BITS_PER_PIXEL;
+const LENGTH_SIZE: usize = 4;
+const CARTRIDGE_WIDTH: u16 = 128;
+
+// Four shades that only differ in the bits carrying the payload
+const CARTRIDGE_PALETTE: [u8; 12] = [
+    0x99, 0x66, 0x00, 0x99, 0x66, 0x01, 0x99, 0x67, 0x00, 0x99, 0x67, 0x01,
+];
+
+#[derive(Error, Debug)]
+pub enum CartridgeError {
+    #[error("cartridge image could not be decoded")]
+    Decoding(#[from] gif::DecodingError),
+    #[error("cartridge image could not be encoded")]
+    Encoding(#[from] gif::EncodingError),
+    #[error("cartridge payload is truncated")]
+    Truncated,
+    #[error("cartridge payload is not valid")]
+    Json(#[from] serde_json::Error),
+    #[error("cartridge payload of {0} bytes does not fit into an image")]
+    TooLarge(usize),
+}
+
+// The options object of Octo, unknown keys are kept so they survive a round trip
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OctoOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tickrate: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_store_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip_quirks: Option<bool>,
+    #[serde(rename = "vBlankQuirks", skip_serializing_if = "Option::is_none")]
+    pub vblank_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jump_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logic_quirks: Option<bool>,
+    // Only affects Octo's own interpreter, rust8 always writes VF last
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vf_order_quirks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill_color2: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buzz_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quiet_color: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl OctoOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_quirks(quirks: &Quirks, tickrate: usize) -> Self {
+        OctoOptions {
+            tickrate: Some(tickrate),
+            shift_quirks: Some(quirks.shift),
+            load_store_quirks: Some(quirks.memory_leave_i_unchanged),
+            clip_quirks: Some(!quirks.wrap),
+            vblank_quirks: Some(quirks.vblank),
+            jump_quirks: Some(quirks.jump),
+            logic_quirks: Some(quirks.logic),
+            ..Self::default()
+        }
+    }
+
+    // Missing keys are disabled, like they are in Octo
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            shift: self.shift_quirks.unwrap_or(false),
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: self.load_store_quirks.unwrap_or(false),
+            wrap: !self.clip_quirks.unwrap_or(false),
+            jump: self.jump_quirks.unwrap_or(false),
+            vblank: self.vblank_quirks.unwrap_or(false),
+            logic: self.logic_quirks.unwrap_or(false),
+        }
+    }
+
+    pub fn apply<D: Display>(&self, chip8: &mut Chip8<D>) {
+        chip8.quirks = self.quirks();
+
+        if let Some(tickrate) = self.tickrate {
+            chip8.instructions_per_frame = tickrate;
+        }
+    }
+}
+
+// The payload of an Octo cartridge, the program is Octo source code
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Cartridge {
+    pub program: String,
+    #[serde(default)]
+    pub options: OctoOptions,
+}
+
+impl Cartridge {
+    pub fn new(program: String, options: OctoOptions) -> Self {
+        Cartridge { program, options }
+    }
+
+    // Builds a cartridge from a binary program by disassembling it into Octo source
+    pub fn from_program(program: &[u8], quirks: &Quirks, tickrate: usize) -> Self {
+        let mut source = Vec::new();
+        RecursiveDisassembly::from_program(program)
+            .write_listing(&mut source)
+            .expect("writing to a vector cannot fail");
+
+        Cartridge {
+            program: String::from_utf8_lossy(&source).into_owned(),
+            options: OctoOptions::from_quirks(quirks, tickrate),
+        }
+    }
+
+    pub fn decode<R: Read>(reader: R) -> Result<Self, CartridgeError> {
+        let mut decoder = gif::DecodeOptions::new().read_info(reader)?;
+
+        let mut pixels = Vec::new();
+        while let Some(frame) = decoder.read_next_frame()? {
+            pixels.extend_from_slice(&frame.buffer);
+        }
+
+        let bytes: Vec<u8> = pixels
+            .chunks_exact(PIXELS_PER_BYTE)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |byte, pixel| byte << BITS_PER_PIXEL | pixel & 0b11)
+            })
+            .collect();
+
+        if bytes.len() < LENGTH_SIZE {
+            return Err(CartridgeError::Truncated);
+        }
+        let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let payload = bytes[LENGTH_SIZE..]
+            .get(..length)
+            .ok_or(CartridgeError::Truncated)?;
+
+        Ok(serde_json::from_slice(payload)?)
+    }
+
+    pub fn encode<W: Write>(&self, writer: W) -> Result<(), CartridgeError> {
+        let payload = serde_json::to_vec(self)?;
+
+        let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&payload);
+
+        let mut pixels: Vec<u8> = bytes
+            .iter()
+            .flat_map(|byte| {
+                (0..PIXELS_PER_BYTE)
+                    .rev()
+                    .map(move |index| byte >> (index * BITS_PER_PIXEL) & 0b11)
+            })
+            .collect();
+
+        let width = CARTRIDGE_WIDTH as usize;
+        let height = u16::try_from(pixels.len().div_ceil(width))
+            .map_err(|_| CartridgeError::TooLarge(payload.len()))?;
+        pixels.resize(width * height as usize, 0);
+
+        let mut encoder = gif::Encoder::new(writer, CARTRIDGE_WIDTH, height, &CARTRIDGE_PALETTE)?;
+        encoder.write_frame(&gif::Frame::from_indexed_pixels(
+            CARTRIDGE_WIDTH,
+            height,
+            pixels,
+            None,
+        ))?;
+
+        Ok(())
+    }
+
+    pub fn apply<D: Display>(&self, chip8: &mut Chip8<D>) {
+        self.options.apply(chip8);
+    }
+}
@@ -0,0 +1,621 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::ops::Range;
+
+use super::constants::{INSTRUCTION_SIZE, UNPROTECTED_MEMORY_START};
+use super::disassembler::{ByteKind, DisassembledInstruction, RecursiveDisassembly};
+use super::instruction::Instruction;
+use super::symbols::SymbolTable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EdgeKind {
+    Fallthrough,
+    Skip,
+    Jump,
+    Call,
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub instructions: Vec<DisassembledInstruction>,
+}
+
+impl BasicBlock {
+    pub fn last(&self) -> &DisassembledInstruction {
+        // Blocks are never empty
+        self.instructions.last().unwrap()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subroutine {
+    pub entry: usize,
+    pub blocks: BTreeSet<usize>,
+    pub callees: BTreeSet<usize>,
+    pub returns: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    pub header: usize,
+    pub latch: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    UnreachableBytes { start: usize, end: usize },
+    InfiniteLoop { address: usize },
+    UnknownJump { address: usize, base: usize },
+    ReturnOutsideSubroutine { address: usize },
+    JumpIntoInstruction { address: usize, target: usize },
+    JumpIntoInterpreterArea { address: usize, target: usize },
+    JumpOutsideProgram { address: usize, target: usize },
+    InvalidInstruction { address: usize },
+}
+
+impl Finding {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Finding::UnreachableBytes { .. }
+            | Finding::InfiniteLoop { .. }
+            | Finding::UnknownJump { .. } => Severity::Info,
+            Finding::ReturnOutsideSubroutine { .. } | Finding::JumpIntoInstruction { .. } => {
+                Severity::Warning
+            }
+            Finding::JumpIntoInterpreterArea { .. }
+            | Finding::JumpOutsideProgram { .. }
+            | Finding::InvalidInstruction { .. } => Severity::Error,
+        }
+    }
+
+    pub fn address(&self) -> usize {
+        match self {
+            Finding::UnreachableBytes { start: address, .. }
+            | Finding::InfiniteLoop { address }
+            | Finding::UnknownJump { address, .. }
+            | Finding::ReturnOutsideSubroutine { address }
+            | Finding::JumpIntoInstruction { address, .. }
+            | Finding::JumpIntoInterpreterArea { address, .. }
+            | Finding::JumpOutsideProgram { address, .. }
+            | Finding::InvalidInstruction { address } => *address,
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::UnreachableBytes { start, end } => {
+                write!(f, "bytes {:#05x}..{:#05x} are never reached", start, end)
+            }
+            Finding::InfiniteLoop { address } => {
+                write!(f, "infinite loop at {:#05x}", address)
+            }
+            Finding::UnknownJump { address, base } => write!(
+                f,
+                "jump at {:#05x} depends on v0, targets from {:#05x} on are not followed",
+                address, base
+            ),
+            Finding::ReturnOutsideSubroutine { address } => {
+                write!(
+                    f,
+                    "return at {:#05x} is reachable outside of a subroutine",
+                    address
+                )
+            }
+            Finding::JumpIntoInstruction { address, target } => write!(
+                f,
+                "{:#05x} transfers control into the middle of the instruction at {:#05x}",
+                address,
+                target - 1
+            ),
+            Finding::JumpIntoInterpreterArea { address, target } => write!(
+                f,
+                "{:#05x} transfers control to {:#05x} inside the interpreter area",
+                address, target
+            ),
+            Finding::JumpOutsideProgram { address, target } => write!(
+                f,
+                "{:#05x} transfers control to {:#05x} outside of the program",
+                address, target
+            ),
+            Finding::InvalidInstruction { address } => {
+                write!(f, "invalid instruction at {:#05x}", address)
+            }
+        }
+    }
+}
+
+pub struct ControlFlowGraph {
+    entry: usize,
+    blocks: BTreeMap<usize, BasicBlock>,
+    edges: BTreeSet<Edge>,
+    subroutines: BTreeMap<usize, Subroutine>,
+    loops: Vec<Loop>,
+    findings: Vec<Finding>,
+    labels: SymbolTable,
+}
+
+fn is_skip(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::SkipIfVxEqualsNum { .. }
+            | Instruction::SkipIfVxNotEqualNum { .. }
+            | Instruction::SkipIfVxEqualsVy { .. }
+            | Instruction::SkipIfVxNotEqualVy { .. }
+            | Instruction::SkipIfKeyInVxPressed { .. }
+            | Instruction::SkipIfKeyInVxNotPressed { .. }
+    )
+}
+
+// Instructions after which a basic block can not continue
+fn ends_block(instruction: &Instruction) -> bool {
+    is_skip(instruction)
+        || matches!(
+            instruction,
+            Instruction::JumpToAddress { .. }
+                | Instruction::ExecuteSubroutine { .. }
+                | Instruction::ReturnFromSubroutine
+                | Instruction::JumpToAddressPlusV0 { .. }
+        )
+}
+
+impl ControlFlowGraph {
+    pub fn new(data: &[u8], range: Range<usize>) -> Self {
+        Self::from_disassembly(&RecursiveDisassembly::new(data, range))
+    }
+
+    pub fn from_program(program: &[u8]) -> Self {
+        Self::from_disassembly(&RecursiveDisassembly::from_program(program))
+    }
+
+    pub fn from_disassembly(disassembly: &RecursiveDisassembly) -> Self {
+        let mut graph = ControlFlowGraph {
+            entry: disassembly.range().start,
+            blocks: BTreeMap::new(),
+            edges: BTreeSet::new(),
+            subroutines: BTreeMap::new(),
+            loops: Vec::new(),
+            findings: Vec::new(),
+            labels: disassembly.labels().clone(),
+        };
+
+        graph.build_blocks(disassembly);
+        graph.build_edges(disassembly);
+        graph.build_subroutines();
+        graph.build_return_edges();
+        graph.find_loops();
+        graph.lint(disassembly);
+
+        graph
+    }
+
+    fn build_blocks(&mut self, disassembly: &RecursiveDisassembly) {
+        let mut leaders = BTreeSet::from([self.entry]);
+
+        for decoded in disassembly.instructions() {
+            let Some(instruction) = decoded.instruction else {
+                continue;
+            };
+            let next = decoded.address + INSTRUCTION_SIZE;
+
+            if let Instruction::JumpToAddress { address: target }
+            | Instruction::ExecuteSubroutine { address: target } = instruction
+            {
+                leaders.insert(target);
+            }
+            if ends_block(&instruction) {
+                leaders.insert(next);
+            }
+            if is_skip(&instruction) {
+                leaders.insert(next + INSTRUCTION_SIZE);
+            }
+        }
+
+        for &leader in &leaders {
+            let mut address = leader;
+            let mut instructions = Vec::new();
+
+            while let Some(decoded) = disassembly.instruction(address) {
+                instructions.push(*decoded);
+                address += INSTRUCTION_SIZE;
+
+                let ends = decoded.instruction.as_ref().is_none_or(ends_block);
+                if ends || leaders.contains(&address) {
+                    break;
+                }
+            }
+
+            if !instructions.is_empty() {
+                self.blocks.insert(
+                    leader,
+                    BasicBlock {
+                        start: leader,
+                        end: address,
+                        instructions,
+                    },
+                );
+            }
+        }
+    }
+
+    fn build_edges(&mut self, disassembly: &RecursiveDisassembly) {
+        let mut edges = Vec::new();
+
+        for block in self.blocks.values() {
+            let last = block.last();
+            let Some(instruction) = last.instruction else {
+                continue;
+            };
+            let next = last.address + INSTRUCTION_SIZE;
+
+            match instruction {
+                Instruction::JumpToAddress { address } => {
+                    edges.push((block.start, last.address, address, EdgeKind::Jump));
+                }
+                Instruction::ExecuteSubroutine { address } => {
+                    edges.push((block.start, last.address, address, EdgeKind::Call));
+                    edges.push((block.start, last.address, next, EdgeKind::Fallthrough));
+                }
+                Instruction::ReturnFromSubroutine | Instruction::JumpToAddressPlusV0 { .. } => {}
+                instruction if is_skip(&instruction) => {
+                    edges.push((block.start, last.address, next, EdgeKind::Fallthrough));
+                    edges.push((
+                        block.start,
+                        last.address,
+                        next + INSTRUCTION_SIZE,
+                        EdgeKind::Skip,
+                    ));
+                }
+                _ => edges.push((block.start, last.address, next, EdgeKind::Fallthrough)),
+            }
+        }
+
+        for (from, address, to, kind) in edges {
+            match self.blocks.contains_key(&to) {
+                true => {
+                    self.edges.insert(Edge { from, to, kind });
+                }
+                false => self.report_missing_target(disassembly, address, to),
+            }
+        }
+    }
+
+    fn report_missing_target(
+        &mut self,
+        disassembly: &RecursiveDisassembly,
+        address: usize,
+        target: usize,
+    ) {
+        let range = disassembly.range();
+
+        let finding = if target < UNPROTECTED_MEMORY_START {
+            Finding::JumpIntoInterpreterArea { address, target }
+        } else if !range.contains(&target) {
+            Finding::JumpOutsideProgram { address, target }
+        } else {
+            // Invalid instructions are reported once by the lint pass
+            return;
+        };
+
+        self.findings.push(finding);
+    }
+
+    fn build_subroutines(&mut self) {
+        let mut entries = BTreeSet::from([self.entry]);
+        entries.extend(
+            self.edges
+                .iter()
+                .filter(|edge| edge.kind == EdgeKind::Call)
+                .map(|edge| edge.to),
+        );
+
+        for entry in entries {
+            let mut blocks = BTreeSet::new();
+            let mut callees = BTreeSet::new();
+            let mut pending = vec![entry];
+
+            while let Some(block) = pending.pop() {
+                if !blocks.insert(block) {
+                    continue;
+                }
+
+                for edge in self.successors(block) {
+                    match edge.kind {
+                        EdgeKind::Call => {
+                            callees.insert(edge.to);
+                        }
+                        EdgeKind::Return => {}
+                        _ => pending.push(edge.to),
+                    }
+                }
+            }
+
+            let returns = blocks.iter().any(|block| {
+                matches!(
+                    self.blocks[block].last().instruction,
+                    Some(Instruction::ReturnFromSubroutine)
+                )
+            });
+
+            self.subroutines.insert(
+                entry,
+                Subroutine {
+                    entry,
+                    blocks,
+                    callees,
+                    returns,
+                },
+            );
+        }
+    }
+
+    fn build_return_edges(&mut self) {
+        let mut edges = Vec::new();
+
+        for subroutine in self.subroutines.values() {
+            let return_sites: Vec<usize> = self
+                .edges
+                .iter()
+                .filter(|edge| edge.kind == EdgeKind::Call && edge.to == subroutine.entry)
+                .map(|edge| self.blocks[&edge.from].end)
+                .filter(|return_site| self.blocks.contains_key(return_site))
+                .collect();
+
+            for block in &subroutine.blocks {
+                if let Some(Instruction::ReturnFromSubroutine) =
+                    self.blocks[block].last().instruction
+                {
+                    edges.extend(return_sites.iter().map(|return_site| Edge {
+                        from: *block,
+                        to: *return_site,
+                        kind: EdgeKind::Return,
+                    }));
+                }
+            }
+        }
+
+        self.edges.extend(edges);
+    }
+
+    fn find_loops(&mut self) {
+        // Back edges of a depth first search over the graph, calls and returns are ignored so
+        // that every loop stays inside of its subroutine
+        let mut visited = BTreeSet::new();
+        let mut on_stack = BTreeSet::new();
+        let mut pending: Vec<(usize, bool)> = self
+            .subroutines
+            .keys()
+            .rev()
+            .map(|entry| (*entry, false))
+            .collect();
+
+        while let Some((block, finished)) = pending.pop() {
+            if finished {
+                on_stack.remove(&block);
+                continue;
+            }
+            if !visited.insert(block) {
+                continue;
+            }
+
+            on_stack.insert(block);
+            pending.push((block, true));
+
+            let successors: Vec<Edge> = self.successors(block).copied().collect();
+            for edge in successors {
+                if matches!(edge.kind, EdgeKind::Call | EdgeKind::Return) {
+                    continue;
+                }
+
+                if on_stack.contains(&edge.to) {
+                    self.loops.push(Loop {
+                        header: edge.to,
+                        latch: block,
+                    });
+                } else if !visited.contains(&edge.to) {
+                    pending.push((edge.to, false));
+                }
+            }
+        }
+
+        self.loops.sort_by_key(|found| (found.header, found.latch));
+    }
+
+    fn lint(&mut self, disassembly: &RecursiveDisassembly) {
+        for block in self.blocks.values() {
+            let last = block.last();
+
+            match last.instruction {
+                Some(Instruction::JumpToAddress { address }) if address == last.address => {
+                    self.findings.push(Finding::InfiniteLoop {
+                        address: last.address,
+                    });
+                }
+                Some(Instruction::JumpToAddressPlusV0 { address }) => {
+                    self.findings.push(Finding::UnknownJump {
+                        address: last.address,
+                        base: address,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(main) = self.subroutines.get(&self.entry) {
+            for block in &main.blocks {
+                let last = self.blocks[block].last();
+                if let Some(Instruction::ReturnFromSubroutine) = last.instruction {
+                    self.findings.push(Finding::ReturnOutsideSubroutine {
+                        address: last.address,
+                    });
+                }
+            }
+        }
+
+        // Two instructions starting one byte apart can only come from a jump into the middle
+        for edge in &self.edges {
+            let overlapped = edge.to.checked_sub(1);
+            if overlapped.is_some_and(|address| disassembly.instruction(address).is_some()) {
+                self.findings.push(Finding::JumpIntoInstruction {
+                    address: self.blocks[&edge.from].last().address,
+                    target: edge.to,
+                });
+            }
+        }
+
+        self.findings.extend(
+            disassembly
+                .invalid_instructions()
+                .map(|address| Finding::InvalidInstruction { address }),
+        );
+
+        let mut unreachable_start = None;
+        for address in disassembly
+            .range()
+            .chain(std::iter::once(disassembly.range().end))
+        {
+            let unknown = disassembly.kind(address) == Some(ByteKind::Unknown);
+
+            match (unknown, unreachable_start) {
+                (true, None) => unreachable_start = Some(address),
+                (false, Some(start)) => {
+                    self.findings.push(Finding::UnreachableBytes {
+                        start,
+                        end: address,
+                    });
+                    unreachable_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        self.findings
+            .sort_by_key(|finding| (finding.address(), finding.severity()));
+        self.findings.dedup();
+    }
+
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks.values()
+    }
+
+    pub fn block(&self, start: usize) -> Option<&BasicBlock> {
+        self.blocks.get(&start)
+    }
+
+    pub fn block_containing(&self, address: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=address)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| address < block.end)
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = &Edge> {
+        self.edges.iter()
+    }
+
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+
+    pub fn subroutines(&self) -> impl Iterator<Item = &Subroutine> {
+        self.subroutines.values()
+    }
+
+    pub fn subroutine(&self, entry: usize) -> Option<&Subroutine> {
+        self.subroutines.get(&entry)
+    }
+
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    pub fn write_dot<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "digraph cfg {{")?;
+        writeln!(writer, "  node [shape=box, fontname=monospace];")?;
+
+        // Blocks shared between subroutines are drawn in the cluster of the first one
+        let mut drawn = BTreeSet::new();
+        for subroutine in self.subroutines.values() {
+            writeln!(writer, "  subgraph cluster_{:03x} {{", subroutine.entry)?;
+            writeln!(
+                writer,
+                "    label=\"{}\";",
+                self.labels.format_address(subroutine.entry)
+            )?;
+
+            for block in &subroutine.blocks {
+                if drawn.insert(*block) {
+                    self.write_dot_block(writer, &self.blocks[block])?;
+                }
+            }
+
+            writeln!(writer, "  }}")?;
+        }
+
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "solid",
+                EdgeKind::Skip => "dashed",
+                EdgeKind::Jump => "bold",
+                EdgeKind::Call => "bold, color=blue",
+                EdgeKind::Return => "dotted, color=gray",
+            };
+            writeln!(
+                writer,
+                "  b{:03x} -> b{:03x} [style={}, label=\"{:?}\"];",
+                edge.from, edge.to, style, edge.kind
+            )?;
+        }
+
+        writeln!(writer, "}}")
+    }
+
+    fn write_dot_block<W: Write>(&self, writer: &mut W, block: &BasicBlock) -> io::Result<()> {
+        let mut label = String::new();
+        if let Some(name) = self.labels.label(block.start) {
+            label.push_str(&format!("{}:\\l", name));
+        }
+        for instruction in &block.instructions {
+            let text = self.labels.format_instruction(instruction);
+            label.push_str(&format!(
+                "{:#05x}  {}\\l",
+                instruction.address,
+                text.replace('"', "\\\"")
+            ));
+        }
+
+        writeln!(writer, "    b{:03x} [label=\"{}\"];", block.start, label)
+    }
+}
@@ -4,6 +4,8 @@ pub const UNPROTECTED_MEMORY_START: usize = 0x200;
 pub const FONT_SPRITE_MEMORY_LOCATION: usize = 0x000;
 pub const FONT_SPRITE_SIZE: usize = 5;
 pub const STACK_SIZE: usize = 24;
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 10;
+pub const FRAMES_PER_SECOND: u64 = 60;
 pub const MEMORY_SIZE: usize = 4096;
 
 pub const SCREEN_WIDTH: usize = 64;
@@ -0,0 +1,310 @@
+// Everything outside the CPU: memory, the I/O ports and the external flags
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+
+    // INP with port 1 to 7, the value read is also stored in memory by the CPU
+    fn input(&mut self, port: u8) -> u8;
+
+    // OUT with port 1 to 7
+    fn output(&mut self, port: u8, value: u8);
+
+    // Whether EF1 to EF4 is asserted
+    fn flag(&self, number: u8) -> bool;
+}
+
+// The RCA CDP1802 COSMAC, counting time in machine cycles of 8 clock cycles each
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cdp1802 {
+    pub registers: [u16; 16],
+    // Selects the program counter
+    pub p: u8,
+    // Selects the data pointer
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    // X and P saved by an interrupt
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+    idle: bool,
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Self {
+        Cdp1802 {
+            registers: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Clears X, P, Q and R0 and enables interrupts, the other registers keep their values
+    pub fn reset(&mut self) {
+        self.registers[0] = 0;
+        self.p = 0;
+        self.x = 0;
+        self.q = false;
+        self.ie = true;
+        self.idle = false;
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.registers[self.p as usize]
+    }
+
+    // Waiting after IDL for a DMA or interrupt request
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let p = self.p as usize;
+        let value = bus.read(self.registers[p]);
+        self.registers[p] = self.registers[p].wrapping_add(1);
+        value
+    }
+
+    fn rx(&self) -> u16 {
+        self.registers[self.x as usize]
+    }
+
+    fn increment(&mut self, register: u8) {
+        let register = &mut self.registers[register as usize];
+        *register = register.wrapping_add(1);
+    }
+
+    fn decrement(&mut self, register: u8) {
+        let register = &mut self.registers[register as usize];
+        *register = register.wrapping_sub(1);
+    }
+
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // DF is set when there is no borrow
+    fn subtract(&mut self, a: u8, b: u8, borrow: bool) {
+        let difference = a as i16 - b as i16 - borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+
+    fn condition(&self, bus: &impl Bus, n: u8) -> bool {
+        match n & 0x7 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            flag => bus.flag(flag - 3),
+        }
+    }
+
+    // One DMA out cycle, the byte at R0 goes to the device
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.registers[0]);
+        self.increment(0);
+        self.idle = false;
+        value
+    }
+
+    // Takes an interrupt if they are enabled, which costs one machine cycle
+    pub fn interrupt(&mut self) -> bool {
+        if !self.ie {
+            return false;
+        }
+
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        true
+    }
+
+    // Executes one instruction and returns the machine cycles it took
+    pub fn step(&mut self, bus: &mut impl Bus) -> u32 {
+        if self.idle {
+            return 1;
+        }
+
+        let opcode = self.fetch(bus);
+        let n = opcode & 0xF;
+        let rn = self.registers[n as usize];
+
+        match opcode >> 4 {
+            0x0 => match n {
+                0 => self.idle = true,
+                _ => self.d = bus.read(rn),
+            },
+            0x1 => self.increment(n),
+            0x2 => self.decrement(n),
+            0x3 => {
+                // Short branches replace the low byte of the program counter
+                let condition = self.condition(bus, n) != (n & 0x8 != 0);
+                let target = self.fetch(bus);
+                if condition {
+                    let p = self.p as usize;
+                    self.registers[p] = self.registers[p] & 0xFF00 | target as u16;
+                }
+            }
+            0x4 => {
+                self.d = bus.read(rn);
+                self.increment(n);
+            }
+            0x5 => bus.write(rn, self.d),
+            0x6 => match n {
+                0 => self.increment(self.x),
+                1..=7 => {
+                    let value = bus.read(self.rx());
+                    bus.output(n, value);
+                    self.increment(self.x);
+                }
+                // 68 is not an instruction on the 1802
+                8 => {}
+                _ => {
+                    self.d = bus.input(n - 8);
+                    bus.write(self.rx(), self.d);
+                }
+            },
+            0x7 => match n {
+                0x0 | 0x1 => {
+                    let value = bus.read(self.rx());
+                    self.increment(self.x);
+                    self.x = value >> 4;
+                    self.p = value & 0xF;
+                    self.ie = n == 0x0;
+                }
+                0x2 => {
+                    self.d = bus.read(self.rx());
+                    self.increment(self.x);
+                }
+                0x3 => {
+                    bus.write(self.rx(), self.d);
+                    self.decrement(self.x);
+                }
+                0x4 => self.add(bus.read(self.rx()), self.d, self.df),
+                0x5 => self.subtract(bus.read(self.rx()), self.d, !self.df),
+                0x6 => {
+                    let carry = self.df;
+                    self.df = self.d & 0x01 != 0;
+                    self.d = self.d >> 1 | (carry as u8) << 7;
+                }
+                0x7 => self.subtract(self.d, bus.read(self.rx()), !self.df),
+                0x8 => bus.write(self.rx(), self.t),
+                0x9 => {
+                    self.t = self.x << 4 | self.p;
+                    bus.write(self.registers[2], self.t);
+                    self.x = self.p;
+                    self.decrement(2);
+                }
+                0xA => self.q = false,
+                0xB => self.q = true,
+                0xC => {
+                    let value = self.fetch(bus);
+                    self.add(value, self.d, self.df);
+                }
+                0xD => {
+                    let value = self.fetch(bus);
+                    self.subtract(value, self.d, !self.df);
+                }
+                0xE => {
+                    let carry = self.df;
+                    self.df = self.d & 0x80 != 0;
+                    self.d = self.d << 1 | carry as u8;
+                }
+                _ => {
+                    let value = self.fetch(bus);
+                    self.subtract(self.d, value, !self.df);
+                }
+            },
+            0x8 => self.d = rn as u8,
+            0x9 => self.d = (rn >> 8) as u8,
+            0xA => self.registers[n as usize] = rn & 0xFF00 | self.d as u16,
+            0xB => self.registers[n as usize] = rn & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                self.long_branch(bus, n);
+                return 3;
+            }
+            0xD => self.p = n,
+            0xE => self.x = n,
+            _ => match n {
+                0x6 => {
+                    self.df = self.d & 0x01 != 0;
+                    self.d >>= 1;
+                }
+                0xE => {
+                    self.df = self.d & 0x80 != 0;
+                    self.d <<= 1;
+                }
+                _ => {
+                    // Immediate forms read their operand at the program counter instead of R(X)
+                    let value = match n {
+                        0x8.. => self.fetch(bus),
+                        _ => bus.read(self.rx()),
+                    };
+
+                    match n & 0x7 {
+                        0x0 => self.d = value,
+                        0x1 => self.d |= value,
+                        0x2 => self.d &= value,
+                        0x3 => self.d ^= value,
+                        0x4 => self.add(value, self.d, false),
+                        0x5 => self.subtract(value, self.d, false),
+                        _ => self.subtract(self.d, value, false),
+                    }
+                }
+            },
+        }
+
+        2
+    }
+
+    // The C0 to CF group of long branches and long skips, all three machine cycles long
+    fn long_branch(&mut self, bus: &mut impl Bus, n: u8) {
+        let p = self.p as usize;
+        let skip = match n {
+            // NOP
+            0x4 => return,
+            // Long skips
+            0x5 => !self.q,
+            0x6 => self.d != 0,
+            0x7 => !self.df,
+            0x8 => true,
+            0xC => self.ie,
+            0xD => self.q,
+            0xE => self.d == 0,
+            0xF => self.df,
+            // Long branches
+            _ => {
+                let condition = self.condition(bus, n & 0x3) != (n & 0x8 != 0);
+                let high = bus.read(self.registers[p]);
+                let low = bus.read(self.registers[p].wrapping_add(1));
+
+                self.registers[p] = match condition {
+                    true => (high as u16) << 8 | low as u16,
+                    false => self.registers[p].wrapping_add(2),
+                };
+                return;
+            }
+        };
+
+        if skip {
+            self.registers[p] = self.registers[p].wrapping_add(2);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+mod cdp1802;
+
+use thiserror::Error;
+
+pub use self::cdp1802::{Bus, Cdp1802};
+use super::constants::{DEFAULT_PROGRAM_ADDRESS, SCREEN_HEIGHT, SCREEN_WIDTH};
+use super::graphic::{BitSlicePixelView, Display, Screen};
+use super::keyboard::Key;
+use super::timing::VIP_MACHINE_CYCLES_PER_FRAME;
+
+pub const VIP_RAM_SIZE: usize = 4096;
+pub const MONITOR_ADDRESS: u16 = 0x8000;
+pub const MONITOR_SIZE: usize = 512;
+
+// CDP1861 video timing, in machine cycles and lines of the 60hz frame
+const CYCLES_PER_LINE: u32 = 14;
+const LINES_PER_FRAME: u32 = 262;
+const FIRST_DISPLAY_LINE: u32 = 80;
+const DISPLAY_LINES: u32 = 128;
+const DMA_BYTES_PER_LINE: usize = SCREEN_WIDTH / 8;
+// The interrupt comes 2 lines and EF1 4 lines before the display starts, EF1 again 4 lines
+// before it ends
+const INTERRUPT_LINES: u32 = 2;
+const EF1_LINES: u32 = 4;
+
+const FRAME_SIZE: usize = SCREEN_WIDTH / 8 * SCREEN_HEIGHT;
+
+#[derive(Error, Debug)]
+pub enum VipError {
+    #[error("{size} bytes do not fit into memory at {address:#06x}")]
+    DoesNotFit { address: usize, size: usize },
+    #[error("the monitor ROM has {0} bytes, at most 512 fit")]
+    InvalidMonitor(usize),
+}
+
+// Memory and I/O of the VIP as seen by the 1802
+struct Hardware {
+    ram: Vec<u8>,
+    monitor: Vec<u8>,
+    // After a reset the monitor also appears at 0000, until the first access with A15 high
+    monitor_at_zero: bool,
+    keys: [bool; 16],
+    key_latch: u8,
+    video_on: bool,
+    ef1: bool,
+}
+
+impl Bus for Hardware {
+    fn read(&mut self, address: u16) -> u8 {
+        if address & MONITOR_ADDRESS != 0 {
+            self.monitor_at_zero = false;
+        }
+
+        match address & MONITOR_ADDRESS != 0 || self.monitor_at_zero {
+            true => {
+                let offset = address as usize % MONITOR_SIZE;
+                self.monitor.get(offset).copied().unwrap_or(0)
+            }
+            false => self.ram[address as usize % self.ram.len()],
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address & MONITOR_ADDRESS == 0 {
+            let length = self.ram.len();
+            self.ram[address as usize % length] = value;
+        }
+    }
+
+    // INP 1 turns the display on
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.video_on = true;
+        }
+        0
+    }
+
+    // OUT 1 turns the display off, OUT 2 selects the key EF3 reports
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.video_on = false,
+            2 => self.key_latch = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn flag(&self, number: u8) -> bool {
+        match number {
+            1 => self.ef1,
+            3 => self.keys[self.key_latch as usize],
+            _ => false,
+        }
+    }
+}
+
+// The whole COSMAC VIP running the original CHIP-8 interpreter on an 1802, so machine code
+// called with 0NNN runs as well. The interpreter relies on routines of the monitor ROM,
+// including its display interrupt, so both images are needed.
+pub struct CosmacVip<D: Display = Screen> {
+    pub cpu: Cdp1802,
+    hardware: Hardware,
+    interpreter: Vec<u8>,
+    pub screen: D,
+    // The bytes the 1861 fetched for each row in the last frame, and what the screen shows
+    frame: [u8; FRAME_SIZE],
+    shown: [u8; FRAME_SIZE],
+    // Machine cycles the last instruction of a frame ran into the next one
+    cycle_debt: u32,
+}
+
+impl CosmacVip {
+    pub fn new(monitor: &[u8], interpreter: &[u8]) -> Result<Self, VipError> {
+        Self::with_display(Screen::default(), monitor, interpreter)
+    }
+}
+
+impl<D: Display> CosmacVip<D> {
+    pub fn with_display(display: D, monitor: &[u8], interpreter: &[u8]) -> Result<Self, VipError> {
+        if monitor.len() > MONITOR_SIZE {
+            return Err(VipError::InvalidMonitor(monitor.len()));
+        }
+        if interpreter.len() > VIP_RAM_SIZE {
+            return Err(VipError::DoesNotFit {
+                address: 0,
+                size: interpreter.len(),
+            });
+        }
+
+        let mut vip = CosmacVip {
+            cpu: Cdp1802::new(),
+            hardware: Hardware {
+                ram: vec![0; VIP_RAM_SIZE],
+                monitor: monitor.to_vec(),
+                monitor_at_zero: true,
+                keys: [false; 16],
+                key_latch: 0,
+                video_on: false,
+                ef1: false,
+            },
+            interpreter: interpreter.to_vec(),
+            screen: display,
+            frame: [0; FRAME_SIZE],
+            shown: [0; FRAME_SIZE],
+            cycle_debt: 0,
+        };
+        vip.reset();
+
+        Ok(vip)
+    }
+
+    // Clears the memory except for the interpreter and restarts at the monitor, which goes on
+    // to run the interpreter unless key C is held down
+    pub fn reset(&mut self) {
+        self.hardware.ram.fill(0);
+        self.hardware.ram[..self.interpreter.len()].copy_from_slice(&self.interpreter);
+        self.hardware.monitor_at_zero = true;
+        self.hardware.key_latch = 0;
+        self.hardware.video_on = false;
+        self.hardware.ef1 = false;
+        self.cpu.reset();
+        self.frame = [0; FRAME_SIZE];
+        self.cycle_debt = 0;
+    }
+
+    pub fn load_program(&mut self, program: &[u8]) -> Result<(), VipError> {
+        self.load_program_to_address(program, DEFAULT_PROGRAM_ADDRESS)
+    }
+
+    pub fn load_program_to_address(
+        &mut self,
+        program: &[u8],
+        address: usize,
+    ) -> Result<(), VipError> {
+        let end = address.checked_add(program.len());
+        if end.is_none_or(|end| end > self.hardware.ram.len()) {
+            return Err(VipError::DoesNotFit {
+                address,
+                size: program.len(),
+            });
+        }
+
+        self.reset();
+        self.hardware.ram[address..address + program.len()].copy_from_slice(program);
+
+        Ok(())
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.hardware.ram
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.hardware.ram
+    }
+
+    pub fn key_down(&mut self, key: Key) {
+        self.hardware.keys[u8::from(key) as usize] = true;
+    }
+
+    pub fn key_up(&mut self, key: Key) {
+        self.hardware.keys[u8::from(key) as usize] = false;
+    }
+
+    // The tone generator follows Q
+    pub fn is_sound_playing(&self) -> bool {
+        self.cpu.q
+    }
+
+    // Runs the 262 lines of one 60hz frame, the 1861 interrupting the CPU before the display
+    // area and fetching 8 bytes by DMA at the start of each of its lines
+    pub fn run_frame(&mut self) {
+        let display_end = FIRST_DISPLAY_LINE + DISPLAY_LINES;
+        let mut cycle = std::mem::take(&mut self.cycle_debt);
+        let mut displayed = false;
+
+        for line in 0..LINES_PER_FRAME {
+            let video_on = self.hardware.video_on;
+            self.hardware.ef1 = video_on
+                && ((FIRST_DISPLAY_LINE - EF1_LINES..FIRST_DISPLAY_LINE).contains(&line)
+                    || (display_end - EF1_LINES..display_end).contains(&line));
+            let interrupt = video_on
+                && (FIRST_DISPLAY_LINE - INTERRUPT_LINES..FIRST_DISPLAY_LINE).contains(&line);
+            let mut dma = video_on && (FIRST_DISPLAY_LINE..display_end).contains(&line);
+
+            // Requests are only served between instructions, like on the real CPU
+            let end = (line + 1) * CYCLES_PER_LINE;
+            while cycle < end {
+                if dma {
+                    self.dma_line(line);
+                    cycle += DMA_BYTES_PER_LINE as u32;
+                    dma = false;
+                    displayed = true;
+                } else if interrupt && self.cpu.interrupt() {
+                    cycle += 1;
+                } else {
+                    cycle += self.cpu.step(&mut self.hardware);
+                }
+            }
+        }
+
+        if !displayed {
+            self.frame = [0; FRAME_SIZE];
+        }
+        self.cycle_debt = cycle - VIP_MACHINE_CYCLES_PER_FRAME;
+        self.present();
+    }
+
+    // The interrupt routine repeats every row on 4 lines, the last one is what is shown
+    fn dma_line(&mut self, line: u32) {
+        let row = (line - FIRST_DISPLAY_LINE) as usize * SCREEN_HEIGHT / DISPLAY_LINES as usize;
+        for column in 0..DMA_BYTES_PER_LINE {
+            self.frame[row * DMA_BYTES_PER_LINE + column] = self.cpu.dma_out(&mut self.hardware);
+        }
+    }
+
+    // Brings the screen up to date by XORing the bits that changed
+    fn present(&mut self) {
+        for (index, (new, old)) in self.frame.iter().zip(&mut self.shown).enumerate() {
+            let changed = [*new ^ *old];
+            if changed[0] != 0 {
+                let sprite = BitSlicePixelView::new_from_byte_slice(&changed, 8, 1);
+                let x = index % DMA_BYTES_PER_LINE * 8;
+                let y = index / DMA_BYTES_PER_LINE;
+                self.screen.draw_sprite(x, y, &sprite, false);
+                *old = *new;
+            }
+        }
+
+        self.screen.present();
+    }
+}
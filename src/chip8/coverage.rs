@@ -0,0 +1,227 @@
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::ops::Range;
+
+use super::constants::MEMORY_SIZE;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryAccess {
+    pub read: bool,
+    pub written: bool,
+    pub executed: bool,
+}
+
+#[derive(Clone, Copy, Default)]
+struct AccessCounts {
+    reads: u32,
+    writes: u32,
+    executions: u32,
+}
+
+pub struct Coverage {
+    counts: Box<[AccessCounts; MEMORY_SIZE]>,
+}
+
+impl MemoryAccess {
+    pub fn is_untouched(&self) -> bool {
+        !(self.read || self.written || self.executed)
+    }
+}
+
+impl Display for MemoryAccess {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            if self.read { 'r' } else { '-' },
+            if self.written { 'w' } else { '-' },
+            if self.executed { 'x' } else { '-' }
+        )
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage {
+            counts: Box::new([AccessCounts::default(); MEMORY_SIZE]),
+        }
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.fill(AccessCounts::default());
+    }
+
+    fn counts_in_range(
+        &mut self,
+        address: usize,
+        byte_count: usize,
+    ) -> impl Iterator<Item = &mut AccessCounts> {
+        let start = address.min(MEMORY_SIZE);
+        let end = address.saturating_add(byte_count).min(MEMORY_SIZE);
+
+        self.counts[start..end].iter_mut()
+    }
+
+    pub fn record_read(&mut self, address: usize, byte_count: usize) {
+        for counts in self.counts_in_range(address, byte_count) {
+            counts.reads = counts.reads.saturating_add(1);
+        }
+    }
+
+    pub fn record_write(&mut self, address: usize, byte_count: usize) {
+        for counts in self.counts_in_range(address, byte_count) {
+            counts.writes = counts.writes.saturating_add(1);
+        }
+    }
+
+    pub fn record_execute(&mut self, address: usize, byte_count: usize) {
+        for counts in self.counts_in_range(address, byte_count) {
+            counts.executions = counts.executions.saturating_add(1);
+        }
+    }
+
+    // Addresses outside of the memory are never accessed
+    pub fn access(&self, address: usize) -> MemoryAccess {
+        let Some(counts) = self.counts.get(address) else {
+            return MemoryAccess::default();
+        };
+
+        MemoryAccess {
+            read: counts.reads > 0,
+            written: counts.writes > 0,
+            executed: counts.executions > 0,
+        }
+    }
+
+    pub fn access_ranges(&self, range: Range<usize>) -> Vec<(Range<usize>, MemoryAccess)> {
+        let mut ranges: Vec<(Range<usize>, MemoryAccess)> = Vec::new();
+
+        for address in range.start..range.end.min(MEMORY_SIZE) {
+            let access = self.access(address);
+
+            match ranges.last_mut() {
+                Some((last_range, last_access)) if *last_access == access => {
+                    last_range.end = address + 1;
+                }
+                _ => ranges.push((address..address + 1, access)),
+            }
+        }
+
+        ranges
+    }
+
+    pub fn write_report<W: Write>(&self, writer: &mut W, range: Range<usize>) -> io::Result<()> {
+        let ranges = self.access_ranges(range);
+
+        let total: usize = ranges.iter().map(|(range, _)| range.len()).sum();
+        let count_matching = |predicate: fn(&MemoryAccess) -> bool| -> usize {
+            ranges
+                .iter()
+                .filter(|(_, access)| predicate(access))
+                .map(|(range, _)| range.len())
+                .sum()
+        };
+        let percentage = |count: usize| count as f64 * 100.0 / total.max(1) as f64;
+
+        let executed = count_matching(|access| access.executed);
+        let read = count_matching(|access| access.read);
+        let written = count_matching(|access| access.written);
+        let untouched = count_matching(|access| access.is_untouched());
+
+        writeln!(writer, "bytes:     {:>6}", total)?;
+        writeln!(
+            writer,
+            "executed:  {:>6} {:>6.2}%",
+            executed,
+            percentage(executed)
+        )?;
+        writeln!(writer, "read:      {:>6} {:>6.2}%", read, percentage(read))?;
+        writeln!(
+            writer,
+            "written:   {:>6} {:>6.2}%",
+            written,
+            percentage(written)
+        )?;
+        writeln!(
+            writer,
+            "untouched: {:>6} {:>6.2}%",
+            untouched,
+            percentage(untouched)
+        )?;
+
+        writeln!(writer)?;
+        for (range, access) in ranges {
+            writeln!(
+                writer,
+                "{:#05x}..{:#05x} {} {}",
+                range.start,
+                range.end,
+                access,
+                range.len()
+            )?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "png")]
+    pub fn write_heatmap_png<W: Write>(
+        &self,
+        writer: W,
+        bytes_per_row: usize,
+        scale: usize,
+    ) -> Result<(), png::EncodingError> {
+        let bytes_per_row = bytes_per_row.max(1);
+        let scale = scale.max(1);
+        let rows = MEMORY_SIZE.div_ceil(bytes_per_row);
+        let width = bytes_per_row * scale;
+        let height = rows * scale;
+
+        let max_count = self
+            .counts
+            .iter()
+            .flat_map(|counts| [counts.reads, counts.writes, counts.executions])
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        // Logarithmic scale so that rarely touched bytes remain visible next to hot loops
+        let intensity = |count: u32| -> u8 {
+            if count == 0 {
+                return 0;
+            }
+            let ratio = (count as f64).ln_1p() / (max_count as f64).ln_1p();
+            (64.0 + ratio * 191.0) as u8
+        };
+
+        let mut image = vec![0u8; width * height * 3];
+        for (address, counts) in self.counts.iter().enumerate() {
+            // Red channel shows writes, green channel reads and blue channel executions
+            let color = [
+                intensity(counts.writes),
+                intensity(counts.reads),
+                intensity(counts.executions),
+            ];
+
+            let origin_x = (address % bytes_per_row) * scale;
+            let origin_y = (address / bytes_per_row) * scale;
+            for y in origin_y..origin_y + scale {
+                for x in origin_x..origin_x + scale {
+                    let offset = (y * width + x) * 3;
+                    image[offset..offset + 3].copy_from_slice(&color);
+                }
+            }
+        }
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&image)
+    }
+}
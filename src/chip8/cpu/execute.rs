@@ -1,36 +1,54 @@
+use rand::Rng;
 use thiserror::Error;
-use crate::Chip8;
+
 use crate::chip8::Blocked;
-use crate::constants::{FONT_SPRITE_MEMORY_LOCATION, FONT_SPRITE_SIZE, INSTRUCTION_SIZE};
+use crate::constants::{
+    FONT_SPRITE_MEMORY_LOCATION, FONT_SPRITE_SIZE, INSTRUCTION_SIZE, STACK_SIZE,
+};
 use crate::data_register::DataRegister;
-use crate::graphic::{PixelView, XorPixelErased};
+use crate::events::Event;
+use crate::extension::ExtensionError;
+use crate::graphic::Display;
+use crate::hypercall::HypercallError;
 use crate::instruction::Instruction;
-use crate::keyboard::{Key, KeyState};
-
+use crate::keyboard::{Key, KeyState, KeyWait};
+use crate::Chip8;
 
 #[derive(Error, Debug)]
 pub enum InstructionExecutionError {
     #[error("invalid return, no address on stack to jump back to")]
     InvalidReturn,
+    #[error("stack overflow, no room for more than {STACK_SIZE} return addresses")]
+    StackOverflow,
     #[error("invalid memory access, attempted to access {0:#04x}")]
     InvalidMemoryAccess(usize),
     #[error("invalid key {0:#01x} specified ")]
     InvalidKey(u8),
+    #[error("hypercall {0:#05x} failed: {1}")]
+    HypercallFailed(usize, HypercallError),
+    #[error("opcode {0:#06x} failed: {1}")]
+    ExtensionFailed(u16, ExtensionError),
 }
 
-impl Chip8 {
+impl<D: Display> Chip8<D> {
     pub fn execute_instruction(
         &mut self,
         instruction: Instruction,
     ) -> Result<(), InstructionExecutionError> {
         match instruction {
-            Instruction::ExecuteMachineLanguageSubroutine { .. } => {
+            Instruction::ExecuteMachineLanguageSubroutine { address } => {
                 // This instruction is only used on the old computers on which Chip-8 was originally implemented. It is ignored by modern interpreters.
-                Ok(())
+                // Registered hypercalls take the place of the machine code routine
+                self.hypercall(address)
+                    .map_err(|error| InstructionExecutionError::HypercallFailed(address, error))
             }
             Instruction::ClearScreen => {
                 self.screen.clear();
 
+                if let Some(events) = &mut self.events {
+                    events.push(Event::ScreenCleared);
+                }
+
                 Ok(())
             }
             Instruction::ReturnFromSubroutine => {
@@ -41,6 +59,12 @@ impl Chip8 {
                     .ok_or(InstructionExecutionError::InvalidReturn)?;
                 self.in_jump = true;
 
+                if let Some(events) = &mut self.events {
+                    events.push(Event::SubroutineReturned {
+                        to: self.program_counter,
+                    });
+                }
+
                 Ok(())
             }
             Instruction::JumpToAddress { address } => {
@@ -52,6 +76,17 @@ impl Chip8 {
 
             Instruction::ExecuteSubroutine { address } => {
                 // The interpreter increments the stack pointer, then puts the current PC on the top of the stack. The PC is then set to nnn.
+                if self.stack.len() >= STACK_SIZE {
+                    return Err(InstructionExecutionError::StackOverflow);
+                }
+
+                if let Some(events) = &mut self.events {
+                    events.push(Event::SubroutineCalled {
+                        from: self.program_counter,
+                        to: address,
+                    });
+                }
+
                 self.stack.push(self.program_counter + INSTRUCTION_SIZE);
                 self.program_counter = address;
                 self.in_jump = true;
@@ -90,7 +125,8 @@ impl Chip8 {
             }
 
             Instruction::AddNumToVx { vx, num } => {
-                self.data_registers[vx] += num;
+                // The carry flag is not changed, the value simply wraps around
+                self.data_registers[vx] = self.data_registers[vx].wrapping_add(num);
 
                 Ok(())
             }
@@ -102,19 +138,22 @@ impl Chip8 {
             }
 
             Instruction::SetVxToVxOrVy { vx, vy } => {
-                self.data_registers[vx] = self.data_registers[vx] | self.data_registers[vy];
+                self.data_registers[vx] |= self.data_registers[vy];
+                self.apply_logic_quirk();
 
                 Ok(())
             }
 
             Instruction::SetVxToVxAndVy { vx, vy } => {
-                self.data_registers[vx] = self.data_registers[vx] & self.data_registers[vy];
+                self.data_registers[vx] &= self.data_registers[vy];
+                self.apply_logic_quirk();
 
                 Ok(())
             }
 
             Instruction::SetVxToVxXorVy { vx, vy } => {
-                self.data_registers[vx] = self.data_registers[vx] ^ self.data_registers[vy];
+                self.data_registers[vx] ^= self.data_registers[vy];
+                self.apply_logic_quirk();
 
                 Ok(())
             }
@@ -147,11 +186,13 @@ impl Chip8 {
             }
 
             Instruction::ShiftVyRightStoreInVx { vx, vy } => {
+                let value = self.data_registers[self.shift_source(vx, vy)];
+
                 // Set register REG_F to the least significant bit prior to the shift
-                self.data_registers[DataRegister::VF] = self.data_registers[vy] & 1;
+                self.data_registers[DataRegister::VF] = value & 1;
 
                 // Store the value of register vy shifted right one bit in register vx
-                self.data_registers[vx] = self.data_registers[vy] >> 1;
+                self.data_registers[vx] = value >> 1;
 
                 Ok(())
             }
@@ -170,11 +211,13 @@ impl Chip8 {
             }
 
             Instruction::ShiftVyLeftStoreInVx { vx, vy } => {
+                let value = self.data_registers[self.shift_source(vx, vy)];
+
                 // Set register REG_F to the most significant bit prior to the shift
-                self.data_registers[DataRegister::VF] = self.data_registers[vy] >> 7;
+                self.data_registers[DataRegister::VF] = value >> 7;
 
                 // Store the value of register vy shifted left one bit in register vx
-                self.data_registers[vx] = self.data_registers[vy] << 1;
+                self.data_registers[vx] = value << 1;
 
                 Ok(())
             }
@@ -190,21 +233,25 @@ impl Chip8 {
 
             Instruction::StoreAddressInAddressRegister { address } => {
                 // Store memory address NNN in register Address Register
-                self.address_register = address as usize;
+                self.address_register = address;
 
                 Ok(())
             }
 
             Instruction::JumpToAddressPlusV0 { address } => {
-                // Jump to address NNN + V0
-                self.program_counter = address + self.data_registers[DataRegister::V0] as usize;
+                // Jump to address NNN + V0, or XNN + VX with the jump quirk
+                let offset_register = match self.quirks.jump {
+                    true => DataRegister::try_from((address >> 8) as u8 & 0xF).unwrap(),
+                    false => DataRegister::V0,
+                };
+                self.program_counter = address + self.data_registers[offset_register] as usize;
                 self.in_jump = true;
 
                 Ok(())
             }
 
             Instruction::SetVxToRandomWithMask { vx, mask } => {
-                self.data_registers[vx] = rand::random::<u8>() & mask;
+                self.data_registers[vx] = self.rng.gen::<u8>() & mask;
 
                 Ok(())
             }
@@ -222,41 +269,49 @@ impl Chip8 {
                         )
                     })?;
 
-                let sprite_x_pos = self.data_registers[vx] as usize;
-                let sprite_y_pos = self.data_registers[vy] as usize;
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_read(self.address_register, byte_count as usize);
+                }
 
-                let mut pixel_erased = XorPixelErased::No;
+                // The start position always wraps, the sprite itself only with the wrap quirk
+                let resolution = self.screen.resolution();
+                let sprite_x_pos = self.data_registers[vx] as usize % resolution.width();
+                let sprite_y_pos = self.data_registers[vy] as usize % resolution.height();
 
-                for y in 0..sprite.height() {
-                    for x in 0..sprite.width() {
-                        let current_pixel_erased = self.screen.xor_pixel_wrapped_position(
-                            sprite_x_pos + x,
-                            sprite_y_pos + y,
-                            sprite.get_pixel_unchecked(x, y),
-                        );
-
-                        if let XorPixelErased::Yes = current_pixel_erased {
-                            pixel_erased = XorPixelErased::Yes;
-                        }
-                    }
-                }
+                let pixel_erased =
+                    self.screen
+                        .draw_sprite(sprite_x_pos, sprite_y_pos, &sprite, self.quirks.wrap);
 
                 self.data_registers[DataRegister::VF] = match pixel_erased {
-                    XorPixelErased::Yes => 1, // If this causes any pixels to be erased, VF is set to 1
-                    XorPixelErased::No => 0,  // Otherwise it is set to 0
+                    true => 1,  // If this causes any pixels to be erased, VF is set to 1
+                    false => 0, // Otherwise it is set to 0
                 };
 
+                if let Some(events) = &mut self.events {
+                    let rows = &self.memory.raw_data
+                        [self.address_register..self.address_register + byte_count as usize];
+                    events.push(Event::SpriteDrawn {
+                        x: sprite_x_pos,
+                        y: sprite_y_pos,
+                        rows: rows.to_vec(),
+                        collision: pixel_erased,
+                    });
+                }
+
+                if self.quirks.vblank {
+                    self.waiting_for_vblank = true;
+                }
+
                 Ok(())
             }
 
             Instruction::SkipIfKeyInVxPressed { vx } => {
                 // Skip next instruction if key with the value of Vx is pressed.
-                if let KeyState::Pressed =
-                    self.keyboard
-                        .get_key_state(Key::try_from(self.data_registers[vx]).map_err(|_| {
-                            InstructionExecutionError::InvalidKey(self.data_registers[vx])
-                        })?)
-                {
+                if let KeyState::Pressed = self.keyboard.observed_key_state(
+                    Key::try_from(self.data_registers[vx]).map_err(|_| {
+                        InstructionExecutionError::InvalidKey(self.data_registers[vx])
+                    })?,
+                ) {
                     self.program_counter += INSTRUCTION_SIZE;
                 }
 
@@ -265,12 +320,11 @@ impl Chip8 {
 
             Instruction::SkipIfKeyInVxNotPressed { vx } => {
                 // Skip next instruction if key with the value of Vx is not pressed.
-                if let KeyState::Released =
-                    self.keyboard
-                        .get_key_state(Key::try_from(self.data_registers[vx]).map_err(|_| {
-                            InstructionExecutionError::InvalidKey(self.data_registers[vx])
-                        })?)
-                {
+                if let KeyState::Released = self.keyboard.observed_key_state(
+                    Key::try_from(self.data_registers[vx]).map_err(|_| {
+                        InstructionExecutionError::InvalidKey(self.data_registers[vx])
+                    })?,
+                ) {
                     self.program_counter += INSTRUCTION_SIZE;
                 }
 
@@ -285,10 +339,18 @@ impl Chip8 {
             }
 
             Instruction::WaitForKeypressStoreInVx { vx } => {
-                self.blocked = Blocked::WaitingOnKeyUp(vx);
+                self.blocked = match self.key_wait {
+                    KeyWait::Accurate => Blocked::WaitingForKeyPress(vx),
+                    KeyWait::Legacy => Blocked::WaitingOnKeyUp(vx),
+                };
+
+                if let Some(events) = &mut self.events {
+                    events.push(Event::WaitingForKey(vx));
+                }
 
                 Ok(())
-            },
+            }
+
             Instruction::SetDelayTimerToVx { vx } => {
                 self.delay_timer = self.data_registers[vx];
 
@@ -297,8 +359,17 @@ impl Chip8 {
 
             Instruction::SetSoundTimerToVx { vx } => {
                 // Set the sound timer to the value of register VX
+                let was_sounding = self.sound_timer > 0;
                 self.sound_timer = self.data_registers[vx];
 
+                if let Some(events) = &mut self.events {
+                    match (was_sounding, self.sound_timer > 0) {
+                        (false, true) => events.push(Event::SoundStarted),
+                        (true, false) => events.push(Event::SoundStopped),
+                        _ => {}
+                    }
+                }
+
                 Ok(())
             }
 
@@ -326,6 +397,10 @@ impl Chip8 {
                     ));
                 }
 
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_write(self.address_register, 3);
+                }
+
                 // Hundreds digit in memory at location in I
                 self.memory.raw_data[self.address_register] = num / 100;
 
@@ -342,17 +417,19 @@ impl Chip8 {
                 // Store the values of registers V0 to VX inclusive in memory starting at address I
                 for register_num in 0..=vx.into() {
                     let memory_address = self.address_register + register_num as usize;
-                    let memory_ref =
-                        self.memory
-                            .raw_data
-                            .get_mut(memory_address)
-                            .ok_or_else(|| {
-                                InstructionExecutionError::InvalidMemoryAccess(memory_address)
-                            })?;
+                    let memory_ref = self.memory.raw_data.get_mut(memory_address).ok_or(
+                        InstructionExecutionError::InvalidMemoryAccess(memory_address),
+                    )?;
 
                     *memory_ref = self.data_registers[register_num.try_into().unwrap()];
                 }
 
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_write(self.address_register, u8::from(vx) as usize + 1);
+                }
+
+                self.address_register += self.quirks.memory_increment(vx.into());
+
                 Ok(())
             }
 
@@ -363,13 +440,32 @@ impl Chip8 {
                     let memory_address = self.address_register + register_num as usize;
 
                     self.data_registers[register_num.try_into().unwrap()] =
-                        *self.memory.raw_data.get(memory_address).ok_or_else(|| {
-                            InstructionExecutionError::InvalidMemoryAccess(memory_address)
-                        })?
+                        *self.memory.raw_data.get(memory_address).ok_or(
+                            InstructionExecutionError::InvalidMemoryAccess(memory_address),
+                        )?
+                }
+
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_read(self.address_register, u8::from(vx) as usize + 1);
                 }
 
+                self.address_register += self.quirks.memory_increment(vx.into());
+
                 Ok(())
             }
         }
     }
+
+    fn apply_logic_quirk(&mut self) {
+        if self.quirks.logic {
+            self.data_registers[DataRegister::VF] = 0;
+        }
+    }
+
+    fn shift_source(&self, vx: DataRegister, vy: DataRegister) -> DataRegister {
+        match self.quirks.shift {
+            true => vx,
+            false => vy,
+        }
+    }
 }
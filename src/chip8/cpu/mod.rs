@@ -3,9 +3,14 @@ use thiserror::Error;
 
 use self::execute::InstructionExecutionError;
 use super::constants::INSTRUCTION_SIZE;
+use super::events::Event;
+use super::graphic::Display;
 use super::{Blocked, Chip8};
 use crate::chip8::Key;
+use crate::instruction::Instruction;
+use crate::keyboard::KeyWait;
 use crate::memory::ReadInstructionError;
+use crate::timing::TimingModel;
 
 #[derive(Error, Debug)]
 pub enum CycleError {
@@ -15,14 +20,50 @@ pub enum CycleError {
     ExecutionError(#[from] InstructionExecutionError),
 }
 
-impl Chip8 {
+impl<D: Display> Chip8<D> {
     pub fn cycle(&mut self) -> Result<(), CycleError> {
+        self.execute_next()?;
+
+        Ok(())
+    }
+
+    // Executes the next instruction like cycle, returns true when an extension or the unknown
+    // opcode policy took it instead of the built-in instructions
+    pub(crate) fn execute_next(&mut self) -> Result<bool, CycleError> {
         if self.blocked != Blocked::No {
-            return Ok(());
+            return Ok(false);
+        }
+
+        if self.has_custom_opcodes() && self.execute_custom_opcode(self.program_counter)? {
+            self.keyboard.mark_observed();
+            return Ok(true);
         }
 
         let instruction = self.memory.read_instruction(self.program_counter)?;
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_execute(self.program_counter, INSTRUCTION_SIZE);
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record_execution(self.program_counter, &instruction);
+        }
+
+        let address = self.program_counter;
         self.execute_instruction(instruction)?;
+        self.keyboard.mark_observed();
+
+        if let Some(events) = &mut self.events {
+            let halted = matches!(
+                instruction,
+                Instruction::JumpToAddress { address: target } if target == address
+            );
+            events.set_halted(halted, address);
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record_control_flow(&instruction);
+        }
 
         // Auto-Increment only when not in jump
         if !self.in_jump {
@@ -30,18 +71,103 @@ impl Chip8 {
         }
         self.in_jump = false;
 
+        Ok(false)
+    }
+
+    // Runs the instructions of one 60hz frame followed by a timer update, or the rest of the
+    // frame when step_instruction already started it
+    pub fn run_frame(&mut self) -> Result<(), CycleError> {
+        let frames = self.frames;
+
+        while self.frames == frames {
+            self.step_instruction()?;
+        }
+
         Ok(())
     }
 
+    // Runs the current frame up to and including its next instruction. Returns false when the
+    // frame ended before one ran, because its instructions are used up or the machine idles
+    // until the next frame.
+    pub fn step_instruction(&mut self) -> Result<bool, CycleError> {
+        let executed = match self.timing {
+            TimingModel::Fixed => self.step_fixed_frame()?,
+            TimingModel::CosmacVip => self.step_vip_frame()?,
+        };
+
+        if !executed {
+            self.waiting_for_vblank = false;
+            self.update_timers();
+            self.screen.present();
+            self.frames += 1;
+        }
+
+        Ok(executed)
+    }
+
+    fn step_fixed_frame(&mut self) -> Result<bool, CycleError> {
+        let instructions = self.instructions_per_frame as u64;
+
+        while (self.frame_position as u64) < instructions {
+            if !self.input.is_empty() {
+                self.apply_input(self.frame_time(self.frame_position as u64, instructions));
+            }
+
+            // With the vblank quirk a sprite draw ends the frame, queued input may still unblock
+            if self.waiting_for_vblank || self.blocked != Blocked::No {
+                match self.input.is_empty() {
+                    true => break,
+                    false => {
+                        self.frame_position += 1;
+                        continue;
+                    }
+                }
+            }
+
+            self.cycle()?;
+            self.frame_position += 1;
+
+            return Ok(true);
+        }
+
+        self.frame_position = 0;
+
+        Ok(false)
+    }
+
+    pub fn handle_key_down_interrupt(&mut self, key: Key) {
+        if let Blocked::WaitingForKeyPress(vx) = self.blocked {
+            self.blocked = Blocked::WaitingForKeyRelease(vx, key);
+
+            if let (0, Some(events)) = (self.sound_timer, &mut self.events) {
+                events.push(Event::SoundStarted);
+            }
+        }
+    }
+
     pub fn handle_key_up_interrupt(&mut self, key: Key) {
-        if let Blocked::WaitingOnKeyUp(vx) = self.blocked {
-            self.data_registers[vx] = u8::from(key);
-            self.blocked = Blocked::No;
+        let vx = match self.blocked {
+            Blocked::WaitingOnKeyUp(vx) => vx,
+            // Only the release of the key pressed during the wait counts
+            Blocked::WaitingForKeyRelease(vx, pressed) if pressed == key => {
+                if let (0, Some(events)) = (self.sound_timer, &mut self.events) {
+                    events.push(Event::SoundStopped);
+                }
+                vx
+            }
+            _ => return,
+        };
+
+        self.data_registers[vx] = u8::from(key);
+        self.blocked = Blocked::No;
+
+        if let Some(events) = &mut self.events {
+            events.push(Event::KeyConsumed { key, register: vx });
         }
     }
 
     pub fn update_timers(&mut self) {
-        if self.blocked != Blocked::No {
+        if self.blocked != Blocked::No && self.key_wait == KeyWait::Legacy {
             return;
         }
 
@@ -52,6 +178,12 @@ impl Chip8 {
 
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
+
+            // A key held during FX0A keeps the buzzer going
+            let stopped = !self.is_sound_playing();
+            if let (true, Some(events)) = (stopped, &mut self.events) {
+                events.push(Event::SoundStopped);
+            }
         }
     }
 }
@@ -0,0 +1,882 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Component, Path};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use super::constants::{INSTRUCTION_SIZE, MEMORY_SIZE};
+use super::data_register::DataRegister;
+use super::debugger::{Breakpoint, Debugger, StopReason};
+use super::disassembler::DisassembledInstruction;
+use super::expression::{Expression, LogMessage};
+use super::graphic::{Display, Screen};
+use super::instruction::Instruction;
+use super::symbols::SymbolTable;
+use super::Chip8;
+
+const THREAD_ID: u64 = 1;
+const CYCLES_PER_REQUEST_POLL: usize = 1024;
+
+const REGISTERS_REFERENCE: u64 = 1;
+const TIMERS_REFERENCE: u64 = 2;
+const STACK_REFERENCE: u64 = 3;
+const MEMORY_REFERENCE: u64 = 4;
+
+const MEMORY_ROW_SIZE: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum DapError {
+    #[error("connection error")]
+    Io(#[from] io::Error),
+    #[error("invalid message")]
+    Json(#[from] serde_json::Error),
+    #[error("message without content length header")]
+    MissingContentLength,
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    pub path: String,
+    lines: BTreeMap<usize, usize>,
+}
+
+impl SourceMap {
+    pub fn new(path: String) -> Self {
+        SourceMap {
+            path,
+            lines: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, line: usize, address: usize) {
+        self.lines.insert(line, address);
+    }
+
+    // Lines without code resolve to the next line that has an address
+    pub fn line_to_address(&self, line: usize) -> Option<(usize, usize)> {
+        self.lines
+            .range(line..)
+            .next()
+            .map(|(line, address)| (*line, *address))
+    }
+
+    pub fn address_to_line(&self, address: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .filter(|(_, line_address)| **line_address <= address)
+            .max_by_key(|(line, line_address)| (**line_address, **line))
+            .map(|(line, _)| *line)
+    }
+}
+
+enum Goal {
+    Breakpoint,
+    Return { address: usize, stack_depth: usize },
+    StackDepthBelow(usize),
+}
+
+pub struct DapServer<W: Write, D: Display = Screen> {
+    chip8: Chip8<D>,
+    debugger: Debugger,
+    writer: W,
+    requests: Receiver<Result<Value, DapError>>,
+    sequence: u64,
+    source_map: Option<SourceMap>,
+    symbols: SymbolTable,
+    line_breakpoints: Vec<(usize, Breakpoint)>,
+    instruction_breakpoints: Vec<(usize, Breakpoint)>,
+    stop_on_entry: bool,
+    goal: Option<Goal>,
+}
+
+pub fn serve_stdio() -> Result<(), DapError> {
+    DapServer::new(io::stdin(), io::stdout()).run()
+}
+
+fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>, DapError> {
+    let mut content_length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut content = vec![0u8; content_length.ok_or(DapError::MissingContentLength)?];
+    reader.read_exact(&mut content)?;
+
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+fn parse_address(value: &Value) -> Option<usize> {
+    match value {
+        Value::Number(number) => number.as_u64().map(|number| number as usize),
+        Value::String(string) => match string.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => string.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+fn path_components(path: &str) -> Vec<Component<'_>> {
+    Path::new(path)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+// Relative names in the symbols match the trailing components of the source path
+fn is_same_source(source: &str, file: &str) -> bool {
+    let file = path_components(file);
+
+    !file.is_empty() && path_components(source).ends_with(&file)
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - index * 6)) & 0x3F] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+impl<W: Write> DapServer<W> {
+    pub fn new<R: Read + Send + 'static>(reader: R, writer: W) -> Self {
+        Self::with_display(reader, writer, Screen::new())
+    }
+}
+
+impl<W: Write, D: Display> DapServer<W, D> {
+    // Debugs on a display provided by the frontend
+    pub fn with_display<R: Read + Send + 'static>(reader: R, writer: W, display: D) -> Self {
+        let (sender, requests) = mpsc::channel();
+
+        // Requests are read on a separate thread so that a running program can be paused
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            loop {
+                match read_message(&mut reader) {
+                    Ok(Some(message)) => {
+                        if sender.send(Ok(message)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => return,
+                    Err(err) => {
+                        let _ = sender.send(Err(err));
+                        return;
+                    }
+                }
+            }
+        });
+
+        DapServer {
+            chip8: Chip8::with_display(display),
+            debugger: Debugger::new(),
+            writer,
+            requests,
+            sequence: 0,
+            source_map: None,
+            symbols: SymbolTable::new(),
+            line_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+            stop_on_entry: false,
+            goal: None,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), DapError> {
+        loop {
+            let message = match self.goal {
+                Some(_) => match self.requests.try_recv() {
+                    Ok(message) => Some(message),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                },
+                None => match self.requests.recv() {
+                    Ok(message) => Some(message),
+                    Err(_) => return Ok(()),
+                },
+            };
+
+            match message {
+                Some(message) => {
+                    if !self.handle_request(&message?)? {
+                        return Ok(());
+                    }
+                }
+                None => self.run_chunk()?,
+            }
+        }
+    }
+
+    fn run_chunk(&mut self) -> Result<(), DapError> {
+        let stop_reason = match self.goal {
+            Some(Goal::Breakpoint) => self
+                .debugger
+                .resume(&mut self.chip8, CYCLES_PER_REQUEST_POLL),
+            Some(Goal::Return {
+                address,
+                stack_depth,
+            }) => self
+                .debugger
+                .run_until(&mut self.chip8, CYCLES_PER_REQUEST_POLL, |chip8| {
+                    chip8.program_counter == address && chip8.stack.len() == stack_depth
+                }),
+            Some(Goal::StackDepthBelow(stack_depth)) => {
+                self.debugger
+                    .run_until(&mut self.chip8, CYCLES_PER_REQUEST_POLL, |chip8| {
+                        chip8.stack.len() < stack_depth
+                    })
+            }
+            None => return Ok(()),
+        };
+
+        self.handle_stop(stop_reason)
+    }
+
+    fn format_value(value: Option<i64>) -> String {
+        value.map_or_else(|| "<error>".to_string(), |value| format!("{:#x}", value))
+    }
+
+    fn send_log_messages(&mut self) -> Result<(), DapError> {
+        for message in self.debugger.take_log_messages() {
+            self.send_event(
+                "output",
+                json!({ "category": "console", "output": format!("{}\n", message) }),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_stop(&mut self, stop_reason: StopReason) -> Result<(), DapError> {
+        self.send_log_messages()?;
+
+        let (reason, description) = match stop_reason {
+            StopReason::CycleLimit => return Ok(()),
+            StopReason::Step => ("step", None),
+            StopReason::Breakpoint(_) => ("breakpoint", None),
+            StopReason::Blocked => ("pause", Some("waiting for a key press".to_string())),
+            StopReason::Watch {
+                index,
+                old_value,
+                new_value,
+            } => (
+                "data breakpoint",
+                Some(format!(
+                    "{} changed from {} to {}",
+                    self.debugger.watches()[index].expression,
+                    Self::format_value(old_value),
+                    Self::format_value(new_value)
+                )),
+            ),
+            StopReason::Error(err) => (
+                "exception",
+                Some(self.symbols.describe_error(&err, &self.chip8)),
+            ),
+        };
+
+        self.goal = None;
+        self.send_stopped(reason, description)
+    }
+
+    fn send_stopped(&mut self, reason: &str, description: Option<String>) -> Result<(), DapError> {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(description) = description {
+            body["description"] = json!(description);
+            body["text"] = json!(description);
+        }
+
+        self.send_event("stopped", body)
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<(), DapError> {
+        self.sequence += 1;
+        message["seq"] = json!(self.sequence);
+
+        let content = serde_json::to_vec(&message)?;
+        write!(self.writer, "Content-Length: {}\r\n\r\n", content.len())?;
+        self.writer.write_all(&content)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> Result<(), DapError> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send_response(
+        &mut self,
+        request: &Value,
+        result: Result<Value, String>,
+    ) -> Result<(), DapError> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+        });
+
+        match result {
+            Ok(body) => {
+                response["success"] = json!(true);
+                response["body"] = body;
+            }
+            Err(message) => {
+                response["success"] = json!(false);
+                response["message"] = json!(message);
+            }
+        }
+
+        self.send(response)
+    }
+
+    fn handle_request(&mut self, request: &Value) -> Result<bool, DapError> {
+        let arguments = request["arguments"].clone();
+        let command = request["command"].as_str().unwrap_or_default();
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConditionalBreakpoints": true,
+                "supportsConfigurationDoneRequest": true,
+                "supportsDisassembleRequest": true,
+                "supportsEvaluateForHovers": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsLogPoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsSetVariable": true,
+                "supportsSteppingGranularity": true,
+            })),
+            "launch" => self.launch(&arguments),
+            "setBreakpoints" => self.set_breakpoints(&arguments),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(&arguments),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "chip8" }] })),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(self.scopes()),
+            "variables" => Ok(self.variables(&arguments)),
+            "setVariable" => self.set_variable(&arguments),
+            "disassemble" => self.disassemble(&arguments),
+            "readMemory" => self.read_memory(&arguments),
+            "evaluate" => self.evaluate(&arguments),
+            "continue" => {
+                self.goal = Some(Goal::Breakpoint);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" | "stepIn" | "stepOut" | "pause" => Ok(json!({})),
+            "disconnect" | "terminate" => {
+                self.send_response(request, Ok(json!({})))?;
+                self.send_event("terminated", json!({}))?;
+                return Ok(false);
+            }
+            _ => Err(format!("unsupported request '{}'", command)),
+        };
+
+        self.send_response(request, result)?;
+
+        // Execution only starts once the response to the request has been sent
+        match command {
+            "initialize" => self.send_event("initialized", json!({}))?,
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.send_stopped("entry", None)?;
+                } else {
+                    self.goal = Some(Goal::Breakpoint);
+                }
+            }
+            "next" => self.next()?,
+            "stepIn" => {
+                let stop_reason = self.debugger.step(&mut self.chip8);
+                self.handle_stop(stop_reason)?;
+            }
+            "stepOut" => {
+                self.goal = Some(Goal::StackDepthBelow(self.chip8.stack.len()));
+                if self.chip8.stack.is_empty() {
+                    self.goal = Some(Goal::Breakpoint);
+                }
+            }
+            "pause" if self.goal.is_some() => {
+                self.goal = None;
+                self.send_stopped("pause", None)?;
+            }
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    fn next(&mut self) -> Result<(), DapError> {
+        match self
+            .chip8
+            .memory
+            .read_instruction(self.chip8.program_counter)
+        {
+            Ok(Instruction::ExecuteSubroutine { .. }) => {
+                self.goal = Some(Goal::Return {
+                    address: self.chip8.program_counter + INSTRUCTION_SIZE,
+                    stack_depth: self.chip8.stack.len(),
+                });
+                Ok(())
+            }
+            _ => {
+                let stop_reason = self.debugger.step(&mut self.chip8);
+                self.handle_stop(stop_reason)
+            }
+        }
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let program_path = arguments["program"]
+            .as_str()
+            .ok_or("missing 'program' launch argument")?;
+        let program = std::fs::read(program_path)
+            .map_err(|err| format!("could not read '{}': {}", program_path, err))?;
+
+        self.chip8
+            .load_program(&program)
+            .map_err(|err| format!("could not load '{}': {}", program_path, err))?;
+
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+
+        if let Some(symbols_path) = arguments["symbols"].as_str() {
+            let symbols = std::fs::read_to_string(symbols_path)
+                .map_err(|err| format!("could not read '{}': {}", symbols_path, err))?;
+            self.symbols = SymbolTable::parse(&symbols)
+                .map_err(|err| format!("could not load '{}': {}", symbols_path, err))?;
+        }
+
+        // Without an explicit source the first file named in the symbols is debugged
+        let source = arguments["source"]
+            .as_str()
+            .map(str::to_string)
+            .or_else(|| {
+                self.symbols
+                    .locations()
+                    .next()
+                    .map(|(_, location)| location.file.clone())
+            });
+
+        if let Some(source) = source {
+            let mut source_map = SourceMap::new(source.clone());
+
+            for (address, location) in self.symbols.locations() {
+                if is_same_source(&source, &location.file) {
+                    source_map.insert(location.line, address);
+                }
+            }
+
+            if let Some(lines) = arguments["lines"].as_object() {
+                for (line, address) in lines {
+                    let line = line
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid line number '{}'", line))?;
+                    let address = parse_address(address)
+                        .ok_or_else(|| format!("invalid address for line {}", line))?;
+                    source_map.insert(line, address);
+                }
+            }
+
+            self.source_map = Some(source_map);
+        }
+
+        Ok(json!({}))
+    }
+
+    fn update_breakpoints(&mut self) {
+        self.debugger.clear_breakpoints();
+
+        for (address, breakpoint) in self
+            .line_breakpoints
+            .iter()
+            .chain(self.instruction_breakpoints.iter())
+        {
+            self.debugger.set_breakpoint(*address, breakpoint.clone());
+        }
+    }
+
+    fn parse_breakpoint(breakpoint: &Value) -> Result<Breakpoint, String> {
+        let condition = breakpoint["condition"]
+            .as_str()
+            .filter(|condition| !condition.trim().is_empty())
+            .map(Expression::parse)
+            .transpose()
+            .map_err(|err| format!("invalid condition: {}", err))?;
+
+        let log_message = breakpoint["logMessage"]
+            .as_str()
+            .map(LogMessage::parse)
+            .transpose()
+            .map_err(|err| format!("invalid log message: {}", err))?;
+
+        Ok(Breakpoint {
+            condition,
+            log_message,
+            hit_count: 0,
+        })
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let mut breakpoints = Vec::new();
+        self.line_breakpoints.clear();
+
+        let requested = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        for breakpoint in requested {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+            let resolved = self
+                .source_map
+                .as_ref()
+                .and_then(|source_map| source_map.line_to_address(line));
+
+            match (resolved, Self::parse_breakpoint(&breakpoint)) {
+                (Some((line, address)), Ok(parsed)) => {
+                    self.line_breakpoints.push((address, parsed));
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "line": line,
+                        "instructionReference": format!("{:#05x}", address),
+                    }));
+                }
+                (Some(_), Err(message)) => breakpoints.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": message,
+                })),
+                (None, _) => breakpoints.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "no code at this line",
+                })),
+            }
+        }
+
+        self.update_breakpoints();
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn set_instruction_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let mut breakpoints = Vec::new();
+        self.instruction_breakpoints.clear();
+
+        let requested = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        for breakpoint in requested {
+            let address = parse_address(&breakpoint["instructionReference"]).map(|address| {
+                (address as i64 + breakpoint["offset"].as_i64().unwrap_or(0)) as usize
+            });
+
+            match (address, Self::parse_breakpoint(&breakpoint)) {
+                (Some(address), Ok(parsed)) if address < MEMORY_SIZE => {
+                    self.instruction_breakpoints.push((address, parsed));
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "instructionReference": format!("{:#05x}", address),
+                    }));
+                }
+                _ => breakpoints.push(json!({ "verified": false })),
+            }
+        }
+
+        self.update_breakpoints();
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn source_location(&self, address: usize) -> Option<(Value, usize)> {
+        let source_map = self.source_map.as_ref()?;
+        let line = source_map.address_to_line(address)?;
+
+        Some((json!({ "path": source_map.path }), line))
+    }
+
+    fn stack_trace(&self) -> Value {
+        // Each return address on the stack points right behind the call that created the frame
+        let call_sites: Vec<usize> = self
+            .chip8
+            .stack
+            .iter()
+            .rev()
+            .map(|return_address| return_address.saturating_sub(INSTRUCTION_SIZE))
+            .collect();
+
+        let frame_addresses =
+            std::iter::once(self.chip8.program_counter).chain(call_sites.iter().copied());
+
+        let frames: Vec<Value> = frame_addresses
+            .enumerate()
+            .map(|(index, address)| {
+                let name = match call_sites.get(index) {
+                    Some(call_site) => match self.chip8.memory.read_instruction(*call_site) {
+                        Ok(Instruction::ExecuteSubroutine { address }) => {
+                            match self.symbols.label(address) {
+                                Some(label) => label.to_string(),
+                                None => format!("sub {:#05x}", address),
+                            }
+                        }
+                        _ => "sub ?".to_string(),
+                    },
+                    None => "main".to_string(),
+                };
+
+                let mut frame = json!({
+                    "id": index,
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("{:#05x}", address),
+                });
+                if let Some((source, line)) = self.source_location(address) {
+                    frame["source"] = source;
+                    frame["line"] = json!(line);
+                    frame["column"] = json!(1);
+                }
+                frame
+            })
+            .collect();
+
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    fn scopes(&self) -> Value {
+        json!({
+            "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                { "name": "Timers", "variablesReference": TIMERS_REFERENCE, "expensive": false },
+                { "name": "Stack", "variablesReference": STACK_REFERENCE, "expensive": false },
+                { "name": "Memory", "variablesReference": MEMORY_REFERENCE, "expensive": true },
+            ]
+        })
+    }
+
+    fn variable(name: &str, value: String) -> Value {
+        json!({ "name": name, "value": value, "variablesReference": 0 })
+    }
+
+    fn variables(&self, arguments: &Value) -> Value {
+        let reference = arguments["variablesReference"].as_u64().unwrap_or(0);
+        let chip8 = &self.chip8;
+
+        let variables: Vec<Value> = match reference {
+            REGISTERS_REFERENCE => {
+                let mut variables: Vec<Value> = (0..16u8)
+                    .map(|register| {
+                        let value = chip8.data_registers[DataRegister::try_from(register).unwrap()];
+                        Self::variable(&format!("v{:x}", register), format!("{:#04x}", value))
+                    })
+                    .collect();
+
+                let mut address_register =
+                    Self::variable("i", format!("{:#05x}", chip8.address_register));
+                address_register["memoryReference"] =
+                    json!(format!("{:#05x}", chip8.address_register));
+                variables.push(address_register);
+
+                let mut program_counter =
+                    Self::variable("pc", format!("{:#05x}", chip8.program_counter));
+                program_counter["memoryReference"] =
+                    json!(format!("{:#05x}", chip8.program_counter));
+                variables.push(program_counter);
+
+                variables.push(Self::variable("sp", chip8.stack.len().to_string()));
+                variables
+            }
+            TIMERS_REFERENCE => vec![
+                Self::variable("dt", chip8.delay_timer.to_string()),
+                Self::variable("st", chip8.sound_timer.to_string()),
+            ],
+            STACK_REFERENCE => chip8
+                .stack
+                .iter()
+                .enumerate()
+                .map(|(index, address)| {
+                    Self::variable(&format!("[{}]", index), format!("{:#05x}", address))
+                })
+                .collect(),
+            MEMORY_REFERENCE => chip8
+                .memory
+                .raw_data
+                .chunks(MEMORY_ROW_SIZE)
+                .enumerate()
+                .map(|(row, bytes)| {
+                    let hex: Vec<String> =
+                        bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    let mut variable =
+                        Self::variable(&format!("{:#05x}", row * MEMORY_ROW_SIZE), hex.join(" "));
+                    variable["memoryReference"] = json!(format!("{:#05x}", row * MEMORY_ROW_SIZE));
+                    variable
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        json!({ "variables": variables })
+    }
+
+    fn set_variable(&mut self, arguments: &Value) -> Result<Value, String> {
+        let name = arguments["name"].as_str().unwrap_or_default();
+        let value = arguments["value"]
+            .as_str()
+            .and_then(|value| parse_address(&json!(value)))
+            .ok_or("invalid value")?;
+
+        let register = name
+            .strip_prefix('v')
+            .and_then(|index| u8::from_str_radix(index, 16).ok())
+            .and_then(|index| DataRegister::try_from(index).ok());
+
+        let formatted = match (name, register) {
+            (_, Some(register)) => {
+                self.chip8.data_registers[register] = value as u8;
+                format!("{:#04x}", value as u8)
+            }
+            ("i", _) => {
+                self.chip8.address_register = value;
+                format!("{:#05x}", value)
+            }
+            ("pc", _) => {
+                self.chip8.program_counter = value;
+                format!("{:#05x}", value)
+            }
+            ("dt", _) => {
+                self.chip8.delay_timer = value as u8;
+                (value as u8).to_string()
+            }
+            ("st", _) => {
+                self.chip8.sound_timer = value as u8;
+                (value as u8).to_string()
+            }
+            _ => return Err(format!("'{}' can not be modified", name)),
+        };
+
+        Ok(json!({ "value": formatted }))
+    }
+
+    fn disassemble(&self, arguments: &Value) -> Result<Value, String> {
+        let base = (parse_address(&arguments["memoryReference"])
+            .ok_or("invalid memory reference")? as i64)
+            .saturating_add(arguments["offset"].as_i64().unwrap_or(0))
+            .saturating_add(
+                arguments["instructionOffset"]
+                    .as_i64()
+                    .unwrap_or(0)
+                    .saturating_mul(INSTRUCTION_SIZE as i64),
+            );
+        // More instructions than fit into the memory can not be shown anyway
+        let count = arguments["instructionCount"]
+            .as_u64()
+            .unwrap_or(0)
+            .min((MEMORY_SIZE / INSTRUCTION_SIZE) as u64) as i64;
+
+        let instructions: Vec<Value> = (0..count)
+            .map(|index| {
+                let address = base.saturating_add(index * INSTRUCTION_SIZE as i64);
+                let decoded = usize::try_from(address).ok().and_then(|address| {
+                    DisassembledInstruction::decode(&self.chip8.memory.raw_data, address)
+                });
+
+                match decoded {
+                    Some(decoded) => {
+                        let mut instruction = json!({
+                            "address": format!("{:#05x}", decoded.address),
+                            "instructionBytes": format!("{:04x}", decoded.opcode),
+                            "instruction": self.symbols.format_instruction(&decoded),
+                        });
+                        if let Some(label) = self.symbols.label(decoded.address) {
+                            instruction["symbol"] = json!(label);
+                        }
+                        if let Some((source, line)) = self.source_location(decoded.address) {
+                            instruction["location"] = source;
+                            instruction["line"] = json!(line);
+                        }
+                        instruction
+                    }
+                    None => json!({
+                        "address": format!("{:#05x}", address.max(0)),
+                        "instruction": "??",
+                        "presentationHint": "invalid",
+                    }),
+                }
+            })
+            .collect();
+
+        Ok(json!({ "instructions": instructions }))
+    }
+
+    fn evaluate(&self, arguments: &Value) -> Result<Value, String> {
+        let expression = Expression::parse(arguments["expression"].as_str().unwrap_or_default())
+            .map_err(|err| err.to_string())?;
+        let value = expression
+            .evaluate(&self.chip8)
+            .map_err(|err| err.to_string())?;
+
+        Ok(json!({
+            "result": format!("{} ({:#x})", value, value),
+            "variablesReference": 0,
+        }))
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value, String> {
+        let address = (parse_address(&arguments["memoryReference"])
+            .ok_or("invalid memory reference")? as i64)
+            .saturating_add(arguments["offset"].as_i64().unwrap_or(0));
+        let count = usize::try_from(arguments["count"].as_u64().unwrap_or(0)).unwrap_or(usize::MAX);
+
+        let start = address.clamp(0, MEMORY_SIZE as i64) as usize;
+        let end = start.saturating_add(count).min(MEMORY_SIZE);
+
+        let mut body = Map::new();
+        body.insert("address".to_string(), json!(format!("{:#05x}", start)));
+        body.insert(
+            "data".to_string(),
+            json!(encode_base64(&self.chip8.memory.raw_data[start..end])),
+        );
+        body.insert("unreadableBytes".to_string(), json!(count - (end - start)));
+
+        Ok(Value::Object(body))
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+use super::graphic::Display;
+use super::platform::Platform;
+use super::quirks::Quirks;
+use super::Chip8;
+
+#[derive(Error, Debug)]
+pub enum DatabaseError {
+    #[error("database file could not be read")]
+    Io(#[from] io::Error),
+    #[error("database file is not valid")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+struct QuirksOverride {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl QuirksOverride {
+    fn apply(&self, quirks: &mut Quirks) {
+        let fields = [
+            (self.shift, &mut quirks.shift),
+            (
+                self.memory_increment_by_x,
+                &mut quirks.memory_increment_by_x,
+            ),
+            (
+                self.memory_leave_i_unchanged,
+                &mut quirks.memory_leave_i_unchanged,
+            ),
+            (self.wrap, &mut quirks.wrap),
+            (self.jump, &mut quirks.jump),
+            (self.vblank, &mut quirks.vblank),
+            (self.logic, &mut quirks.logic),
+        ];
+
+        for (value, quirk) in fields {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    file: Option<String>,
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirksOverride>,
+    tickrate: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct ProgramEntry {
+    title: String,
+    description: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct PlatformEntry {
+    id: String,
+    #[serde(default)]
+    quirks: QuirksOverride,
+}
+
+// Everything needed to run a known ROM the way it was meant to
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomConfig {
+    pub title: String,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub file: Option<String>,
+    pub platform_id: Option<String>,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub tickrate: Option<usize>,
+}
+
+impl RomConfig {
+    pub fn apply<D: Display>(&self, chip8: &mut Chip8<D>) {
+        chip8.quirks = self.quirks;
+
+        if let Some(tickrate) = self.tickrate {
+            chip8.instructions_per_frame = tickrate;
+        }
+    }
+}
+
+// Quirks of the platform ids used by the database, for when no platforms file is loaded
+fn builtin_platform(id: &str) -> Option<(Platform, Quirks)> {
+    let chip8 = Quirks {
+        shift: false,
+        memory_increment_by_x: false,
+        memory_leave_i_unchanged: false,
+        wrap: false,
+        jump: false,
+        vblank: false,
+        logic: false,
+    };
+    let chip48 = Quirks {
+        shift: true,
+        memory_increment_by_x: true,
+        jump: true,
+        ..chip8
+    };
+
+    match id {
+        "originalChip8" | "hybridVIP" | "chip8x" => Some((Platform::Chip8, Quirks::cosmac_vip())),
+        "modernChip8" => Some((Platform::Chip8, chip8)),
+        "chip48" | "superchip1" => Some((Platform::SuperChip, chip48)),
+        "superchip" | "megachip8" => Some((Platform::SuperChip, Quirks::super_chip())),
+        "xochip" => Some((Platform::XoChip, Quirks::xo_chip())),
+        _ => None,
+    }
+}
+
+pub fn program_hash(program: &[u8]) -> String {
+    Sha1::digest(program)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct Database {
+    programs: Vec<ProgramEntry>,
+    hashes: HashMap<String, usize>,
+    platforms: HashMap<String, Quirks>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Takes the contents of programs.json and optionally platforms.json
+    pub fn from_json(programs: &str, platforms: Option<&str>) -> Result<Self, DatabaseError> {
+        let programs: Vec<ProgramEntry> = serde_json::from_str(programs)?;
+
+        let mut hashes = HashMap::new();
+        for (index, program) in programs.iter().enumerate() {
+            for hash in program.roms.keys() {
+                hashes.insert(hash.to_ascii_lowercase(), index);
+            }
+        }
+
+        let mut database = Database {
+            programs,
+            hashes,
+            platforms: HashMap::new(),
+        };
+
+        if let Some(platforms) = platforms {
+            let platforms: Vec<PlatformEntry> = serde_json::from_str(platforms)?;
+            for platform in platforms {
+                let mut quirks = builtin_platform(&platform.id)
+                    .map(|(_, quirks)| quirks)
+                    .unwrap_or_default();
+                platform.quirks.apply(&mut quirks);
+                database.platforms.insert(platform.id, quirks);
+            }
+        }
+
+        Ok(database)
+    }
+
+    // Loads a checkout of the database, the platforms file is optional
+    pub fn load<P: AsRef<Path>>(directory: P) -> Result<Self, DatabaseError> {
+        let directory = directory.as_ref();
+        let programs = fs::read_to_string(directory.join("programs.json"))?;
+        let platforms = match fs::read_to_string(directory.join("platforms.json")) {
+            Ok(platforms) => Some(platforms),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+
+        Self::from_json(&programs, platforms.as_deref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn lookup(&self, program: &[u8]) -> Option<RomConfig> {
+        self.lookup_hash(&program_hash(program))
+    }
+
+    pub fn lookup_hash(&self, hash: &str) -> Option<RomConfig> {
+        let hash = hash.to_ascii_lowercase();
+        let program = &self.programs[*self.hashes.get(&hash)?];
+        let rom = program
+            .roms
+            .iter()
+            .find(|(rom_hash, _)| rom_hash.to_ascii_lowercase() == hash)
+            .map(|(_, rom)| rom)?;
+
+        // The first listed platform is the one the ROM was written for
+        let platform_id = rom.platforms.first().cloned();
+        let builtin = platform_id.as_deref().and_then(builtin_platform);
+        let platform = builtin.map(|(platform, _)| platform).unwrap_or_default();
+        let mut quirks = platform_id
+            .as_ref()
+            .and_then(|id| self.platforms.get(id).copied())
+            .or(builtin.map(|(_, quirks)| quirks))
+            .unwrap_or_default();
+
+        if let Some(overrides) = platform_id
+            .as_ref()
+            .and_then(|id| rom.quirky_platforms.get(id))
+        {
+            overrides.apply(&mut quirks);
+        }
+
+        Some(RomConfig {
+            title: program.title.clone(),
+            description: program.description.clone(),
+            authors: program.authors.clone(),
+            file: rom.file.clone(),
+            platform_id,
+            platform,
+            quirks,
+            tickrate: rom.tickrate,
+        })
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+
+use super::constants::INSTRUCTION_SIZE;
+use super::cpu::CycleError;
+use super::expression::{Expression, LogMessage};
+use super::graphic::Display;
+use super::instruction::Instruction;
+use super::Chip8;
+
+#[derive(Debug)]
+pub enum StopReason {
+    Step,
+    Breakpoint(usize),
+    Blocked,
+    CycleLimit,
+    Watch {
+        index: usize,
+        old_value: Option<i64>,
+        new_value: Option<i64>,
+    },
+    Error(CycleError),
+}
+
+// A breakpoint with a log message is a logpoint, it records the message instead of stopping
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Breakpoint {
+    pub condition: Option<Expression>,
+    pub log_message: Option<LogMessage>,
+    pub hit_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    pub expression: Expression,
+    pub value: Option<i64>,
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    watches: Vec<Watch>,
+    log_messages: Vec<String>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.set_breakpoint(address, Breakpoint::default())
+    }
+
+    pub fn set_breakpoint(&mut self, address: usize, breakpoint: Breakpoint) -> bool {
+        self.breakpoints.insert(address, breakpoint).is_none()
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn breakpoint(&self, address: usize) -> Option<&Breakpoint> {
+        self.breakpoints.get(&address)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn has_breakpoint(&self, address: usize) -> bool {
+        self.breakpoints.contains_key(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    pub fn add_watch<D: Display>(&mut self, expression: Expression, chip8: &Chip8<D>) -> usize {
+        let value = expression.evaluate(chip8).ok();
+        self.watches.push(Watch { expression, value });
+
+        self.watches.len() - 1
+    }
+
+    pub fn remove_watch(&mut self, index: usize) -> Option<Watch> {
+        (index < self.watches.len()).then(|| self.watches.remove(index))
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    pub fn take_log_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.log_messages)
+    }
+
+    // Runs the next instruction through the frames of the machine, so that timers, input and
+    // the display go on as in a frontend. A frame that ends first, because its instructions
+    // are used up or the machine waits for the display, is followed by the next one.
+    fn execute_cycle<D: Display>(&mut self, chip8: &mut Chip8<D>) -> Result<bool, CycleError> {
+        for _ in 0..2 {
+            if chip8.step_instruction()? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    pub fn step<D: Display>(&mut self, chip8: &mut Chip8<D>) -> StopReason {
+        if chip8.is_blocked() {
+            return StopReason::Blocked;
+        }
+
+        match self.execute_cycle(chip8) {
+            Ok(true) => {}
+            Ok(false) => return StopReason::Blocked,
+            Err(err) => return StopReason::Error(err),
+        }
+
+        self.check_watches(chip8).unwrap_or(StopReason::Step)
+    }
+
+    pub fn resume<D: Display>(&mut self, chip8: &mut Chip8<D>, max_cycles: usize) -> StopReason {
+        self.run_until(chip8, max_cycles, |_| false)
+    }
+
+    pub fn step_over<D: Display>(&mut self, chip8: &mut Chip8<D>, max_cycles: usize) -> StopReason {
+        let Ok(Instruction::ExecuteSubroutine { .. }) =
+            chip8.memory.read_instruction(chip8.program_counter)
+        else {
+            return self.step(chip8);
+        };
+
+        let return_address = chip8.program_counter + INSTRUCTION_SIZE;
+        let stack_depth = chip8.stack.len();
+
+        self.run_until(chip8, max_cycles, |chip8| {
+            chip8.program_counter == return_address && chip8.stack.len() == stack_depth
+        })
+    }
+
+    pub fn step_out<D: Display>(&mut self, chip8: &mut Chip8<D>, max_cycles: usize) -> StopReason {
+        let stack_depth = chip8.stack.len();
+        if stack_depth == 0 {
+            return self.resume(chip8, max_cycles);
+        }
+
+        self.run_until(chip8, max_cycles, |chip8| chip8.stack.len() < stack_depth)
+    }
+
+    pub fn run_until<D: Display, F: Fn(&Chip8<D>) -> bool>(
+        &mut self,
+        chip8: &mut Chip8<D>,
+        max_cycles: usize,
+        reached: F,
+    ) -> StopReason {
+        // Stop conditions are checked after each instruction, so a breakpoint on the current
+        // instruction does not stop the machine from leaving it
+        for _ in 0..max_cycles {
+            if chip8.is_blocked() {
+                return StopReason::Blocked;
+            }
+
+            match self.execute_cycle(chip8) {
+                Ok(true) => {}
+                Ok(false) => return StopReason::Blocked,
+                Err(err) => return StopReason::Error(err),
+            }
+
+            if let Some(stop_reason) = self.check_watches(chip8) {
+                return stop_reason;
+            }
+
+            if reached(chip8) {
+                return StopReason::Step;
+            }
+
+            if self.check_breakpoint(chip8) {
+                return StopReason::Breakpoint(chip8.program_counter);
+            }
+        }
+
+        StopReason::CycleLimit
+    }
+
+    fn check_breakpoint<D: Display>(&mut self, chip8: &Chip8<D>) -> bool {
+        let Some(breakpoint) = self.breakpoints.get_mut(&chip8.program_counter) else {
+            return false;
+        };
+
+        if let Some(condition) = &breakpoint.condition {
+            match condition.is_true(chip8) {
+                Ok(true) => {}
+                Ok(false) => return false,
+                Err(err) => {
+                    // A broken condition stops the machine so that it can be fixed
+                    self.log_messages.push(format!(
+                        "breakpoint condition '{}' at {:#05x} failed: {}",
+                        condition, chip8.program_counter, err
+                    ));
+                    return true;
+                }
+            }
+        }
+
+        breakpoint.hit_count += 1;
+
+        match &breakpoint.log_message {
+            Some(log_message) => {
+                self.log_messages.push(log_message.format(chip8));
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn check_watches<D: Display>(&mut self, chip8: &Chip8<D>) -> Option<StopReason> {
+        for (index, watch) in self.watches.iter_mut().enumerate() {
+            let value = watch.expression.evaluate(chip8).ok();
+
+            if value != watch.value {
+                let old_value = std::mem::replace(&mut watch.value, value);
+                return Some(StopReason::Watch {
+                    index,
+                    old_value,
+                    new_value: value,
+                });
+            }
+        }
+
+        None
+    }
+}
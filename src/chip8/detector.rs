@@ -0,0 +1,269 @@
+use std::collections::BTreeSet;
+use std::marker::PhantomData;
+
+use super::cfg::{BasicBlock, ControlFlowGraph};
+use super::constants::DEFAULT_PROGRAM_ADDRESS;
+use super::disassembler::RecursiveDisassembly;
+use super::graphic::{Display, Pixel, Screen};
+use super::instruction::Instruction;
+use super::platform::Platform;
+use super::quirks::QuirksProfile;
+use super::Chip8;
+
+// Weights of the individual observations, opcodes of a newer platform are near proof
+const PLATFORM_OPCODE_WEIGHT: f64 = 8.0;
+const QUIRK_USAGE_WEIGHT: f64 = 1.0;
+const TRIAL_CRASH_WEIGHT: f64 = 4.0;
+const TRIAL_BLANK_SCREEN_WEIGHT: f64 = 1.0;
+// Trial runs that draw random numbers turn out the same every time
+const TRIAL_SEED: u64 = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub profile: QuirksProfile,
+    pub confidence: f64,
+    pub reasons: Vec<String>,
+}
+
+struct Evidence {
+    profile: QuirksProfile,
+    score: f64,
+    reasons: Vec<String>,
+}
+
+impl Evidence {
+    fn add(&mut self, weight: f64, reason: String) {
+        self.score += weight;
+        self.reasons.push(reason);
+    }
+}
+
+// The trial runs happen on a display of type D
+pub struct Detector<D: Display + Default = Screen> {
+    trial_frames: usize,
+    display: PhantomData<fn() -> D>,
+}
+
+impl<D: Display + Default> Default for Detector<D> {
+    fn default() -> Self {
+        Detector {
+            trial_frames: 0,
+            display: PhantomData,
+        }
+    }
+}
+
+// Whether I is read again before it is reloaded, which only makes sense when the
+// instruction at the index advanced it
+fn reads_relative_to_address_register(block: &BasicBlock, index: usize) -> bool {
+    for instruction in block.instructions[index + 1..]
+        .iter()
+        .filter_map(|decoded| decoded.instruction)
+    {
+        match instruction {
+            Instruction::StoreAddressInAddressRegister { .. }
+            | Instruction::SetAddressRegisterToSpriteAddressOfSpriteInVx { .. }
+            | Instruction::AddVxToAddressRegister { .. } => return false,
+            Instruction::DrawSpriteAtVxVy { .. }
+            | Instruction::StoreBCDOfVx { .. }
+            | Instruction::StoreRegistersInMemory { .. }
+            | Instruction::FillRegistersFromMemory { .. } => return true,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<D: Display + Default> Detector<D> {
+    // Runs the program for the given number of frames under every profile, 0 disables it
+    pub fn with_trial_runs(mut self, frames: usize) -> Self {
+        self.trial_frames = frames;
+        self
+    }
+
+    pub fn detect(&self, program: &[u8]) -> Vec<Candidate> {
+        let mut evidence: Vec<Evidence> = QuirksProfile::ALL
+            .iter()
+            .map(|profile| Evidence {
+                profile: *profile,
+                score: 1.0,
+                reasons: Vec::new(),
+            })
+            .collect();
+
+        let disassembly = RecursiveDisassembly::from_program(program);
+        self.check_opcodes(program, &disassembly, &mut evidence);
+        self.check_quirk_usage(&disassembly, &mut evidence);
+        if self.trial_frames > 0 {
+            self.run_trials(program, &mut evidence);
+        }
+
+        let total: f64 = evidence
+            .iter()
+            .map(|evidence| evidence.score.max(0.0))
+            .sum();
+        let mut candidates: Vec<Candidate> = evidence
+            .into_iter()
+            .map(|evidence| Candidate {
+                profile: evidence.profile,
+                confidence: match total > 0.0 {
+                    true => evidence.score.max(0.0) / total,
+                    false => 0.0,
+                },
+                reasons: evidence.reasons,
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        candidates
+    }
+
+    fn check_opcodes(
+        &self,
+        program: &[u8],
+        disassembly: &RecursiveDisassembly,
+        evidence: &mut [Evidence],
+    ) {
+        // Only words reached as code count, sprites and other data may look like any opcode.
+        // Opcodes of newer platforms are not CHIP-8 instructions and end the path they are on,
+        // so they are among the invalid instructions.
+        let reachable: BTreeSet<usize> = disassembly
+            .instructions()
+            .map(|instruction| instruction.address)
+            .chain(disassembly.invalid_instructions())
+            .collect();
+
+        let mut required = Platform::Chip8;
+        let mut first_address = None;
+
+        for address in reachable {
+            let offset = address - DEFAULT_PROGRAM_ADDRESS;
+            let Some(word) = program.get(offset..offset + 2) else {
+                continue;
+            };
+            let opcode = u16::from_be_bytes([word[0], word[1]]);
+            if let Some(platform) = Platform::introducing(opcode) {
+                if platform > required {
+                    required = platform;
+                    first_address = Some((address, opcode));
+                }
+            }
+        }
+
+        let Some((address, opcode)) = first_address else {
+            return;
+        };
+
+        for evidence in evidence.iter_mut() {
+            if evidence.profile.platform() >= required {
+                evidence.add(
+                    PLATFORM_OPCODE_WEIGHT,
+                    format!(
+                        "opcode {:04x} at {:#05x} requires {}",
+                        opcode, address, required
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_quirk_usage(&self, disassembly: &RecursiveDisassembly, evidence: &mut [Evidence]) {
+        let graph = ControlFlowGraph::from_disassembly(disassembly);
+        let mut shifts_vy = None;
+        let mut increments_i = None;
+
+        for block in graph.blocks() {
+            for (index, decoded) in block.instructions.iter().enumerate() {
+                match decoded.instruction {
+                    Some(
+                        Instruction::ShiftVyRightStoreInVx { vx, vy }
+                        | Instruction::ShiftVyLeftStoreInVx { vx, vy },
+                    ) if vx != vy => {
+                        shifts_vy.get_or_insert(decoded.address);
+                    }
+                    Some(
+                        Instruction::StoreRegistersInMemory { .. }
+                        | Instruction::FillRegistersFromMemory { .. },
+                    ) if reads_relative_to_address_register(block, index) => {
+                        increments_i.get_or_insert(decoded.address);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for evidence in evidence.iter_mut() {
+            let quirks = evidence.profile.quirks();
+
+            if let (Some(address), false) = (shifts_vy, quirks.shift) {
+                evidence.add(
+                    QUIRK_USAGE_WEIGHT,
+                    format!("shift at {:#05x} uses a separate source register", address),
+                );
+            }
+
+            if let (Some(address), false) = (increments_i, quirks.memory_leave_i_unchanged) {
+                evidence.add(
+                    QUIRK_USAGE_WEIGHT,
+                    format!(
+                        "memory access relative to i follows the load or store at {:#05x}",
+                        address
+                    ),
+                );
+            }
+        }
+    }
+
+    fn run_trials(&self, program: &[u8], evidence: &mut [Evidence]) {
+        for evidence in evidence.iter_mut() {
+            let mut chip8 = Chip8::with_display(D::default());
+            chip8.quirks = evidence.profile.quirks();
+            chip8.seed_rng(TRIAL_SEED);
+            if chip8.load_program(program).is_err() {
+                continue;
+            }
+
+            let mut crashed = None;
+            for frame in 0..self.trial_frames {
+                if let Err(err) = chip8.run_frame() {
+                    crashed = Some((frame, chip8.program_counter, err));
+                    break;
+                }
+            }
+
+            match crashed {
+                Some((frame, address, err)) => evidence.add(
+                    -TRIAL_CRASH_WEIGHT,
+                    format!(
+                        "trial run failed at {:#05x} in frame {}: {}",
+                        address, frame, err
+                    ),
+                ),
+                None => {
+                    let resolution = chip8.screen.resolution();
+                    let blank = (0..resolution.height()).all(|y| {
+                        (0..resolution.width()).all(|x| chip8.screen.pixel(x, y) == Pixel::Off)
+                    });
+
+                    match blank {
+                        true => evidence.add(
+                            -TRIAL_BLANK_SCREEN_WEIGHT,
+                            format!("screen is blank after {} frames", self.trial_frames),
+                        ),
+                        false => evidence.add(
+                            0.0,
+                            format!("ran {} frames without errors", self.trial_frames),
+                        ),
+                    }
+                }
+            }
+        }
+    }
+}
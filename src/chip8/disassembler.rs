@@ -0,0 +1,314 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::ops::Range;
+
+use super::constants::{DEFAULT_PROGRAM_ADDRESS, INSTRUCTION_SIZE, MEMORY_SIZE};
+use super::instruction::Instruction;
+use super::symbols::SymbolTable;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisassembledInstruction {
+    pub address: usize,
+    pub opcode: u16,
+    pub instruction: Option<Instruction>,
+}
+
+impl DisassembledInstruction {
+    pub fn decode(data: &[u8], address: usize) -> Option<Self> {
+        let bytes: &[u8; INSTRUCTION_SIZE] = data
+            .get(address..address + INSTRUCTION_SIZE)?
+            .try_into()
+            .ok()?;
+
+        Some(DisassembledInstruction {
+            address,
+            opcode: u16::from_be_bytes(*bytes),
+            instruction: Instruction::try_from(bytes).ok(),
+        })
+    }
+}
+
+impl Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.instruction {
+            Some(instruction) => write!(f, "{}", instruction),
+            None => write!(f, "{:#04x} {:#04x}", self.opcode >> 8, self.opcode & 0xFF),
+        }
+    }
+}
+
+pub fn disassemble(data: &[u8], range: Range<usize>) -> Vec<DisassembledInstruction> {
+    let end = range.end.min(data.len()).min(MEMORY_SIZE);
+
+    (range.start..end)
+        .step_by(INSTRUCTION_SIZE)
+        .filter_map(|address| DisassembledInstruction::decode(data, address))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteKind {
+    Unknown,
+    Code,
+    Data,
+    Sprite,
+}
+
+// Result of following the control flow of a program, every byte of the range is classified
+pub struct RecursiveDisassembly {
+    range: Range<usize>,
+    bytes: Vec<u8>,
+    kinds: Vec<ByteKind>,
+    instructions: BTreeMap<usize, DisassembledInstruction>,
+    labels: SymbolTable,
+    unknown_jump_targets: BTreeSet<usize>,
+    invalid_instructions: BTreeSet<usize>,
+}
+
+pub fn disassemble_recursive(data: &[u8], range: Range<usize>) -> RecursiveDisassembly {
+    RecursiveDisassembly::new(data, range)
+}
+
+impl RecursiveDisassembly {
+    pub fn new(data: &[u8], range: Range<usize>) -> Self {
+        let range = range.start..range.end.min(data.len()).min(MEMORY_SIZE);
+        let range = range.start.min(range.end)..range.end;
+
+        let mut disassembly = RecursiveDisassembly {
+            bytes: data[range.clone()].to_vec(),
+            kinds: vec![ByteKind::Unknown; range.len()],
+            range,
+            instructions: BTreeMap::new(),
+            labels: SymbolTable::new(),
+            unknown_jump_targets: BTreeSet::new(),
+            invalid_instructions: BTreeSet::new(),
+        };
+        disassembly.trace();
+
+        disassembly
+    }
+
+    pub fn from_program(program: &[u8]) -> Self {
+        let mut memory = vec![0; DEFAULT_PROGRAM_ADDRESS];
+        memory.extend_from_slice(program);
+
+        Self::new(&memory, DEFAULT_PROGRAM_ADDRESS..memory.len())
+    }
+
+    fn trace(&mut self) {
+        let mut data_references = Vec::new();
+        // Each pending path carries the value of I known at its start, if any
+        let mut pending = vec![(self.range.start, None)];
+
+        if !self.range.is_empty() {
+            self.labels.insert_label(self.range.start, "main");
+        }
+
+        while let Some((mut address, mut address_register)) = pending.pop() {
+            loop {
+                if !self.range.contains(&address) || self.instructions.contains_key(&address) {
+                    break;
+                }
+
+                let Some(decoded) = self.decode(address) else {
+                    break;
+                };
+                let Some(instruction) = decoded.instruction else {
+                    self.invalid_instructions.insert(address);
+                    break;
+                };
+
+                self.instructions.insert(address, decoded);
+                let next = address + INSTRUCTION_SIZE;
+
+                match instruction {
+                    Instruction::JumpToAddress { address: target } => {
+                        self.add_label(target, "label");
+                        pending.push((target, address_register));
+                        break;
+                    }
+                    Instruction::ExecuteSubroutine { address: target } => {
+                        self.add_label(target, "sub");
+                        // The subroutine may change I, so it is unknown after the call
+                        pending.push((target, address_register));
+                        address_register = None;
+                    }
+                    Instruction::ReturnFromSubroutine => break,
+                    Instruction::JumpToAddressPlusV0 { address: target } => {
+                        // The target depends on v0 at runtime, so it can not be followed
+                        self.add_label(target, "table");
+                        self.unknown_jump_targets.insert(target);
+                        break;
+                    }
+                    Instruction::SkipIfVxEqualsNum { .. }
+                    | Instruction::SkipIfVxNotEqualNum { .. }
+                    | Instruction::SkipIfVxEqualsVy { .. }
+                    | Instruction::SkipIfVxNotEqualVy { .. }
+                    | Instruction::SkipIfKeyInVxPressed { .. }
+                    | Instruction::SkipIfKeyInVxNotPressed { .. } => {
+                        pending.push((next + INSTRUCTION_SIZE, address_register));
+                    }
+                    Instruction::StoreAddressInAddressRegister { address: target } => {
+                        address_register = Some(target);
+                    }
+                    Instruction::DrawSpriteAtVxVy { byte_count, .. } => {
+                        if let Some(target) = address_register {
+                            data_references.push((target, byte_count as usize, ByteKind::Sprite));
+                        }
+                    }
+                    Instruction::StoreBCDOfVx { .. } => {
+                        if let Some(target) = address_register {
+                            data_references.push((target, 3, ByteKind::Data));
+                        }
+                    }
+                    Instruction::StoreRegistersInMemory { vx }
+                    | Instruction::FillRegistersFromMemory { vx } => {
+                        if let Some(target) = address_register {
+                            data_references.push((
+                                target,
+                                u8::from(vx) as usize + 1,
+                                ByteKind::Data,
+                            ));
+                        }
+                        // I is left unchanged, but other interpreters increment it
+                        address_register = None;
+                    }
+                    Instruction::AddVxToAddressRegister { .. }
+                    | Instruction::SetAddressRegisterToSpriteAddressOfSpriteInVx { .. } => {
+                        address_register = None;
+                    }
+                    _ => {}
+                }
+
+                address = next;
+            }
+        }
+
+        for address in self.instructions.keys() {
+            let offset = address - self.range.start;
+            self.kinds[offset..offset + INSTRUCTION_SIZE].fill(ByteKind::Code);
+        }
+
+        // Code wins over data when both claim the same bytes
+        for (address, length, kind) in data_references {
+            let prefix = match kind {
+                ByteKind::Sprite => "sprite",
+                _ => "data",
+            };
+            self.add_label(address, prefix);
+
+            for address in address..address + length {
+                if let Some(byte_kind) = self.kind_mut(address) {
+                    if *byte_kind != ByteKind::Code {
+                        *byte_kind = kind;
+                    }
+                }
+            }
+        }
+    }
+
+    fn decode(&self, address: usize) -> Option<DisassembledInstruction> {
+        let decoded = DisassembledInstruction::decode(&self.bytes, address - self.range.start)?;
+
+        Some(DisassembledInstruction { address, ..decoded })
+    }
+
+    fn kind_mut(&mut self, address: usize) -> Option<&mut ByteKind> {
+        let offset = address.checked_sub(self.range.start)?;
+        self.kinds.get_mut(offset)
+    }
+
+    fn add_label(&mut self, address: usize, prefix: &str) {
+        if self.range.contains(&address) && self.labels.label(address).is_none() {
+            self.labels
+                .insert_label(address, &format!("{}_{:03x}", prefix, address));
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn kind(&self, address: usize) -> Option<ByteKind> {
+        let offset = address.checked_sub(self.range.start)?;
+        self.kinds.get(offset).copied()
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = &DisassembledInstruction> {
+        self.instructions.values()
+    }
+
+    pub fn instruction(&self, address: usize) -> Option<&DisassembledInstruction> {
+        self.instructions.get(&address)
+    }
+
+    pub fn labels(&self) -> &SymbolTable {
+        &self.labels
+    }
+
+    pub fn unknown_jump_targets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.unknown_jump_targets.iter().copied()
+    }
+
+    // Addresses reached by the control flow that do not hold a valid instruction
+    pub fn invalid_instructions(&self) -> impl Iterator<Item = usize> + '_ {
+        self.invalid_instructions.iter().copied()
+    }
+
+    // Writes an Octo listing that assembles back into the same bytes
+    pub fn write_listing<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut address = self.range.start;
+
+        while address < self.range.end {
+            if let Some(label) = self.labels.label(address) {
+                writeln!(writer)?;
+                match self.unknown_jump_targets.contains(&address) {
+                    true => writeln!(writer, ": {}  # jump0 target, not followed", label)?,
+                    false => writeln!(writer, ": {}", label)?,
+                }
+            }
+
+            // Instructions overlapped by a label are written as bytes to keep the label
+            let instruction = self
+                .instructions
+                .get(&address)
+                .filter(|_| self.labels.label(address + 1).is_none());
+
+            match instruction {
+                Some(instruction) => {
+                    match instruction.instruction {
+                        Some(Instruction::ExecuteMachineLanguageSubroutine { .. }) => writeln!(
+                            writer,
+                            "\t{:#04x} {:#04x}  # {}",
+                            instruction.opcode >> 8,
+                            instruction.opcode & 0xFF,
+                            instruction
+                        )?,
+                        _ => writeln!(writer, "\t{}", self.labels.format_instruction(instruction))?,
+                    }
+                    address += INSTRUCTION_SIZE;
+                }
+                None => {
+                    let byte = self.bytes[address - self.range.start];
+                    match self.kind(address) {
+                        Some(ByteKind::Sprite) => {
+                            let pattern: String = (0..8)
+                                .rev()
+                                .map(|bit| match byte >> bit & 1 {
+                                    1 => '#',
+                                    _ => '.',
+                                })
+                                .collect();
+                            writeln!(writer, "\t{:#04x}  # {}", byte, pattern)?;
+                        }
+                        _ => writeln!(writer, "\t{:#04x}", byte)?,
+                    }
+                    address += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
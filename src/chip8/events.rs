@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+
+use super::data_register::DataRegister;
+use super::graphic::Display;
+use super::keyboard::Key;
+use super::Chip8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    ScreenCleared,
+    SpriteDrawn {
+        x: usize,
+        y: usize,
+        rows: Vec<u8>,
+        collision: bool,
+    },
+    SoundStarted,
+    SoundStopped,
+    WaitingForKey(DataRegister),
+    KeyConsumed {
+        key: Key,
+        register: DataRegister,
+    },
+    SubroutineCalled {
+        from: usize,
+        to: usize,
+    },
+    SubroutineReturned {
+        to: usize,
+    },
+    // The program jumped to itself, which is how CHIP-8 programs end
+    Halted {
+        address: usize,
+    },
+}
+
+enum Delivery {
+    Queue(VecDeque<Event>),
+    Callback(Box<dyn FnMut(&Event) + Send>),
+}
+
+pub(crate) struct EventSink {
+    delivery: Delivery,
+    halted: bool,
+}
+
+impl EventSink {
+    pub(crate) fn push(&mut self, event: Event) {
+        match &mut self.delivery {
+            Delivery::Queue(queue) => queue.push_back(event),
+            Delivery::Callback(callback) => callback(&event),
+        }
+    }
+
+    // Reports a halt once, no matter how often the jump to itself is executed
+    pub(crate) fn set_halted(&mut self, halted: bool, address: usize) {
+        if halted && !self.halted {
+            self.push(Event::Halted { address });
+        }
+        self.halted = halted;
+    }
+}
+
+impl<D: Display> Chip8<D> {
+    // Collects events until they are drained
+    pub fn enable_events(&mut self) {
+        if !matches!(
+            self.events,
+            Some(EventSink {
+                delivery: Delivery::Queue(_),
+                ..
+            })
+        ) {
+            self.events = Some(EventSink {
+                delivery: Delivery::Queue(VecDeque::new()),
+                halted: false,
+            });
+        }
+    }
+
+    // Delivers every event to the callback as soon as it happens
+    pub fn set_event_callback<F: FnMut(&Event) + Send + 'static>(&mut self, callback: F) {
+        self.events = Some(EventSink {
+            delivery: Delivery::Callback(Box::new(callback)),
+            halted: false,
+        });
+    }
+
+    pub fn disable_events(&mut self) {
+        self.events = None;
+    }
+
+    // Events collected since the last call, empty when a callback is registered
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        let queue = match &mut self.events {
+            Some(EventSink {
+                delivery: Delivery::Queue(queue),
+                ..
+            }) => Some(queue.drain(..)),
+            _ => None,
+        };
+
+        queue.into_iter().flatten()
+    }
+}
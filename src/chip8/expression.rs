@@ -0,0 +1,521 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::data_register::DataRegister;
+use super::graphic;
+use super::Chip8;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ExpressionError {
+    #[error("unexpected character '{0}' at position {1}")]
+    UnexpectedCharacter(char, usize),
+    #[error("invalid number '{0}'")]
+    InvalidNumber(String),
+    #[error("unknown identifier '{0}'")]
+    UnknownIdentifier(String),
+    #[error("unexpected token '{0}'")]
+    UnexpectedToken(String),
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("unterminated placeholder in log message")]
+    UnterminatedPlaceholder,
+    #[error("expression is nested deeper than {0} levels")]
+    TooDeeplyNested(usize),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum EvaluationError {
+    #[error("memory address {0:#x} is outside of the memory")]
+    MemoryOutOfRange(i64),
+    #[error("division by zero")]
+    DivisionByZero,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Operator(&'static str),
+    OpenParenthesis,
+    CloseParenthesis,
+    OpenBracket,
+    CloseBracket,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnaryOperator {
+    Negate,
+    Not,
+    BitwiseNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOperator {
+    Or,
+    And,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(i64),
+    DataRegister(DataRegister),
+    AddressRegister,
+    ProgramCounter,
+    DelayTimer,
+    SoundTimer,
+    StackPointer,
+    Opcode,
+    Memory(Box<Node>),
+    Unary(UnaryOperator, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValueFormat {
+    Decimal,
+    Hexadecimal,
+    Binary,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Value(Expression, ValueFormat),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogMessage {
+    segments: Vec<Segment>,
+}
+
+// Parsing and evaluating recurse on the nesting, so it is limited to keep the stack in bounds
+const MAX_NESTING_DEPTH: usize = 64;
+
+// Operators are ordered so that longer operators are matched before their prefixes
+const OPERATORS: [&str; 23] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<", ">", "+", "-", "*", "/",
+    "%", "!", "~", "(", ")", "[",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = source.chars().collect();
+    let mut position = 0;
+
+    while position < chars.len() {
+        let character = chars[position];
+
+        if character.is_whitespace() {
+            position += 1;
+            continue;
+        }
+
+        if character.is_ascii_alphanumeric() || character == '_' {
+            let start = position;
+            while position < chars.len()
+                && (chars[position].is_ascii_alphanumeric() || chars[position] == '_')
+            {
+                position += 1;
+            }
+            let word: String = chars[start..position].iter().collect();
+
+            if character.is_ascii_digit() {
+                tokens.push(Token::Number(parse_number(&word)?));
+            } else {
+                tokens.push(Token::Identifier(word.to_ascii_lowercase()));
+            }
+            continue;
+        }
+
+        if character == ']' {
+            tokens.push(Token::CloseBracket);
+            position += 1;
+            continue;
+        }
+
+        let rest: String = chars[position..].iter().take(2).collect();
+        let operator = OPERATORS
+            .iter()
+            .find(|operator| rest.starts_with(**operator))
+            .ok_or(ExpressionError::UnexpectedCharacter(character, position))?;
+
+        tokens.push(match *operator {
+            "(" => Token::OpenParenthesis,
+            ")" => Token::CloseParenthesis,
+            "[" => Token::OpenBracket,
+            operator => Token::Operator(operator),
+        });
+        position += operator.len();
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Result<i64, ExpressionError> {
+    let lowercase = word.to_ascii_lowercase();
+
+    let parsed = if let Some(hex) = lowercase.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = lowercase.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        lowercase.parse()
+    };
+
+    parsed.map_err(|_| ExpressionError::InvalidNumber(word.to_string()))
+}
+
+fn binary_operator(operator: &str) -> Option<(BinaryOperator, u8)> {
+    // Higher numbers bind stronger, following the precedence rules of C
+    let operator = match operator {
+        "||" => (BinaryOperator::Or, 1),
+        "&&" => (BinaryOperator::And, 2),
+        "|" => (BinaryOperator::BitwiseOr, 3),
+        "^" => (BinaryOperator::BitwiseXor, 4),
+        "&" => (BinaryOperator::BitwiseAnd, 5),
+        "==" => (BinaryOperator::Equal, 6),
+        "!=" => (BinaryOperator::NotEqual, 6),
+        "<" => (BinaryOperator::Less, 7),
+        "<=" => (BinaryOperator::LessOrEqual, 7),
+        ">" => (BinaryOperator::Greater, 7),
+        ">=" => (BinaryOperator::GreaterOrEqual, 7),
+        "<<" => (BinaryOperator::ShiftLeft, 8),
+        ">>" => (BinaryOperator::ShiftRight, 8),
+        "+" => (BinaryOperator::Add, 9),
+        "-" => (BinaryOperator::Subtract, 9),
+        "*" => (BinaryOperator::Multiply, 10),
+        "/" => (BinaryOperator::Divide, 10),
+        "%" => (BinaryOperator::Remainder, 10),
+        _ => return None,
+    };
+
+    Some(operator)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn enter(&mut self) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        match self.depth > MAX_NESTING_DEPTH {
+            true => Err(ExpressionError::TooDeeplyNested(MAX_NESTING_DEPTH)),
+            false => Ok(()),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, ExpressionError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or(ExpressionError::UnexpectedEnd)?;
+        self.position += 1;
+
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ExpressionError> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(ExpressionError::UnexpectedToken(format!("{:?}", token))),
+        }
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Node, ExpressionError> {
+        let depth = self.depth;
+        let mut left = self.parse_unary()?;
+
+        while let Some(Token::Operator(operator)) = self.peek() {
+            let Some((operator, precedence)) = binary_operator(operator) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+
+            // Every operator in a chain nests the operators before it one level deeper
+            self.enter()?;
+            self.position += 1;
+            let right = self.parse_binary(precedence + 1)?;
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, ExpressionError> {
+        self.enter()?;
+        let node = self.parse_operand();
+        self.depth -= 1;
+        node
+    }
+
+    fn parse_operand(&mut self) -> Result<Node, ExpressionError> {
+        match self.next()? {
+            Token::Number(number) => Ok(Node::Number(number)),
+            Token::Identifier(identifier) => Self::parse_identifier(&identifier),
+            Token::Operator("-") => Ok(Node::Unary(
+                UnaryOperator::Negate,
+                Box::new(self.parse_unary()?),
+            )),
+            Token::Operator("!") => Ok(Node::Unary(
+                UnaryOperator::Not,
+                Box::new(self.parse_unary()?),
+            )),
+            Token::Operator("~") => Ok(Node::Unary(
+                UnaryOperator::BitwiseNot,
+                Box::new(self.parse_unary()?),
+            )),
+            Token::OpenParenthesis => {
+                let node = self.parse_binary(0)?;
+                self.expect(Token::CloseParenthesis)?;
+                Ok(node)
+            }
+            Token::OpenBracket => {
+                let node = self.parse_binary(0)?;
+                self.expect(Token::CloseBracket)?;
+                Ok(Node::Memory(Box::new(node)))
+            }
+            token => Err(ExpressionError::UnexpectedToken(format!("{:?}", token))),
+        }
+    }
+
+    fn parse_identifier(identifier: &str) -> Result<Node, ExpressionError> {
+        match identifier {
+            "i" => Ok(Node::AddressRegister),
+            "pc" => Ok(Node::ProgramCounter),
+            "dt" => Ok(Node::DelayTimer),
+            "st" => Ok(Node::SoundTimer),
+            "sp" => Ok(Node::StackPointer),
+            "opcode" => Ok(Node::Opcode),
+            _ => identifier
+                .strip_prefix('v')
+                .filter(|index| index.len() == 1)
+                .and_then(|index| u8::from_str_radix(index, 16).ok())
+                .and_then(|index| DataRegister::try_from(index).ok())
+                .map(Node::DataRegister)
+                .ok_or_else(|| ExpressionError::UnknownIdentifier(identifier.to_string())),
+        }
+    }
+}
+
+impl Node {
+    fn evaluate<D: graphic::Display>(&self, chip8: &Chip8<D>) -> Result<i64, EvaluationError> {
+        let read_memory = |address: i64| -> Result<u8, EvaluationError> {
+            usize::try_from(address)
+                .ok()
+                .and_then(|address| chip8.memory.raw_data.get(address).copied())
+                .ok_or(EvaluationError::MemoryOutOfRange(address))
+        };
+
+        match self {
+            Node::Number(number) => Ok(*number),
+            Node::DataRegister(register) => Ok(chip8.data_registers[*register] as i64),
+            Node::AddressRegister => Ok(chip8.address_register as i64),
+            Node::ProgramCounter => Ok(chip8.program_counter as i64),
+            Node::DelayTimer => Ok(chip8.delay_timer as i64),
+            Node::SoundTimer => Ok(chip8.sound_timer as i64),
+            Node::StackPointer => Ok(chip8.stack.len() as i64),
+            Node::Opcode => {
+                let address = chip8.program_counter as i64;
+                let high = read_memory(address)? as i64;
+                let low = read_memory(address + 1)? as i64;
+                Ok(high << 8 | low)
+            }
+            Node::Memory(address) => Ok(read_memory(address.evaluate(chip8)?)? as i64),
+            Node::Unary(operator, operand) => {
+                let value = operand.evaluate(chip8)?;
+                Ok(match operator {
+                    UnaryOperator::Negate => value.wrapping_neg(),
+                    UnaryOperator::Not => (value == 0) as i64,
+                    UnaryOperator::BitwiseNot => !value,
+                })
+            }
+            Node::Binary(operator, left, right) => {
+                let left = left.evaluate(chip8)?;
+
+                // Logical operators short-circuit like in C
+                match operator {
+                    BinaryOperator::And if left == 0 => return Ok(0),
+                    BinaryOperator::Or if left != 0 => return Ok(1),
+                    _ => {}
+                }
+
+                let right = right.evaluate(chip8)?;
+                Ok(match operator {
+                    BinaryOperator::Or | BinaryOperator::And => (right != 0) as i64,
+                    BinaryOperator::BitwiseOr => left | right,
+                    BinaryOperator::BitwiseXor => left ^ right,
+                    BinaryOperator::BitwiseAnd => left & right,
+                    BinaryOperator::Equal => (left == right) as i64,
+                    BinaryOperator::NotEqual => (left != right) as i64,
+                    BinaryOperator::Less => (left < right) as i64,
+                    BinaryOperator::LessOrEqual => (left <= right) as i64,
+                    BinaryOperator::Greater => (left > right) as i64,
+                    BinaryOperator::GreaterOrEqual => (left >= right) as i64,
+                    BinaryOperator::ShiftLeft => left.wrapping_shl(right as u32),
+                    BinaryOperator::ShiftRight => left.wrapping_shr(right as u32),
+                    BinaryOperator::Add => left.wrapping_add(right),
+                    BinaryOperator::Subtract => left.wrapping_sub(right),
+                    BinaryOperator::Multiply => left.wrapping_mul(right),
+                    BinaryOperator::Divide => left
+                        .checked_div(right)
+                        .ok_or(EvaluationError::DivisionByZero)?,
+                    BinaryOperator::Remainder => left
+                        .checked_rem(right)
+                        .ok_or(EvaluationError::DivisionByZero)?,
+                })
+            }
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        };
+
+        let root = parser.parse_binary(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(ExpressionError::UnexpectedToken(format!("{:?}", token)));
+        }
+
+        Ok(Expression {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    pub fn evaluate<D: graphic::Display>(&self, chip8: &Chip8<D>) -> Result<i64, EvaluationError> {
+        self.root.evaluate(chip8)
+    }
+
+    pub fn is_true<D: graphic::Display>(&self, chip8: &Chip8<D>) -> Result<bool, EvaluationError> {
+        Ok(self.evaluate(chip8)? != 0)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Expression::parse(source)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl LogMessage {
+    // Placeholders are written as {expression} or {expression:x} / {expression:b},
+    // literal braces are escaped by doubling them
+    pub fn parse(template: &str) -> Result<Self, ExpressionError> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(character) = chars.next() {
+            match character {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(character) => placeholder.push(character),
+                            None => return Err(ExpressionError::UnterminatedPlaceholder),
+                        }
+                    }
+
+                    let (source, format) = match placeholder.rsplit_once(':') {
+                        Some((source, "x")) => (source, ValueFormat::Hexadecimal),
+                        Some((source, "b")) => (source, ValueFormat::Binary),
+                        Some((source, "d")) => (source, ValueFormat::Decimal),
+                        _ => (placeholder.as_str(), ValueFormat::Decimal),
+                    };
+
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Value(Expression::parse(source)?, format));
+                }
+                character => text.push(character),
+            }
+        }
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Ok(LogMessage { segments })
+    }
+
+    pub fn format<D: graphic::Display>(&self, chip8: &Chip8<D>) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Value(expression, format) => match (expression.evaluate(chip8), format) {
+                    (Ok(value), ValueFormat::Decimal) => value.to_string(),
+                    (Ok(value), ValueFormat::Hexadecimal) => format!("{:#x}", value),
+                    (Ok(value), ValueFormat::Binary) => format!("{:#b}", value),
+                    (Err(err), _) => format!("<{}>", err),
+                },
+            })
+            .collect()
+    }
+}
+
+impl FromStr for LogMessage {
+    type Err = ExpressionError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        LogMessage::parse(template)
+    }
+}
@@ -0,0 +1,177 @@
+use super::constants::INSTRUCTION_SIZE;
+use super::cpu::execute::InstructionExecutionError;
+use super::cpu::CycleError;
+use super::graphic::{Display, Screen};
+use super::hypercall::HypercallError;
+use super::instruction::Instruction;
+use super::Chip8;
+
+// Extensions fail the same way hypercalls do, with a message for the user
+pub type ExtensionError = HypercallError;
+
+// Where execution goes on after an extension opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramCounterUpdate {
+    // The program counter moves past the opcode, on top of whatever the extension added
+    Advance,
+    // The extension set the program counter itself, to the opcode again to wait or halt there
+    Handled,
+}
+
+// Opcodes added by a CHIP-8 variant. Extensions are asked before the built-in instructions,
+// so they can redefine those as well.
+pub trait OpcodeExtension<D: Display = Screen>: Send {
+    type Instruction;
+
+    // Only opcodes with opcode & mask == pattern are decoded
+    fn mask(&self) -> u16;
+    fn pattern(&self) -> u16;
+
+    // None leaves the opcode to the next extension and the built-in instructions
+    fn decode(&self, opcode: u16) -> Option<Self::Instruction>;
+
+    fn execute(
+        &mut self,
+        chip8: &mut Chip8<D>,
+        instruction: Self::Instruction,
+    ) -> Result<ProgramCounterUpdate, ExtensionError>;
+}
+
+pub(crate) trait ErasedExtension<D: Display>: Send {
+    fn try_execute(
+        &mut self,
+        chip8: &mut Chip8<D>,
+        opcode: u16,
+    ) -> Option<Result<ProgramCounterUpdate, ExtensionError>>;
+}
+
+impl<D: Display, E: OpcodeExtension<D>> ErasedExtension<D> for E {
+    fn try_execute(
+        &mut self,
+        chip8: &mut Chip8<D>,
+        opcode: u16,
+    ) -> Option<Result<ProgramCounterUpdate, ExtensionError>> {
+        if opcode & self.mask() != self.pattern() {
+            return None;
+        }
+
+        let instruction = self.decode(opcode)?;
+        Some(self.execute(chip8, instruction))
+    }
+}
+
+pub type UnknownOpcodeHandler<D = Screen> =
+    Box<dyn FnMut(&mut Chip8<D>, u16) -> Result<ProgramCounterUpdate, ExtensionError> + Send>;
+
+// What happens to opcodes that neither an extension nor the built-in instructions know
+#[derive(Default)]
+pub enum UnknownOpcodePolicy<D: Display = Screen> {
+    // Stops the machine with InvalidInstruction
+    #[default]
+    Error,
+    // Skips them like a NOP
+    Ignore,
+    // Hands them to the handler, which tells how the program counter goes on like an extension
+    Callback(UnknownOpcodeHandler<D>),
+}
+
+impl<D: Display> Chip8<D> {
+    // Extensions are asked in the order they were registered
+    pub fn register_extension<E: OpcodeExtension<D> + 'static>(&mut self, extension: E) {
+        self.extensions.push(Box::new(extension));
+    }
+
+    pub fn clear_extensions(&mut self) {
+        self.extensions.clear();
+    }
+
+    pub fn set_unknown_opcode_policy(&mut self, policy: UnknownOpcodePolicy<D>) {
+        self.unknown_opcodes = policy;
+    }
+
+    pub fn set_unknown_opcode_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(&mut Chip8<D>, u16) -> Result<ProgramCounterUpdate, ExtensionError>
+            + Send
+            + 'static,
+    {
+        self.unknown_opcodes = UnknownOpcodePolicy::Callback(Box::new(handler));
+    }
+
+    // Whether cycle has to look at opcodes before the built-in instructions do
+    pub(crate) fn has_custom_opcodes(&self) -> bool {
+        !self.extensions.is_empty() || !matches!(self.unknown_opcodes, UnknownOpcodePolicy::Error)
+    }
+
+    fn execute_extensions(
+        &mut self,
+        opcode: u16,
+    ) -> Option<Result<ProgramCounterUpdate, ExtensionError>> {
+        let mut extensions = std::mem::take(&mut self.extensions);
+        let result = extensions
+            .iter_mut()
+            .find_map(|extension| extension.try_execute(self, opcode));
+
+        let registered = std::mem::replace(&mut self.extensions, extensions);
+        self.extensions.extend(registered);
+
+        result
+    }
+
+    fn execute_unknown_opcode(
+        &mut self,
+        opcode: u16,
+    ) -> Result<ProgramCounterUpdate, ExtensionError> {
+        match std::mem::take(&mut self.unknown_opcodes) {
+            UnknownOpcodePolicy::Callback(mut handler) => {
+                let result = handler(self, opcode);
+                if matches!(self.unknown_opcodes, UnknownOpcodePolicy::Error) {
+                    self.unknown_opcodes = UnknownOpcodePolicy::Callback(handler);
+                }
+                result
+            }
+            policy => {
+                self.unknown_opcodes = policy;
+                Ok(ProgramCounterUpdate::Advance)
+            }
+        }
+    }
+
+    // Executes the instruction at the address if an extension or the unknown opcode policy
+    // takes care of it, returns false when it is up to the built-in instructions
+    pub(crate) fn execute_custom_opcode(&mut self, address: usize) -> Result<bool, CycleError> {
+        let opcode = self.memory.read_opcode(address)?;
+
+        let result = match self.execute_extensions(opcode) {
+            Some(result) => result,
+            None if matches!(self.unknown_opcodes, UnknownOpcodePolicy::Error) => return Ok(false),
+            None if Instruction::try_from(&opcode.to_be_bytes()).is_ok() => return Ok(false),
+            None => self.execute_unknown_opcode(opcode),
+        };
+        let update =
+            result.map_err(|error| InstructionExecutionError::ExtensionFailed(opcode, error))?;
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_execute(address, INSTRUCTION_SIZE);
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record_custom_execution(address);
+        }
+
+        // Staying at the opcode is what a jump to itself is for the built-in instructions
+        let halted = match update {
+            ProgramCounterUpdate::Advance => {
+                self.program_counter += INSTRUCTION_SIZE;
+                false
+            }
+            ProgramCounterUpdate::Handled => self.program_counter == address,
+        };
+
+        if let Some(events) = &mut self.events {
+            events.set_halted(halted, address);
+        }
+
+        Ok(true)
+    }
+}
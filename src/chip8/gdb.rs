@@ -0,0 +1,635 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Stdout, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use thiserror::Error;
+
+use super::constants::MEMORY_SIZE;
+use super::data_register::DataRegister;
+use super::debugger::{Debugger, StopReason};
+use super::graphic::{Display, Screen};
+use super::Chip8;
+
+pub const TARGET_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.rust8.chip8">
+    <reg name="v0" bitsize="8" regnum="0" type="uint8" group="general"/>
+    <reg name="v1" bitsize="8" type="uint8" group="general"/>
+    <reg name="v2" bitsize="8" type="uint8" group="general"/>
+    <reg name="v3" bitsize="8" type="uint8" group="general"/>
+    <reg name="v4" bitsize="8" type="uint8" group="general"/>
+    <reg name="v5" bitsize="8" type="uint8" group="general"/>
+    <reg name="v6" bitsize="8" type="uint8" group="general"/>
+    <reg name="v7" bitsize="8" type="uint8" group="general"/>
+    <reg name="v8" bitsize="8" type="uint8" group="general"/>
+    <reg name="v9" bitsize="8" type="uint8" group="general"/>
+    <reg name="va" bitsize="8" type="uint8" group="general"/>
+    <reg name="vb" bitsize="8" type="uint8" group="general"/>
+    <reg name="vc" bitsize="8" type="uint8" group="general"/>
+    <reg name="vd" bitsize="8" type="uint8" group="general"/>
+    <reg name="ve" bitsize="8" type="uint8" group="general"/>
+    <reg name="vf" bitsize="8" type="uint8" group="general"/>
+    <reg name="i" bitsize="16" type="data_ptr" group="general"/>
+    <reg name="pc" bitsize="16" type="code_ptr" group="general"/>
+    <reg name="dt" bitsize="8" type="uint8" group="timers"/>
+    <reg name="st" bitsize="8" type="uint8" group="timers"/>
+    <reg name="sp" bitsize="8" type="uint8" group="general"/>
+  </feature>
+</target>
+"#;
+
+const REGISTER_COUNT: usize = 21;
+const REGISTER_I: usize = 16;
+const REGISTER_PC: usize = 17;
+const REGISTER_DT: usize = 18;
+const REGISTER_ST: usize = 19;
+const REGISTER_SP: usize = 20;
+
+const SIGNAL_INTERRUPT: u8 = 2;
+const SIGNAL_ILLEGAL_INSTRUCTION: u8 = 4;
+const SIGNAL_TRAP: u8 = 5;
+
+const CYCLES_PER_INTERRUPT_POLL: usize = 1024;
+const INTERRUPT_BYTE: u8 = 0x03;
+
+#[derive(Error, Debug)]
+pub enum GdbError {
+    #[error("connection error")]
+    Io(#[from] io::Error),
+    #[error("connection closed by the debugger")]
+    ConnectionClosed,
+}
+
+pub trait Connection: Read + Write {
+    // Called while the machine is running to detect a break request from the debugger
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+impl Connection for TcpStream {
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        let mut buffer = [0u8; 1];
+
+        self.set_nonblocking(true)?;
+        let result = self.peek(&mut buffer);
+        self.set_nonblocking(false)?;
+
+        match result {
+            Ok(1) if buffer[0] == INTERRUPT_BYTE => {
+                self.read_exact(&mut buffer)?;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+// Stdin is read on a thread of its own, so a break request can be seen while the machine runs
+pub struct StdioConnection {
+    input: Receiver<Vec<u8>>,
+    pending: VecDeque<u8>,
+    closed: bool,
+    stdout: Stdout,
+}
+
+impl Default for StdioConnection {
+    fn default() -> Self {
+        let (sender, input) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut stdin = io::stdin();
+            let mut buffer = [0u8; 4096];
+            loop {
+                // An empty chunk marks the end of the input
+                let chunk = match stdin.read(&mut buffer) {
+                    Ok(0) | Err(_) => Vec::new(),
+                    Ok(count) => buffer[..count].to_vec(),
+                };
+                let end = chunk.is_empty();
+                if sender.send(chunk).is_err() || end {
+                    break;
+                }
+            }
+        });
+
+        StdioConnection {
+            input,
+            pending: VecDeque::new(),
+            closed: false,
+            stdout: io::stdout(),
+        }
+    }
+}
+
+impl StdioConnection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn receive(&mut self, chunk: Result<Vec<u8>, ()>) {
+        match chunk {
+            Ok(chunk) if !chunk.is_empty() => self.pending.extend(chunk),
+            _ => self.closed = true,
+        }
+    }
+}
+
+impl Read for StdioConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() && !self.closed {
+            let chunk = self.input.recv().map_err(|_| ());
+            self.receive(chunk);
+        }
+
+        let count = buf.len().min(self.pending.len());
+        for (target, byte) in buf.iter_mut().zip(self.pending.drain(..count)) {
+            *target = byte;
+        }
+
+        Ok(count)
+    }
+}
+
+impl Write for StdioConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Connection for StdioConnection {
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        while !self.closed {
+            match self.input.try_recv() {
+                Ok(chunk) => self.receive(Ok(chunk)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.receive(Err(())),
+            }
+        }
+
+        if self.pending.front() == Some(&INTERRUPT_BYTE) {
+            self.pending.pop_front();
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+}
+
+enum Action {
+    Reply(Vec<u8>),
+    Resume,
+    Step,
+    Close(Option<Vec<u8>>),
+}
+
+pub struct GdbServer<'a, C: Connection, D: Display = Screen> {
+    chip8: &'a mut Chip8<D>,
+    debugger: Debugger,
+    connection: C,
+    no_ack_mode: bool,
+}
+
+pub fn serve_tcp<A: ToSocketAddrs, D: Display>(
+    chip8: &mut Chip8<D>,
+    address: A,
+) -> Result<(), GdbError> {
+    let listener = TcpListener::bind(address)?;
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+
+    GdbServer::new(chip8, stream).run()
+}
+
+pub fn serve_stdio<D: Display>(chip8: &mut Chip8<D>) -> Result<(), GdbError> {
+    GdbServer::new(chip8, StdioConnection::new()).run()
+}
+
+impl<'a, C: Connection, D: Display> GdbServer<'a, C, D> {
+    pub fn new(chip8: &'a mut Chip8<D>, connection: C) -> Self {
+        GdbServer {
+            chip8,
+            debugger: Debugger::new(),
+            connection,
+            no_ack_mode: false,
+        }
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    pub fn into_connection(self) -> C {
+        self.connection
+    }
+
+    pub fn run(&mut self) -> Result<(), GdbError> {
+        loop {
+            let packet = match self.read_packet() {
+                Ok(packet) => packet,
+                Err(GdbError::ConnectionClosed) => return Ok(()),
+                Err(err) => return Err(err),
+            };
+
+            match self.handle_packet(&packet) {
+                Action::Reply(reply) => self.write_packet(&reply)?,
+                Action::Step => {
+                    let stop_reason = self.debugger.step(self.chip8);
+                    self.write_packet(&Self::stop_reply(&stop_reason))?;
+                }
+                Action::Resume => {
+                    let stop_reason = self.resume()?;
+                    self.write_packet(&Self::stop_reply(&stop_reason))?;
+                }
+                Action::Close(reply) => {
+                    if let Some(reply) = reply {
+                        self.write_packet(&reply)?;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn resume(&mut self) -> Result<StopReason, GdbError> {
+        loop {
+            match self.debugger.resume(self.chip8, CYCLES_PER_INTERRUPT_POLL) {
+                StopReason::CycleLimit => {
+                    if self.connection.poll_interrupt()? {
+                        return Ok(StopReason::CycleLimit);
+                    }
+                }
+                stop_reason => return Ok(stop_reason),
+            }
+        }
+    }
+
+    fn stop_reply(stop_reason: &StopReason) -> Vec<u8> {
+        match stop_reason {
+            StopReason::Breakpoint(_) => format!("T{:02x}swbreak:;", SIGNAL_TRAP).into_bytes(),
+            StopReason::Step | StopReason::Blocked | StopReason::Watch { .. } => {
+                format!("S{:02x}", SIGNAL_TRAP).into_bytes()
+            }
+            StopReason::CycleLimit => format!("S{:02x}", SIGNAL_INTERRUPT).into_bytes(),
+            StopReason::Error(_) => format!("S{:02x}", SIGNAL_ILLEGAL_INSTRUCTION).into_bytes(),
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, GdbError> {
+        let mut buffer = [0u8; 1];
+
+        match self.connection.read(&mut buffer)? {
+            0 => Err(GdbError::ConnectionClosed),
+            _ => Ok(buffer[0]),
+        }
+    }
+
+    fn read_packet(&mut self) -> Result<Vec<u8>, GdbError> {
+        loop {
+            // Skip acknowledgements and stray interrupt requests until a packet starts
+            if self.read_byte()? != b'$' {
+                continue;
+            }
+
+            let mut data = Vec::new();
+            let mut checksum: u8 = 0;
+            loop {
+                let byte = self.read_byte()?;
+                if byte == b'#' {
+                    break;
+                }
+                checksum = checksum.wrapping_add(byte);
+                data.push(byte);
+            }
+
+            let checksum_digits = [self.read_byte()?, self.read_byte()?];
+            let expected_checksum = std::str::from_utf8(&checksum_digits)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+
+            if self.no_ack_mode {
+                return Ok(Self::unescape(&data));
+            }
+
+            if expected_checksum == Some(checksum) {
+                self.connection.write_all(b"+")?;
+                self.connection.flush()?;
+                return Ok(Self::unescape(&data));
+            }
+
+            self.connection.write_all(b"-")?;
+            self.connection.flush()?;
+        }
+    }
+
+    fn unescape(data: &[u8]) -> Vec<u8> {
+        let mut unescaped = Vec::with_capacity(data.len());
+        let mut bytes = data.iter();
+
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'}' => {
+                    if let Some(escaped) = bytes.next() {
+                        unescaped.push(escaped ^ 0x20);
+                    }
+                }
+                _ => unescaped.push(*byte),
+            }
+        }
+
+        unescaped
+    }
+
+    fn write_packet(&mut self, data: &[u8]) -> Result<(), GdbError> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        let mut checksum: u8 = 0;
+
+        packet.push(b'$');
+        for byte in data {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                packet.push(b'}');
+                packet.push(byte ^ 0x20);
+                checksum = checksum.wrapping_add(b'}').wrapping_add(byte ^ 0x20);
+            } else {
+                packet.push(*byte);
+                checksum = checksum.wrapping_add(*byte);
+            }
+        }
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+
+        self.connection.write_all(&packet)?;
+        self.connection.flush()?;
+
+        Ok(())
+    }
+
+    fn handle_packet(&mut self, packet: &[u8]) -> Action {
+        // Split on the raw byte, a packet from the network does not have to be valid UTF-8
+        let Some((&command, arguments)) = packet.split_first() else {
+            return Action::Reply(Vec::new());
+        };
+        let arguments = String::from_utf8_lossy(arguments);
+        let arguments = arguments.as_ref();
+
+        match command {
+            b'?' => Action::Reply(format!("S{:02x}", SIGNAL_TRAP).into_bytes()),
+            b'g' => Action::Reply(self.read_registers()),
+            b'G' => Self::reply_result(self.write_registers(arguments)),
+            b'p' => Action::Reply(
+                Self::parse_hex(arguments)
+                    .and_then(|register| self.read_register(register))
+                    .unwrap_or_else(|| b"E01".to_vec()),
+            ),
+            b'P' => Self::reply_result(self.write_register(arguments)),
+            b'm' => Action::Reply(
+                self.read_memory(arguments)
+                    .unwrap_or_else(|| b"E01".to_vec()),
+            ),
+            b'M' => Self::reply_result(self.write_memory(arguments)),
+            b'Z' | b'z' => self.handle_breakpoint(command == b'Z', arguments),
+            b's' => self.handle_resume(arguments, Action::Step),
+            b'c' => self.handle_resume(arguments, Action::Resume),
+            b'H' | b'T' => Action::Reply(b"OK".to_vec()),
+            b'k' => Action::Close(None),
+            b'D' => Action::Close(Some(b"OK".to_vec())),
+            b'q' | b'Q' => self.handle_query(&String::from_utf8_lossy(packet)),
+            _ => Action::Reply(Vec::new()),
+        }
+    }
+
+    fn reply_result(result: Option<()>) -> Action {
+        match result {
+            Some(()) => Action::Reply(b"OK".to_vec()),
+            None => Action::Reply(b"E01".to_vec()),
+        }
+    }
+
+    fn handle_resume(&mut self, arguments: &str, action: Action) -> Action {
+        if !arguments.is_empty() {
+            match Self::parse_hex(arguments) {
+                Some(address) if address < MEMORY_SIZE => self.chip8.program_counter = address,
+                _ => return Action::Reply(b"E01".to_vec()),
+            }
+        }
+
+        action
+    }
+
+    fn handle_breakpoint(&mut self, insert: bool, arguments: &str) -> Action {
+        let mut fields = arguments.split(',');
+        let kind = fields.next();
+        let address = fields.next().and_then(Self::parse_hex);
+
+        match (kind, address) {
+            // Hardware breakpoints are handled the same way as software breakpoints
+            (Some("0") | Some("1"), Some(address)) => {
+                if insert {
+                    self.debugger.add_breakpoint(address);
+                } else {
+                    self.debugger.remove_breakpoint(address);
+                }
+                Action::Reply(b"OK".to_vec())
+            }
+            _ => Action::Reply(Vec::new()),
+        }
+    }
+
+    fn handle_query(&mut self, packet: &str) -> Action {
+        let (name, arguments) = packet.split_once(':').unwrap_or((packet, ""));
+
+        match name {
+            "qSupported" => Action::Reply(
+                b"PacketSize=4000;qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+".to_vec(),
+            ),
+            "QStartNoAckMode" => {
+                // The acknowledgement for this packet is still sent, so the mode starts afterwards
+                self.no_ack_mode = true;
+                Action::Reply(b"OK".to_vec())
+            }
+            "qAttached" => Action::Reply(b"1".to_vec()),
+            "qC" => Action::Reply(b"QC1".to_vec()),
+            "qfThreadInfo" => Action::Reply(b"m1".to_vec()),
+            "qsThreadInfo" => Action::Reply(b"l".to_vec()),
+            "qXfer" => Action::Reply(Self::read_target_description(arguments)),
+            _ => Action::Reply(Vec::new()),
+        }
+    }
+
+    fn read_target_description(arguments: &str) -> Vec<u8> {
+        let mut fields = arguments.splitn(4, ':');
+
+        let (Some("features"), Some("read"), Some("target.xml"), Some(range)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return b"E00".to_vec();
+        };
+
+        let Some((offset, length)) = range.split_once(',').and_then(|(offset, length)| {
+            Some((Self::parse_hex(offset)?, Self::parse_hex(length)?))
+        }) else {
+            return b"E00".to_vec();
+        };
+
+        let description = TARGET_DESCRIPTION.as_bytes();
+        let start = offset.min(description.len());
+        let end = start.saturating_add(length).min(description.len());
+
+        let mut reply = vec![if end == description.len() { b'l' } else { b'm' }];
+        reply.extend_from_slice(&description[start..end]);
+        reply
+    }
+
+    fn register_values(&self) -> [(u16, usize); REGISTER_COUNT] {
+        let mut values = [(0u16, 1usize); REGISTER_COUNT];
+
+        for (register, value) in values.iter_mut().take(16).enumerate() {
+            let data_register = DataRegister::try_from(register as u8).unwrap();
+            *value = (self.chip8.data_registers[data_register] as u16, 1);
+        }
+        values[REGISTER_I] = (self.chip8.address_register as u16, 2);
+        values[REGISTER_PC] = (self.chip8.program_counter as u16, 2);
+        values[REGISTER_DT] = (self.chip8.delay_timer as u16, 1);
+        values[REGISTER_ST] = (self.chip8.sound_timer as u16, 1);
+        values[REGISTER_SP] = (self.chip8.stack.len() as u16, 1);
+
+        values
+    }
+
+    fn encode_register(value: u16, size: usize) -> String {
+        value.to_le_bytes()[..size]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn read_registers(&self) -> Vec<u8> {
+        self.register_values()
+            .iter()
+            .map(|(value, size)| Self::encode_register(*value, *size))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    fn read_register(&self, register: usize) -> Option<Vec<u8>> {
+        let (value, size) = self.register_values().get(register).copied()?;
+
+        Some(Self::encode_register(value, size).into_bytes())
+    }
+
+    fn set_register(&mut self, register: usize, value: u16) -> Option<()> {
+        match register {
+            0..=15 => {
+                let data_register = DataRegister::try_from(register as u8).ok()?;
+                self.chip8.data_registers[data_register] = value as u8;
+            }
+            REGISTER_I => self.chip8.address_register = value as usize,
+            REGISTER_PC => self.chip8.program_counter = value as usize,
+            REGISTER_DT => self.chip8.delay_timer = value as u8,
+            REGISTER_ST => self.chip8.sound_timer = value as u8,
+            // The stack pointer is derived from the stack and can only be shrunk
+            REGISTER_SP => self.chip8.stack.truncate(value as usize),
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    fn write_registers(&mut self, arguments: &str) -> Option<()> {
+        let bytes = Self::decode_hex_bytes(arguments)?;
+        let sizes: Vec<usize> = self
+            .register_values()
+            .iter()
+            .map(|(_, size)| *size)
+            .collect();
+
+        let mut offset = 0;
+        for (register, size) in sizes.into_iter().enumerate() {
+            let Some(value_bytes) = bytes.get(offset..offset + size) else {
+                break;
+            };
+            let mut value = [0u8; 2];
+            value[..size].copy_from_slice(value_bytes);
+
+            self.set_register(register, u16::from_le_bytes(value))?;
+            offset += size;
+        }
+
+        Some(())
+    }
+
+    fn write_register(&mut self, arguments: &str) -> Option<()> {
+        let (register, value) = arguments.split_once('=')?;
+        let register = Self::parse_hex(register)?;
+        let bytes = Self::decode_hex_bytes(value)?;
+
+        let mut value = [0u8; 2];
+        let size = bytes.len().min(2);
+        value[..size].copy_from_slice(&bytes[..size]);
+
+        self.set_register(register, u16::from_le_bytes(value))
+    }
+
+    fn read_memory(&self, arguments: &str) -> Option<Vec<u8>> {
+        let (address, length) = arguments.split_once(',')?;
+        let address = Self::parse_hex(address)?;
+        let length = Self::parse_hex(length)?;
+
+        let start = address.min(MEMORY_SIZE);
+        let end = address.saturating_add(length).min(MEMORY_SIZE);
+        if start == end && length > 0 {
+            return None;
+        }
+
+        Some(
+            self.chip8.memory.raw_data[start..end]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+                .into_bytes(),
+        )
+    }
+
+    fn write_memory(&mut self, arguments: &str) -> Option<()> {
+        let (location, data) = arguments.split_once(':')?;
+        let (address, length) = location.split_once(',')?;
+        let address = Self::parse_hex(address)?;
+        let length = Self::parse_hex(length)?;
+        let data = Self::decode_hex_bytes(data)?;
+
+        if data.len() != length {
+            return None;
+        }
+
+        self.chip8
+            .memory
+            .raw_data
+            .get_mut(address..address.checked_add(length)?)?
+            .copy_from_slice(&data);
+
+        Some(())
+    }
+
+    fn parse_hex(value: &str) -> Option<usize> {
+        usize::from_str_radix(value, 16).ok()
+    }
+
+    fn decode_hex_bytes(value: &str) -> Option<Vec<u8>> {
+        if !value.len().is_multiple_of(2) {
+            return None;
+        }
+
+        (0..value.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+            .collect()
+    }
+}
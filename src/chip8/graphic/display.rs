@@ -0,0 +1,55 @@
+use super::pixel::Pixel;
+use super::pixel_view::PixelView;
+use crate::chip8::constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Resolution {
+    #[default]
+    Low,
+    High,
+}
+
+impl Resolution {
+    pub fn width(&self) -> usize {
+        match self {
+            Resolution::Low => SCREEN_WIDTH,
+            Resolution::High => SCREEN_WIDTH * 2,
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        match self {
+            Resolution::Low => SCREEN_HEIGHT,
+            Resolution::High => SCREEN_HEIGHT * 2,
+        }
+    }
+}
+
+// Distances are in pixels of the current resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scroll {
+    Up(usize),
+    Down(usize),
+    Left(usize),
+    Right(usize),
+}
+
+// Everything the emulator does to the screen, implemented by frontends with their own renderer
+pub trait Display {
+    fn clear(&mut self);
+
+    // XORs the sprite onto the display with its top left corner already inside the screen,
+    // returns whether any pixel was erased
+    fn draw_sprite(&mut self, x: usize, y: usize, sprite: &dyn PixelView, wrap: bool) -> bool;
+
+    fn scroll(&mut self, scroll: Scroll);
+
+    fn set_resolution(&mut self, resolution: Resolution);
+
+    fn resolution(&self) -> Resolution;
+
+    fn pixel(&self, x: usize, y: usize) -> Pixel;
+
+    // Called after every frame, so the result can be shown
+    fn present(&mut self) {}
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+use super::display::{Display, Resolution, Scroll};
+use super::pixel::Pixel;
+use super::pixel_view::PixelView;
+use super::screen::Screen;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayEvent {
+    Clear,
+    Sprite {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        // Row by row, width pixels each
+        pixels: Vec<Pixel>,
+        wrap: bool,
+        collision: bool,
+    },
+    Scroll(Scroll),
+    Resolution(Resolution),
+    Present,
+}
+
+// Passes everything on to another display and records it as events, for frontends that
+// render on the GPU or somewhere else entirely
+#[derive(Default)]
+pub struct EventDisplay<D: Display = Screen> {
+    display: D,
+    events: VecDeque<DisplayEvent>,
+}
+
+impl<D: Display> EventDisplay<D> {
+    pub fn new(display: D) -> Self {
+        EventDisplay {
+            display,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn display(&self) -> &D {
+        &self.display
+    }
+
+    pub fn display_mut(&mut self) -> &mut D {
+        &mut self.display
+    }
+
+    pub fn into_inner(self) -> D {
+        self.display
+    }
+
+    pub fn has_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    pub fn next_event(&mut self) -> Option<DisplayEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn drain_events(&mut self) -> impl Iterator<Item = DisplayEvent> + '_ {
+        self.events.drain(..)
+    }
+}
+
+impl<D: Display> Display for EventDisplay<D> {
+    fn clear(&mut self) {
+        self.display.clear();
+        self.events.push_back(DisplayEvent::Clear);
+    }
+
+    fn draw_sprite(&mut self, x: usize, y: usize, sprite: &dyn PixelView, wrap: bool) -> bool {
+        let collision = self.display.draw_sprite(x, y, sprite, wrap);
+
+        let pixels = (0..sprite.height())
+            .flat_map(|y| (0..sprite.width()).map(move |x| (x, y)))
+            .map(|(x, y)| sprite.get_pixel_unchecked(x, y))
+            .collect();
+        self.events.push_back(DisplayEvent::Sprite {
+            x,
+            y,
+            width: sprite.width(),
+            height: sprite.height(),
+            pixels,
+            wrap,
+            collision,
+        });
+
+        collision
+    }
+
+    fn scroll(&mut self, scroll: Scroll) {
+        self.display.scroll(scroll);
+        self.events.push_back(DisplayEvent::Scroll(scroll));
+    }
+
+    fn set_resolution(&mut self, resolution: Resolution) {
+        self.display.set_resolution(resolution);
+        self.events.push_back(DisplayEvent::Resolution(resolution));
+    }
+
+    fn resolution(&self) -> Resolution {
+        self.display.resolution()
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Pixel {
+        self.display.pixel(x, y)
+    }
+
+    fn present(&mut self) {
+        self.display.present();
+        self.events.push_back(DisplayEvent::Present);
+    }
+}
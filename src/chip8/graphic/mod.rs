@@ -1,7 +1,11 @@
+mod display;
+mod event_display;
 mod pixel;
 mod pixel_view;
 mod screen;
 
+pub use display::{Display, Resolution, Scroll};
+pub use event_display::{DisplayEvent, EventDisplay};
 pub use pixel::Pixel;
 pub use pixel_view::{BitSlicePixelView, PixelView};
 pub use screen::{Screen, XorPixelErased};
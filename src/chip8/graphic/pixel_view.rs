@@ -17,7 +17,11 @@ pub struct BitSlicePixelView<'a> {
 }
 
 impl<'a> BitSlicePixelView<'a> {
-    pub fn new(slice: &'a BitSlice<u8, Msb0>, width: usize, height: usize) -> BitSlicePixelView {
+    pub fn new(
+        slice: &'a BitSlice<u8, Msb0>,
+        width: usize,
+        height: usize,
+    ) -> BitSlicePixelView<'a> {
         BitSlicePixelView {
             slice,
             width,
@@ -25,7 +29,11 @@ impl<'a> BitSlicePixelView<'a> {
         }
     }
 
-    pub fn new_from_byte_slice(slice: &'a [u8], width: usize, height: usize) -> BitSlicePixelView {
+    pub fn new_from_byte_slice(
+        slice: &'a [u8],
+        width: usize,
+        height: usize,
+    ) -> BitSlicePixelView<'a> {
         BitSlicePixelView {
             slice: slice.view_bits::<Msb0>(),
             width,
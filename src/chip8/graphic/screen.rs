@@ -1,4 +1,6 @@
+use super::display::{Display, Resolution, Scroll};
 use super::pixel::Pixel;
+use super::pixel_view::PixelView;
 use crate::chip8::constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
 pub struct Screen {
@@ -61,4 +63,74 @@ impl Screen {
     pub fn framebuffer(&self) -> &[[Pixel; SCREEN_WIDTH]; SCREEN_HEIGHT] {
         &self.framebuffer
     }
+
+    pub fn set_framebuffer(&mut self, framebuffer: [[Pixel; SCREEN_WIDTH]; SCREEN_HEIGHT]) {
+        self.framebuffer = framebuffer;
+        self.content_updated = true;
+    }
+}
+
+// Only has the 64x32 resolution of the original CHIP-8, so resolution changes are ignored
+impl Display for Screen {
+    fn clear(&mut self) {
+        Screen::clear(self);
+    }
+
+    fn draw_sprite(&mut self, x: usize, y: usize, sprite: &dyn PixelView, wrap: bool) -> bool {
+        let mut pixel_erased = false;
+
+        for sprite_y in 0..sprite.height() {
+            for sprite_x in 0..sprite.width() {
+                let clipped = x + sprite_x >= SCREEN_WIDTH || y + sprite_y >= SCREEN_HEIGHT;
+                if clipped && !wrap {
+                    continue;
+                }
+
+                let erased = self.xor_pixel_wrapped_position(
+                    x + sprite_x,
+                    y + sprite_y,
+                    sprite.get_pixel_unchecked(sprite_x, sprite_y),
+                );
+                pixel_erased |= matches!(erased, XorPixelErased::Yes);
+            }
+        }
+
+        pixel_erased
+    }
+
+    fn scroll(&mut self, scroll: Scroll) {
+        let source = self.framebuffer;
+        let (dx, dy) = match scroll {
+            Scroll::Up(distance) => (0, -(distance as isize)),
+            Scroll::Down(distance) => (0, distance as isize),
+            Scroll::Left(distance) => (-(distance as isize), 0),
+            Scroll::Right(distance) => (distance as isize, 0),
+        };
+
+        for (y, row) in self.framebuffer.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let source_x = x as isize - dx;
+                let source_y = y as isize - dy;
+
+                *pixel = match (0..SCREEN_WIDTH as isize).contains(&source_x)
+                    && (0..SCREEN_HEIGHT as isize).contains(&source_y)
+                {
+                    true => source[source_y as usize][source_x as usize],
+                    false => Pixel::Off,
+                };
+            }
+        }
+
+        self.content_updated = true;
+    }
+
+    fn set_resolution(&mut self, _resolution: Resolution) {}
+
+    fn resolution(&self) -> Resolution {
+        Resolution::Low
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Pixel {
+        self.framebuffer[y % SCREEN_HEIGHT][x % SCREEN_WIDTH]
+    }
 }
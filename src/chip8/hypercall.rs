@@ -0,0 +1,55 @@
+use thiserror::Error;
+
+use super::graphic::Display;
+use super::Chip8;
+
+// Why a hypercall stopped the machine, like a failed assertion in a test ROM
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{0}")]
+pub struct HypercallError(pub String);
+
+impl From<&str> for HypercallError {
+    fn from(message: &str) -> Self {
+        HypercallError(message.to_string())
+    }
+}
+
+impl From<String> for HypercallError {
+    fn from(message: String) -> Self {
+        HypercallError(message)
+    }
+}
+
+pub(crate) type Hypercall<D> = Box<dyn FnMut(&mut Chip8<D>) -> Result<(), HypercallError> + Send>;
+
+impl<D: Display> Chip8<D> {
+    // Runs the handler instead of the machine code routine whenever 0NNN calls the address,
+    // execution goes on with the next instruction unless the handler returns an error
+    pub fn register_hypercall<F>(&mut self, address: usize, handler: F)
+    where
+        F: FnMut(&mut Chip8<D>) -> Result<(), HypercallError> + Send + 'static,
+    {
+        self.hypercalls.insert(address, Box::new(handler));
+    }
+
+    pub fn unregister_hypercall(&mut self, address: usize) -> bool {
+        self.hypercalls.remove(&address).is_some()
+    }
+
+    pub fn has_hypercall(&self, address: usize) -> bool {
+        self.hypercalls.contains_key(&address)
+    }
+
+    // Calls without a handler are ignored, like on every interpreter but the original
+    pub(crate) fn hypercall(&mut self, address: usize) -> Result<(), HypercallError> {
+        // The handler is taken out while it runs, so it can have the whole machine
+        let Some(mut handler) = self.hypercalls.remove(&address) else {
+            return Ok(());
+        };
+
+        let result = handler(self);
+        self.hypercalls.entry(address).or_insert(handler);
+
+        result
+    }
+}
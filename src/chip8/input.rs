@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use super::constants::FRAMES_PER_SECOND;
+use super::graphic::Display;
+use super::keyboard::Key;
+use super::Chip8;
+
+// A key press or release at a point in emulated time, counted from the last reset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputEvent {
+    pub time: Duration,
+    pub key: Key,
+    pub pressed: bool,
+}
+
+impl InputEvent {
+    pub fn key_down(time: Duration, key: Key) -> Self {
+        InputEvent {
+            time,
+            key,
+            pressed: true,
+        }
+    }
+
+    pub fn key_up(time: Duration, key: Key) -> Self {
+        InputEvent {
+            time,
+            key,
+            pressed: false,
+        }
+    }
+}
+
+impl<D: Display> Chip8<D> {
+    // Queues an event for run_frame, which applies it before the first instruction that
+    // starts at or after its time
+    pub fn push_input(&mut self, event: InputEvent) {
+        let index = self
+            .input
+            .partition_point(|queued| queued.time <= event.time);
+        self.input.insert(index, event);
+    }
+
+    pub fn pending_input(&self) -> usize {
+        self.input.len()
+    }
+
+    pub fn clear_input(&mut self) {
+        self.input.clear();
+    }
+
+    // Emulated time of the start of the next frame
+    pub fn emulated_time(&self) -> Duration {
+        self.frame_time(0, 1)
+    }
+
+    // The point that lies elapsed out of total steps into the current frame, steps being
+    // instructions or machine cycles depending on the timing model
+    pub(crate) fn frame_time(&self, elapsed: u64, total: u64) -> Duration {
+        let total = total.max(1) as u128;
+        let nanos = (self.frames as u128 * total + elapsed as u128) * 1_000_000_000
+            / (FRAMES_PER_SECOND as u128 * total);
+
+        Duration::from_nanos(nanos as u64)
+    }
+
+    // Applies the events that are due, a key pressed here is only released at a later
+    // boundary and the keyboard keeps it pressed for the key reads until an instruction ran,
+    // so every press is seen by at least one instruction even while the machine idles
+    pub(crate) fn apply_input(&mut self, time: Duration) {
+        let mut pressed = Vec::new();
+
+        while let Some(event) = self.input.front().copied() {
+            if event.time > time || (!event.pressed && pressed.contains(&event.key)) {
+                break;
+            }
+            self.input.pop_front();
+
+            match event.pressed {
+                true => {
+                    pressed.push(event.key);
+                    self.key_down(event.key);
+                }
+                false => self.key_up(event.key),
+            }
+        }
+    }
+}
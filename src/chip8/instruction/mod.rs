@@ -1,8 +1,10 @@
 pub mod parser;
 
+use std::fmt::{self, Display, Formatter};
+
 use crate::data_register::DataRegister;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Instruction {
     ExecuteMachineLanguageSubroutine {
         address: usize,
@@ -124,3 +126,142 @@ pub enum Instruction {
         vx: DataRegister,
     },
 }
+
+impl Instruction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instruction::ExecuteMachineLanguageSubroutine { .. } => {
+                "ExecuteMachineLanguageSubroutine"
+            }
+            Instruction::ClearScreen => "ClearScreen",
+            Instruction::ReturnFromSubroutine => "ReturnFromSubroutine",
+            Instruction::JumpToAddress { .. } => "JumpToAddress",
+            Instruction::ExecuteSubroutine { .. } => "ExecuteSubroutine",
+            Instruction::SkipIfVxEqualsNum { .. } => "SkipIfVxEqualsNum",
+            Instruction::SkipIfVxNotEqualNum { .. } => "SkipIfVxNotEqualNum",
+            Instruction::SkipIfVxEqualsVy { .. } => "SkipIfVxEqualsVy",
+            Instruction::StoreNumInVx { .. } => "StoreNumInVx",
+            Instruction::AddNumToVx { .. } => "AddNumToVx",
+            Instruction::StoreVyInVx { .. } => "StoreVyInVx",
+            Instruction::SetVxToVxOrVy { .. } => "SetVxToVxOrVy",
+            Instruction::SetVxToVxAndVy { .. } => "SetVxToVxAndVy",
+            Instruction::SetVxToVxXorVy { .. } => "SetVxToVxXorVy",
+            Instruction::AddVyToVx { .. } => "AddVyToVx",
+            Instruction::SubtractVyFromVx { .. } => "SubtractVyFromVx",
+            Instruction::ShiftVyRightStoreInVx { .. } => "ShiftVyRightStoreInVx",
+            Instruction::SetVxToVyMinusVx { .. } => "SetVxToVyMinusVx",
+            Instruction::ShiftVyLeftStoreInVx { .. } => "ShiftVyLeftStoreInVx",
+            Instruction::SkipIfVxNotEqualVy { .. } => "SkipIfVxNotEqualVy",
+            Instruction::StoreAddressInAddressRegister { .. } => "StoreAddressInAddressRegister",
+            Instruction::JumpToAddressPlusV0 { .. } => "JumpToAddressPlusV0",
+            Instruction::SetVxToRandomWithMask { .. } => "SetVxToRandomWithMask",
+            Instruction::DrawSpriteAtVxVy { .. } => "DrawSpriteAtVxVy",
+            Instruction::SkipIfKeyInVxPressed { .. } => "SkipIfKeyInVxPressed",
+            Instruction::SkipIfKeyInVxNotPressed { .. } => "SkipIfKeyInVxNotPressed",
+            Instruction::StoreDelayTimerInVx { .. } => "StoreDelayTimerInVx",
+            Instruction::WaitForKeypressStoreInVx { .. } => "WaitForKeypressStoreInVx",
+            Instruction::SetDelayTimerToVx { .. } => "SetDelayTimerToVx",
+            Instruction::SetSoundTimerToVx { .. } => "SetSoundTimerToVx",
+            Instruction::AddVxToAddressRegister { .. } => "AddVxToAddressRegister",
+            Instruction::SetAddressRegisterToSpriteAddressOfSpriteInVx { .. } => {
+                "SetAddressRegisterToSpriteAddressOfSpriteInVx"
+            }
+            Instruction::StoreBCDOfVx { .. } => "StoreBCDOfVx",
+            Instruction::StoreRegistersInMemory { .. } => "StoreRegistersInMemory",
+            Instruction::FillRegistersFromMemory { .. } => "FillRegistersFromMemory",
+        }
+    }
+
+    pub fn target_address(&self) -> Option<usize> {
+        match self {
+            Instruction::ExecuteMachineLanguageSubroutine { address }
+            | Instruction::JumpToAddress { address }
+            | Instruction::ExecuteSubroutine { address }
+            | Instruction::StoreAddressInAddressRegister { address }
+            | Instruction::JumpToAddressPlusV0 { address } => Some(*address),
+            _ => None,
+        }
+    }
+}
+
+// Instructions are rendered in Octo syntax, so skips read as the condition under which
+// the following instruction is executed
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let register = |register: &DataRegister| format!("v{:x}", u8::from(*register));
+
+        match self {
+            Instruction::ExecuteMachineLanguageSubroutine { address } => {
+                write!(f, "native {:#05x}", address)
+            }
+            Instruction::ClearScreen => write!(f, "clear"),
+            Instruction::ReturnFromSubroutine => write!(f, "return"),
+            Instruction::JumpToAddress { address } => write!(f, "jump {:#05x}", address),
+            Instruction::ExecuteSubroutine { address } => write!(f, ":call {:#05x}", address),
+            Instruction::SkipIfVxEqualsNum { vx, num } => {
+                write!(f, "if {} != {:#04x} then", register(vx), num)
+            }
+            Instruction::SkipIfVxNotEqualNum { vx, num } => {
+                write!(f, "if {} == {:#04x} then", register(vx), num)
+            }
+            Instruction::SkipIfVxEqualsVy { vx, vy } => {
+                write!(f, "if {} != {} then", register(vx), register(vy))
+            }
+            Instruction::StoreNumInVx { vx, num } => write!(f, "{} := {:#04x}", register(vx), num),
+            Instruction::AddNumToVx { vx, num } => write!(f, "{} += {:#04x}", register(vx), num),
+            Instruction::StoreVyInVx { vx, vy } => {
+                write!(f, "{} := {}", register(vx), register(vy))
+            }
+            Instruction::SetVxToVxOrVy { vx, vy } => {
+                write!(f, "{} |= {}", register(vx), register(vy))
+            }
+            Instruction::SetVxToVxAndVy { vx, vy } => {
+                write!(f, "{} &= {}", register(vx), register(vy))
+            }
+            Instruction::SetVxToVxXorVy { vx, vy } => {
+                write!(f, "{} ^= {}", register(vx), register(vy))
+            }
+            Instruction::AddVyToVx { vx, vy } => write!(f, "{} += {}", register(vx), register(vy)),
+            Instruction::SubtractVyFromVx { vx, vy } => {
+                write!(f, "{} -= {}", register(vx), register(vy))
+            }
+            Instruction::ShiftVyRightStoreInVx { vx, vy } => {
+                write!(f, "{} >>= {}", register(vx), register(vy))
+            }
+            Instruction::SetVxToVyMinusVx { vx, vy } => {
+                write!(f, "{} =- {}", register(vx), register(vy))
+            }
+            Instruction::ShiftVyLeftStoreInVx { vx, vy } => {
+                write!(f, "{} <<= {}", register(vx), register(vy))
+            }
+            Instruction::SkipIfVxNotEqualVy { vx, vy } => {
+                write!(f, "if {} == {} then", register(vx), register(vy))
+            }
+            Instruction::StoreAddressInAddressRegister { address } => {
+                write!(f, "i := {:#05x}", address)
+            }
+            Instruction::JumpToAddressPlusV0 { address } => write!(f, "jump0 {:#05x}", address),
+            Instruction::SetVxToRandomWithMask { vx, mask } => {
+                write!(f, "{} := random {:#04x}", register(vx), mask)
+            }
+            Instruction::DrawSpriteAtVxVy { vx, vy, byte_count } => {
+                write!(f, "sprite {} {} {}", register(vx), register(vy), byte_count)
+            }
+            Instruction::SkipIfKeyInVxPressed { vx } => write!(f, "if {} -key then", register(vx)),
+            Instruction::SkipIfKeyInVxNotPressed { vx } => {
+                write!(f, "if {} key then", register(vx))
+            }
+            Instruction::StoreDelayTimerInVx { vx } => write!(f, "{} := delay", register(vx)),
+            Instruction::WaitForKeypressStoreInVx { vx } => write!(f, "{} := key", register(vx)),
+            Instruction::SetDelayTimerToVx { vx } => write!(f, "delay := {}", register(vx)),
+            Instruction::SetSoundTimerToVx { vx } => write!(f, "buzzer := {}", register(vx)),
+            Instruction::AddVxToAddressRegister { vx } => write!(f, "i += {}", register(vx)),
+            Instruction::SetAddressRegisterToSpriteAddressOfSpriteInVx { vx } => {
+                write!(f, "i := hex {}", register(vx))
+            }
+            Instruction::StoreBCDOfVx { vx } => write!(f, "bcd {}", register(vx)),
+            Instruction::StoreRegistersInMemory { vx } => write!(f, "save {}", register(vx)),
+            Instruction::FillRegistersFromMemory { vx } => write!(f, "load {}", register(vx)),
+        }
+    }
+}
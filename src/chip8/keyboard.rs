@@ -1,6 +1,6 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-#[derive(Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
 pub enum Key {
     Num0 = 0x0,
@@ -21,6 +21,17 @@ pub enum Key {
     F = 0xF,
 }
 
+// How FX0A waits for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyWait {
+    // Like the COSMAC VIP, a key pressed during the wait has to be released again, timers keep
+    // running and the buzzer sounds while the key is held
+    #[default]
+    Accurate,
+    // Releasing any key ends the wait and timers are paused while waiting
+    Legacy,
+}
+
 #[derive(Clone, Copy)]
 pub enum KeyState {
     Pressed,
@@ -29,12 +40,16 @@ pub enum KeyState {
 
 pub struct Keyboard {
     raw: [KeyState; 16],
+    // Keys pressed since the last instruction ran, one bit per key, so that a press released
+    // again before any instruction could see it still reaches the program
+    unobserved: u16,
 }
 
 impl Default for Keyboard {
     fn default() -> Self {
         Keyboard {
             raw: [KeyState::Released; 16],
+            unobserved: 0,
         }
     }
 }
@@ -46,6 +61,7 @@ impl Keyboard {
 
     pub fn key_down(&mut self, key: Key) {
         self.raw[u8::from(key) as usize] = KeyState::Pressed;
+        self.unobserved |= 1 << u8::from(key);
     }
 
     pub fn key_up(&mut self, key: Key) {
@@ -55,6 +71,26 @@ impl Keyboard {
     pub fn get_key_state(&self, key: Key) -> KeyState {
         self.raw[u8::from(key) as usize]
     }
+
+    // The state the program sees, which includes presses no instruction has seen yet
+    pub fn observed_key_state(&self, key: Key) -> KeyState {
+        match self.unobserved & 1 << u8::from(key) != 0 {
+            true => KeyState::Pressed,
+            false => self.get_key_state(key),
+        }
+    }
+
+    pub fn mark_observed(&mut self) {
+        self.unobserved = 0;
+    }
+
+    pub fn unobserved(&self) -> u16 {
+        self.unobserved
+    }
+
+    pub fn set_unobserved(&mut self, keys: u16) {
+        self.unobserved = keys;
+    }
 }
 
 impl TryFrom<char> for Key {
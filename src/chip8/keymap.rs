@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::keyboard::Key;
+
+// The 4x4 hex keypad of the COSMAC VIP, row by row
+const KEYPAD: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum KeyMapError {
+    #[error("line {0}: expected 'name = value' or '[roms.\"name\"]'")]
+    InvalidLine(usize),
+    #[error("line {line}: unknown layout '{name}'")]
+    UnknownLayout { line: usize, name: String },
+    #[error("line {line}: '{name}' is not a CHIP-8 key")]
+    InvalidKey { line: usize, name: String },
+    #[error("line {0}: expected a string or a list of strings")]
+    InvalidValue(usize),
+}
+
+#[derive(Error, Debug)]
+#[error("unknown layout '{0}'")]
+pub struct UnknownLayoutError(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Dvorak,
+    NumericKeypad,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 4] = [
+        Layout::Qwerty,
+        Layout::Azerty,
+        Layout::Dvorak,
+        Layout::NumericKeypad,
+    ];
+
+    // The host keys of every CHIP-8 key
+    pub fn bindings(&self) -> Vec<(Key, Vec<&'static str>)> {
+        let positional = |rows: [[&'static [&'static str]; 4]; 4]| {
+            rows.iter()
+                .flatten()
+                .zip(KEYPAD)
+                .map(|(hosts, key)| (Key::try_from(key).unwrap(), hosts.to_vec()))
+                .collect()
+        };
+
+        match self {
+            Layout::Qwerty => positional([
+                [&["1"], &["2"], &["3"], &["4"]],
+                [&["q"], &["w"], &["e"], &["r"]],
+                [&["a"], &["s"], &["d"], &["f"]],
+                [&["z"], &["x"], &["c"], &["v"]],
+            ]),
+            // Also binds the characters the unshifted number row produces
+            Layout::Azerty => positional([
+                [&["1", "&"], &["2", "é"], &["3", "\""], &["4", "'"]],
+                [&["a"], &["z"], &["e"], &["r"]],
+                [&["q"], &["s"], &["d"], &["f"]],
+                [&["w"], &["x"], &["c"], &["v"]],
+            ]),
+            Layout::Dvorak => positional([
+                [&["1"], &["2"], &["3"], &["4"]],
+                [&["'"], &[","], &["."], &["p"]],
+                [&["a"], &["o"], &["e"], &["u"]],
+                [&[";"], &["q"], &["j"], &["k"]],
+            ]),
+            // The digits keep their value, the letters go to the operator keys
+            Layout::NumericKeypad => [
+                "numpad0",
+                "numpad1",
+                "numpad2",
+                "numpad3",
+                "numpad4",
+                "numpad5",
+                "numpad6",
+                "numpad7",
+                "numpad8",
+                "numpad9",
+                "numpaddivide",
+                "numpadmultiply",
+                "numpadsubtract",
+                "numpadadd",
+                "numpadenter",
+                "numpaddecimal",
+            ]
+            .iter()
+            .enumerate()
+            .map(|(key, host)| (Key::try_from(key as u8).unwrap(), vec![*host]))
+            .collect(),
+        }
+    }
+}
+
+impl Display for Layout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Qwerty => write!(f, "qwerty"),
+            Layout::Azerty => write!(f, "azerty"),
+            Layout::Dvorak => write!(f, "dvorak"),
+            Layout::NumericKeypad => write!(f, "numpad"),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = UnknownLayoutError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name
+            .to_ascii_lowercase()
+            .replace(['-', '_', ' '], "")
+            .as_str()
+        {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "dvorak" => Ok(Layout::Dvorak),
+            "numpad" | "numerickeypad" | "keypad" => Ok(Layout::NumericKeypad),
+            _ => Err(UnknownLayoutError(name.to_string())),
+        }
+    }
+}
+
+// Host keys are characters or key names like "ArrowUp", both compared case insensitively
+fn normalize(host: &str) -> String {
+    host.to_lowercase()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyMap {
+    bindings: BTreeMap<String, Key>,
+}
+
+impl KeyMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_layout(layout: Layout) -> Self {
+        let mut keymap = KeyMap::new();
+        for (key, hosts) in layout.bindings() {
+            for host in hosts {
+                keymap.bind(host, key);
+            }
+        }
+
+        keymap
+    }
+
+    // A CHIP-8 key can have any number of host keys, a host key only one CHIP-8 key
+    pub fn bind(&mut self, host: &str, key: Key) {
+        self.bindings.insert(normalize(host), key);
+    }
+
+    pub fn unbind(&mut self, host: &str) -> Option<Key> {
+        self.bindings.remove(&normalize(host))
+    }
+
+    pub fn unbind_key(&mut self, key: Key) {
+        self.bindings.retain(|_, bound| *bound != key);
+    }
+
+    pub fn key(&self, host: &str) -> Option<Key> {
+        self.bindings.get(&normalize(host)).copied()
+    }
+
+    pub fn key_for_char(&self, character: char) -> Option<Key> {
+        self.key(character.encode_utf8(&mut [0; 4]))
+    }
+
+    pub fn host_keys(&self, key: Key) -> impl Iterator<Item = &str> {
+        self.bindings
+            .iter()
+            .filter(move |(_, bound)| **bound == key)
+            .map(|(host, _)| host.as_str())
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, Key)> {
+        self.bindings
+            .iter()
+            .map(|(host, key)| (host.as_str(), *key))
+    }
+
+    // Every CHIP-8 key bound in the overrides loses its previous host keys
+    pub fn apply(&mut self, overrides: &KeyMap) {
+        for (_, key) in overrides.bindings() {
+            self.unbind_key(key);
+        }
+        for (host, key) in overrides.bindings() {
+            self.bind(host, key);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Section {
+    layout: Option<Layout>,
+    overrides: KeyMap,
+}
+
+// Key bindings of a frontend, read from a small subset of TOML:
+//
+//   layout = "azerty"
+//   5 = ["z", "ArrowUp"]
+//
+//   [roms."pong.ch8"]
+//   1 = "ArrowUp"
+//
+// rust8-dbg reads it with -k, the other bundled binary, rust8-patch, has no keys to map
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyConfig {
+    default: Section,
+    roms: HashMap<String, Section>,
+}
+
+// Length of the quoted string at the start of the text, including its quotes
+fn quoted_length(text: &str) -> Option<usize> {
+    let quote = text
+        .chars()
+        .next()
+        .filter(|quote| matches!(quote, '"' | '\''))?;
+    let mut escaped = false;
+
+    for (index, character) in text.char_indices().skip(1) {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quote == '"' => escaped = true,
+            _ if character == quote => return Some(index + 1),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut rest = line;
+    let mut offset = 0;
+
+    while let Some(index) = rest.find(['#', '"', '\'']) {
+        if rest[index..].starts_with('#') {
+            return &line[..offset + index];
+        }
+
+        let length = index + quoted_length(&rest[index..]).unwrap_or(rest.len() - index);
+        offset += length;
+        rest = &rest[length..];
+    }
+
+    line
+}
+
+// Literal strings in single quotes are taken as they are, basic strings in double quotes
+// may escape characters with a backslash
+fn parse_string(value: &str) -> Option<String> {
+    let value = value.trim();
+    if quoted_length(value)? != value.len() {
+        return None;
+    }
+
+    let inner = &value[1..value.len() - 1];
+    if value.starts_with('\'') {
+        return Some(inner.to_string());
+    }
+
+    let mut string = String::new();
+    let mut characters = inner.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => string.push(characters.next()?),
+            _ => string.push(character),
+        }
+    }
+
+    Some(string)
+}
+
+fn parse_strings(value: &str) -> Option<Vec<String>> {
+    let value = value.trim();
+    let Some(list) = value.strip_prefix('[') else {
+        return Some(vec![parse_string(value)?]);
+    };
+
+    let mut strings = Vec::new();
+    let mut rest = list.strip_suffix(']')?.trim_start();
+    while !rest.is_empty() {
+        let length = quoted_length(rest)?;
+        strings.push(parse_string(&rest[..length])?);
+
+        rest = rest[length..].trim_start();
+        rest = match rest.strip_prefix(',') {
+            Some(rest) => rest.trim_start(),
+            None if rest.is_empty() => rest,
+            None => return None,
+        };
+    }
+
+    Some(strings)
+}
+
+impl KeyConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, KeyMapError> {
+        let mut config = KeyConfig::new();
+        let mut rom = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .strip_suffix(']')
+                    .and_then(|header| header.trim().strip_prefix("roms."))
+                    .ok_or(KeyMapError::InvalidLine(line_number))?;
+                let name = parse_string(name).unwrap_or_else(|| name.trim().to_string());
+
+                config.roms.entry(name.clone()).or_default();
+                rom = Some(name);
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or(KeyMapError::InvalidLine(line_number))?;
+            let name = name.trim();
+            let section = match &rom {
+                Some(rom) => config.roms.get_mut(rom).unwrap(),
+                None => &mut config.default,
+            };
+
+            if name == "layout" {
+                let layout = parse_string(value).ok_or(KeyMapError::InvalidValue(line_number))?;
+                section.layout = Some(layout.parse().map_err(|_| KeyMapError::UnknownLayout {
+                    line: line_number,
+                    name: layout,
+                })?);
+                continue;
+            }
+
+            let key = u8::from_str_radix(name.strip_prefix("0x").unwrap_or(name), 16)
+                .ok()
+                .and_then(|key| Key::try_from(key).ok())
+                .ok_or(KeyMapError::InvalidKey {
+                    line: line_number,
+                    name: name.to_string(),
+                })?;
+            let hosts = parse_strings(value).ok_or(KeyMapError::InvalidValue(line_number))?;
+
+            section.overrides.unbind_key(key);
+            for host in hosts {
+                section.overrides.bind(&host, key);
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn keymap(&self) -> KeyMap {
+        let mut keymap = KeyMap::from_layout(self.default.layout.unwrap_or_default());
+        keymap.apply(&self.default.overrides);
+        keymap
+    }
+
+    // A ROM with its own layout starts from that layout, otherwise from the default keymap
+    pub fn keymap_for_rom(&self, rom: &str) -> KeyMap {
+        let Some(section) = self.roms.get(rom) else {
+            return self.keymap();
+        };
+
+        let mut keymap = match section.layout {
+            Some(layout) => KeyMap::from_layout(layout),
+            None => self.keymap(),
+        };
+        keymap.apply(&section.overrides);
+        keymap
+    }
+}
+
+impl FromStr for KeyConfig {
+    type Err = KeyMapError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
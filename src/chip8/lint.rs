@@ -0,0 +1,406 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+use super::cfg::{BasicBlock, ControlFlowGraph, Finding, Severity};
+use super::constants::{
+    DEFAULT_PROGRAM_ADDRESS, SCREEN_HEIGHT, SCREEN_WIDTH, UNPROTECTED_MEMORY_START,
+};
+use super::data_register::DataRegister;
+use super::disassembler::RecursiveDisassembly;
+use super::instruction::Instruction;
+use super::platform::Platform;
+
+const SPRITE_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    ShiftQuirk,
+    MemoryIncrementQuirk,
+    JumpQuirk,
+    SpriteAtScreenEdge {
+        x: Option<u8>,
+        y: Option<u8>,
+    },
+    ProtectedMemoryWrite {
+        target: usize,
+    },
+    OddJumpTarget {
+        target: usize,
+    },
+    MachineCodeCall {
+        target: usize,
+    },
+    Recursion,
+    StackOverflow {
+        depth: usize,
+        limit: usize,
+    },
+    UnsupportedOpcode {
+        opcode: u16,
+        platform: Option<Platform>,
+    },
+    ControlFlow(Finding),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub address: usize,
+    pub severity: Severity,
+    pub kind: LintKind,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{} {:#05x}: ", severity, self.address)?;
+
+        match &self.kind {
+            LintKind::ShiftQuirk => write!(f, "shift result depends on the shift quirk"),
+            LintKind::MemoryIncrementQuirk => {
+                write!(f, "value of i afterwards depends on the memory quirk")
+            }
+            LintKind::JumpQuirk => write!(f, "jump target depends on the jump quirk"),
+            LintKind::SpriteAtScreenEdge { x, y } => {
+                let position = |value: &Option<u8>| match value {
+                    Some(value) => value.to_string(),
+                    None => "?".to_string(),
+                };
+                write!(
+                    f,
+                    "sprite at ({}, {}) crosses the screen edge, clipping depends on the wrap quirk",
+                    position(x),
+                    position(y)
+                )
+            }
+            LintKind::ProtectedMemoryWrite { target } => {
+                write!(f, "write to {:#05x} inside the interpreter area", target)
+            }
+            LintKind::OddJumpTarget { target } => {
+                write!(f, "jump to odd address {:#05x}", target)
+            }
+            LintKind::MachineCodeCall { target } => {
+                write!(f, "call of machine code routine at {:#05x}", target)
+            }
+            LintKind::Recursion => write!(f, "subroutine is recursive, the stack may overflow"),
+            LintKind::StackOverflow { depth, limit } => write!(
+                f,
+                "call depth of {} exceeds the stack size of {}",
+                depth, limit
+            ),
+            LintKind::UnsupportedOpcode {
+                opcode,
+                platform: Some(platform),
+            } => write!(f, "opcode {:04x} requires {}", opcode, platform),
+            LintKind::UnsupportedOpcode {
+                opcode,
+                platform: None,
+            } => write!(f, "opcode {:04x} is not defined on any platform", opcode),
+            LintKind::ControlFlow(finding) => write!(f, "{}", finding),
+        }
+    }
+}
+
+// Register and I values known from constant loads earlier in the same basic block
+#[derive(Default)]
+struct KnownValues {
+    registers: [Option<u8>; 16],
+    address_register: Option<usize>,
+}
+
+impl KnownValues {
+    fn register(&self, register: DataRegister) -> Option<u8> {
+        self.registers[u8::from(register) as usize]
+    }
+
+    fn set_register(&mut self, register: DataRegister, value: Option<u8>) {
+        self.registers[u8::from(register) as usize] = value;
+    }
+
+    fn update(&mut self, instruction: &Instruction) {
+        match *instruction {
+            Instruction::StoreNumInVx { vx, num } => self.set_register(vx, Some(num)),
+            Instruction::AddNumToVx { vx, num } => {
+                let value = self.register(vx).map(|value| value.wrapping_add(num));
+                self.set_register(vx, value);
+            }
+            Instruction::StoreVyInVx { vx, vy } => self.set_register(vx, self.register(vy)),
+            Instruction::SetVxToVxOrVy { vx, .. }
+            | Instruction::SetVxToVxAndVy { vx, .. }
+            | Instruction::SetVxToVxXorVy { vx, .. }
+            | Instruction::AddVyToVx { vx, .. }
+            | Instruction::SubtractVyFromVx { vx, .. }
+            | Instruction::ShiftVyRightStoreInVx { vx, .. }
+            | Instruction::SetVxToVyMinusVx { vx, .. }
+            | Instruction::ShiftVyLeftStoreInVx { vx, .. } => {
+                self.set_register(vx, None);
+                self.set_register(DataRegister::VF, None);
+            }
+            Instruction::SetVxToRandomWithMask { vx, .. }
+            | Instruction::StoreDelayTimerInVx { vx }
+            | Instruction::WaitForKeypressStoreInVx { vx } => self.set_register(vx, None),
+            Instruction::DrawSpriteAtVxVy { .. } => self.set_register(DataRegister::VF, None),
+            Instruction::StoreAddressInAddressRegister { address } => {
+                self.address_register = Some(address)
+            }
+            Instruction::AddVxToAddressRegister { .. }
+            | Instruction::SetAddressRegisterToSpriteAddressOfSpriteInVx { .. }
+            | Instruction::StoreRegistersInMemory { .. } => self.address_register = None,
+            Instruction::FillRegistersFromMemory { vx } => {
+                for register in 0..=u8::from(vx) as usize {
+                    self.registers[register] = None;
+                }
+                self.address_register = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Whether I is read by a later instruction of the block before it is loaded again
+fn address_register_used_after(block: &BasicBlock, index: usize) -> bool {
+    for instruction in block.instructions[index + 1..]
+        .iter()
+        .filter_map(|decoded| decoded.instruction)
+    {
+        match instruction {
+            Instruction::StoreAddressInAddressRegister { .. }
+            | Instruction::SetAddressRegisterToSpriteAddressOfSpriteInVx { .. } => return false,
+            Instruction::DrawSpriteAtVxVy { .. }
+            | Instruction::AddVxToAddressRegister { .. }
+            | Instruction::StoreBCDOfVx { .. }
+            | Instruction::StoreRegistersInMemory { .. }
+            | Instruction::FillRegistersFromMemory { .. } => return true,
+            _ => {}
+        }
+    }
+
+    // The following blocks are not followed, so the value may still be used there
+    true
+}
+
+pub struct Linter {
+    platform: Platform,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter {
+            platform: Platform::Chip8,
+        }
+    }
+}
+
+impl Linter {
+    pub fn new(platform: Platform) -> Self {
+        Linter { platform }
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn lint_program(&self, program: &[u8]) -> Vec<Lint> {
+        let mut memory = vec![0; DEFAULT_PROGRAM_ADDRESS];
+        memory.extend_from_slice(program);
+
+        self.lint(&memory, DEFAULT_PROGRAM_ADDRESS..memory.len())
+    }
+
+    pub fn lint(&self, data: &[u8], range: Range<usize>) -> Vec<Lint> {
+        let disassembly = RecursiveDisassembly::new(data, range);
+        let graph = ControlFlowGraph::from_disassembly(&disassembly);
+        let mut lints = Vec::new();
+
+        for block in graph.blocks() {
+            self.lint_block(block, &mut lints);
+        }
+
+        self.lint_call_graph(&graph, &mut lints);
+
+        for finding in graph.findings() {
+            // Undecodable instructions are often opcodes of a newer platform
+            if let Finding::InvalidInstruction { address } = finding {
+                if let Some(bytes) = data.get(*address..*address + 2) {
+                    let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+                    let platform = Platform::introducing(opcode);
+                    if platform.is_some_and(|platform| platform <= self.platform) {
+                        // Supported, but this emulator can not follow it
+                        lints.push(Lint {
+                            address: *address,
+                            severity: Severity::Info,
+                            kind: LintKind::ControlFlow(finding.clone()),
+                        });
+                    } else {
+                        lints.push(Lint {
+                            address: *address,
+                            severity: Severity::Error,
+                            kind: LintKind::UnsupportedOpcode { opcode, platform },
+                        });
+                    }
+                    continue;
+                }
+            }
+
+            lints.push(Lint {
+                address: finding.address(),
+                severity: finding.severity(),
+                kind: LintKind::ControlFlow(finding.clone()),
+            });
+        }
+
+        lints.sort_by_key(|lint| lint.address);
+        lints
+    }
+
+    fn lint_block(&self, block: &BasicBlock, lints: &mut Vec<Lint>) {
+        let mut known = KnownValues::default();
+
+        for (index, decoded) in block.instructions.iter().enumerate() {
+            let Some(instruction) = decoded.instruction else {
+                continue;
+            };
+            let mut lint = |severity, kind| {
+                lints.push(Lint {
+                    address: decoded.address,
+                    severity,
+                    kind,
+                })
+            };
+
+            if !self.platform.supports(decoded.opcode) {
+                lint(
+                    Severity::Error,
+                    LintKind::UnsupportedOpcode {
+                        opcode: decoded.opcode,
+                        platform: Platform::introducing(decoded.opcode),
+                    },
+                );
+            }
+
+            match instruction {
+                Instruction::ShiftVyRightStoreInVx { vx, vy }
+                | Instruction::ShiftVyLeftStoreInVx { vx, vy }
+                    if vx != vy =>
+                {
+                    lint(Severity::Warning, LintKind::ShiftQuirk);
+                }
+                Instruction::StoreRegistersInMemory { .. }
+                | Instruction::FillRegistersFromMemory { .. }
+                    if address_register_used_after(block, index) =>
+                {
+                    lint(Severity::Warning, LintKind::MemoryIncrementQuirk);
+                }
+                // BXNN adds VX instead of V0 with the quirk, so B0NN behaves the same
+                Instruction::JumpToAddressPlusV0 { address } if address & 0xF00 != 0 => {
+                    lint(Severity::Warning, LintKind::JumpQuirk);
+                }
+                Instruction::DrawSpriteAtVxVy { vx, vy, byte_count } => {
+                    let x = known.register(vx);
+                    let y = known.register(vy);
+                    let crosses_x =
+                        x.is_some_and(|x| x as usize % SCREEN_WIDTH + SPRITE_WIDTH > SCREEN_WIDTH);
+                    let crosses_y = y.is_some_and(|y| {
+                        y as usize % SCREEN_HEIGHT + byte_count as usize > SCREEN_HEIGHT
+                    });
+
+                    if crosses_x || crosses_y {
+                        lint(Severity::Warning, LintKind::SpriteAtScreenEdge { x, y });
+                    }
+                }
+                Instruction::JumpToAddress { address }
+                | Instruction::ExecuteSubroutine { address }
+                | Instruction::JumpToAddressPlusV0 { address }
+                    if !address.is_multiple_of(2) =>
+                {
+                    lint(
+                        Severity::Warning,
+                        LintKind::OddJumpTarget { target: address },
+                    );
+                }
+                // Newer platforms reuse parts of the 0NNN range for their own opcodes
+                Instruction::ExecuteMachineLanguageSubroutine { address }
+                    if Platform::introducing(decoded.opcode) == Some(Platform::Chip8) =>
+                {
+                    lint(
+                        Severity::Warning,
+                        LintKind::MachineCodeCall { target: address },
+                    );
+                }
+                _ => {}
+            }
+
+            let writes_memory = matches!(
+                instruction,
+                Instruction::StoreBCDOfVx { .. } | Instruction::StoreRegistersInMemory { .. }
+            );
+            if let (true, Some(target)) = (writes_memory, known.address_register) {
+                if target < UNPROTECTED_MEMORY_START {
+                    lint(Severity::Error, LintKind::ProtectedMemoryWrite { target });
+                }
+            }
+
+            known.update(&instruction);
+        }
+    }
+
+    fn lint_call_graph(&self, graph: &ControlFlowGraph, lints: &mut Vec<Lint>) {
+        let callees: BTreeMap<usize, &BTreeSet<usize>> = graph
+            .subroutines()
+            .map(|subroutine| (subroutine.entry, &subroutine.callees))
+            .collect();
+
+        // Longest call chain from the entry point, recursive calls are reported instead
+        let mut depths = BTreeMap::new();
+        let mut recursive = BTreeSet::new();
+        let mut pending = vec![(graph.entry(), 0, vec![graph.entry()])];
+
+        while let Some((entry, depth, path)) = pending.pop() {
+            if depths.get(&entry).is_some_and(|known| *known >= depth) {
+                continue;
+            }
+            depths.insert(entry, depth);
+
+            for callee in callees
+                .get(&entry)
+                .into_iter()
+                .flat_map(|callees| callees.iter())
+            {
+                if path.contains(callee) {
+                    recursive.insert(*callee);
+                    continue;
+                }
+
+                let mut path = path.clone();
+                path.push(*callee);
+                pending.push((*callee, depth + 1, path));
+            }
+        }
+
+        for entry in recursive {
+            lints.push(Lint {
+                address: entry,
+                severity: Severity::Warning,
+                kind: LintKind::Recursion,
+            });
+        }
+
+        let limit = self.platform.stack_size();
+        if let Some((entry, depth)) = depths.iter().max_by_key(|(_, depth)| **depth) {
+            if *depth > limit {
+                lints.push(Lint {
+                    address: *entry,
+                    severity: Severity::Error,
+                    kind: LintKind::StackOverflow {
+                        depth: *depth,
+                        limit,
+                    },
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::constants::DEFAULT_PROGRAM_ADDRESS;
+use super::graphic;
+use super::instruction::Instruction;
+use super::memory::WriteError;
+use super::platform::Platform;
+use super::quirks::QuirksProfile;
+use super::Chip8;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum IntelHexError {
+    #[error("record does not start with ':'")]
+    MissingStartCode,
+    #[error("record contains a character that is not a hex digit")]
+    InvalidDigit,
+    #[error("record length does not match its byte count")]
+    InvalidLength,
+    #[error("checksum is {found:#04x} but should be {expected:#04x}")]
+    ChecksumMismatch { expected: u8, found: u8 },
+    #[error("unsupported record type {0:#04x}")]
+    UnsupportedRecordType(u8),
+    #[error("record follows the end of file record")]
+    DataAfterEndOfFile,
+}
+
+#[derive(Error, Debug)]
+pub enum LoadError {
+    #[error("program file could not be read")]
+    Io(#[from] io::Error),
+    #[error("program is empty")]
+    Empty,
+    #[error("intel hex line {line}: {source}")]
+    IntelHex { line: usize, source: IntelHexError },
+    #[error("intel hex file has no end of file record")]
+    MissingEndOfFile,
+    #[error("hex text line {line}: '{token}' is not a sequence of hex bytes")]
+    InvalidHexText { line: usize, token: String },
+    #[error("invalid base64 character '{character}' at offset {offset}")]
+    InvalidBase64Character { character: char, offset: usize },
+    #[error("base64 input has an invalid length")]
+    InvalidBase64Length,
+    #[error("segments overlap at {0:#05x}")]
+    OverlappingSegments(usize),
+    #[error("segment at {0:#05x} lies before the program address")]
+    SegmentBeforeProgram(usize),
+    #[error("segment could not be loaded")]
+    Write(#[from] WriteError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProgramFormat {
+    Binary,
+    IntelHex,
+    HexText,
+    Base64,
+}
+
+impl ProgramFormat {
+    pub fn from_extension(extension: &str) -> Option<ProgramFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ch8" | "c8" | "sc8" | "xo8" | "bin" | "rom" => Some(ProgramFormat::Binary),
+            "hex" | "ihx" | "ihex" => Some(ProgramFormat::IntelHex),
+            "b64" | "base64" => Some(ProgramFormat::Base64),
+            _ => None,
+        }
+    }
+
+    // Guesses the format of data without a telling extension
+    pub fn detect(data: &[u8]) -> ProgramFormat {
+        let Ok(text) = std::str::from_utf8(data) else {
+            return ProgramFormat::Binary;
+        };
+        if text.trim().is_empty() || text.contains('\0') {
+            return ProgramFormat::Binary;
+        }
+
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.all(|line| line.starts_with(':')) {
+            return ProgramFormat::IntelHex;
+        }
+
+        // Base64 has no separators inside a line, so separated tokens are a broken hex dump
+        let separated = text.lines().any(|line| {
+            line.trim()
+                .contains(|character: char| character.is_whitespace() || character == ',')
+        });
+
+        if separated {
+            return match parse_hex_text(text) {
+                Ok(_) => ProgramFormat::HexText,
+                Err(_) => ProgramFormat::Binary,
+            };
+        }
+
+        match (parse_hex_text(text), decode_base64(text)) {
+            (Ok(segments), Ok(bytes)) => match looks_like_base64(text, &segments, &bytes) {
+                true => ProgramFormat::Base64,
+                false => ProgramFormat::HexText,
+            },
+            (Ok(_), Err(_)) => ProgramFormat::HexText,
+            (Err(_), Ok(_)) => ProgramFormat::Base64,
+            (Err(_), Err(_)) => ProgramFormat::Binary,
+        }
+    }
+}
+
+// Decides for text made only of hex digits, which decodes either way. Hex dumps are written
+// in a single case and decode into instructions, base64 mixes cases and is less likely to.
+fn looks_like_base64(text: &str, hex: &[Segment], base64: &[u8]) -> bool {
+    let text = text.trim();
+    if text.starts_with("0x") || text.starts_with("0X") {
+        return false;
+    }
+
+    let lowercase = text.contains(|character: char| character.is_ascii_lowercase());
+    let uppercase = text.contains(|character: char| character.is_ascii_uppercase());
+    if lowercase && uppercase {
+        return true;
+    }
+
+    let hex: Vec<u8> = hex
+        .iter()
+        .flat_map(|segment| segment.data.iter().copied())
+        .collect();
+
+    instruction_ratio(base64) > instruction_ratio(&hex)
+}
+
+fn instruction_ratio(data: &[u8]) -> f64 {
+    let words = data.chunks_exact(2);
+    let total = words.len().max(1);
+    let valid = words
+        .filter(|word| Instruction::try_from(&[word[0], word[1]]).is_ok())
+        .count();
+
+    valid as f64 / total as f64
+}
+
+impl Display for ProgramFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramFormat::Binary => write!(f, "binary"),
+            ProgramFormat::IntelHex => write!(f, "Intel HEX"),
+            ProgramFormat::HexText => write!(f, "hex text"),
+            ProgramFormat::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub address: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedProgram {
+    pub format: ProgramFormat,
+    // Only known when the file extension names a platform
+    pub platform: Option<Platform>,
+    pub start_address: usize,
+    pub segments: Vec<Segment>,
+}
+
+impl LoadedProgram {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+
+        Self::parse(
+            &data,
+            path.extension().and_then(|extension| extension.to_str()),
+        )
+    }
+
+    pub fn parse(data: &[u8], extension: Option<&str>) -> Result<Self, LoadError> {
+        let format = extension
+            .and_then(ProgramFormat::from_extension)
+            .unwrap_or_else(|| ProgramFormat::detect(data));
+
+        let mut program = Self::parse_as(data, format)?;
+        program.platform = extension.and_then(Platform::from_extension);
+
+        Ok(program)
+    }
+
+    pub fn parse_as(data: &[u8], format: ProgramFormat) -> Result<Self, LoadError> {
+        let text = || String::from_utf8_lossy(data);
+
+        let (segments, start_address) = match format {
+            ProgramFormat::Binary => (
+                vec![Segment {
+                    address: DEFAULT_PROGRAM_ADDRESS,
+                    data: data.to_vec(),
+                }],
+                None,
+            ),
+            ProgramFormat::IntelHex => parse_intel_hex(&text())?,
+            ProgramFormat::HexText => (parse_hex_text(&text())?, None),
+            ProgramFormat::Base64 => (
+                vec![Segment {
+                    address: DEFAULT_PROGRAM_ADDRESS,
+                    data: decode_base64(&text())?,
+                }],
+                None,
+            ),
+        };
+
+        let mut segments: Vec<Segment> = segments
+            .into_iter()
+            .filter(|segment| !segment.data.is_empty())
+            .collect();
+        if segments.is_empty() {
+            return Err(LoadError::Empty);
+        }
+
+        segments.sort_by_key(|segment| segment.address);
+        for pair in segments.windows(2) {
+            if pair[0].address + pair[0].data.len() > pair[1].address {
+                return Err(LoadError::OverlappingSegments(pair[1].address));
+            }
+        }
+
+        Ok(LoadedProgram {
+            format,
+            platform: None,
+            start_address: start_address.unwrap_or(segments[0].address),
+            segments,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|segment| segment.data.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The program as one block starting at the default program address, gaps are zero filled
+    pub fn image(&self) -> Result<Vec<u8>, LoadError> {
+        let mut image = Vec::new();
+
+        for segment in &self.segments {
+            let offset = segment
+                .address
+                .checked_sub(DEFAULT_PROGRAM_ADDRESS)
+                .ok_or(LoadError::SegmentBeforeProgram(segment.address))?;
+            if image.len() < offset + segment.data.len() {
+                image.resize(offset + segment.data.len(), 0);
+            }
+            image[offset..offset + segment.data.len()].copy_from_slice(&segment.data);
+        }
+
+        Ok(image)
+    }
+}
+
+impl<D: graphic::Display> Chip8<D> {
+    // Also switches to the quirks of the platform the file extension names
+    pub fn load(&mut self, program: &LoadedProgram) -> Result<(), WriteError> {
+        self.reset();
+        self.load_font_sprites();
+
+        if let Some(platform) = program.platform {
+            self.quirks = QuirksProfile::for_platform(platform).quirks();
+        }
+
+        for segment in &program.segments {
+            self.memory
+                .write_restricted(&segment.data, segment.address)?;
+        }
+        self.program_counter = program.start_address;
+
+        Ok(())
+    }
+}
+
+fn parse_hex_byte(digits: &[u8]) -> Option<u8> {
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+fn parse_intel_hex(text: &str) -> Result<(Vec<Segment>, Option<usize>), LoadError> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut start_address = None;
+    let mut base_address = 0;
+    let mut end_of_file = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |source| LoadError::IntelHex {
+            line: index + 1,
+            source,
+        };
+
+        if end_of_file {
+            return Err(error(IntelHexError::DataAfterEndOfFile));
+        }
+
+        let digits = line
+            .strip_prefix(':')
+            .ok_or(error(IntelHexError::MissingStartCode))?
+            .as_bytes();
+        if digits.len() % 2 != 0 {
+            return Err(error(IntelHexError::InvalidLength));
+        }
+        let bytes = digits
+            .chunks_exact(2)
+            .map(parse_hex_byte)
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(error(IntelHexError::InvalidDigit))?;
+
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(error(IntelHexError::InvalidLength));
+        }
+
+        let (record, checksum) = bytes.split_at(bytes.len() - 1);
+        let expected = record
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+            .wrapping_neg();
+        if checksum[0] != expected {
+            return Err(error(IntelHexError::ChecksumMismatch {
+                expected,
+                found: checksum[0],
+            }));
+        }
+
+        let offset = u16::from_be_bytes([record[1], record[2]]) as usize;
+        let data = &record[4..];
+        let value = || {
+            data.iter()
+                .fold(0, |value, byte| value << 8 | *byte as usize)
+        };
+
+        match record[3] {
+            0x00 => {
+                let address = base_address + offset;
+                match segments.last_mut() {
+                    Some(segment) if segment.address + segment.data.len() == address => {
+                        segment.data.extend_from_slice(data)
+                    }
+                    _ => segments.push(Segment {
+                        address,
+                        data: data.to_vec(),
+                    }),
+                }
+            }
+            0x01 => end_of_file = true,
+            0x02 if data.len() == 2 => base_address = value() << 4,
+            0x04 if data.len() == 2 => base_address = value() << 16,
+            // Segmented start addresses are CS:IP, linear ones are a plain address
+            0x03 if data.len() == 4 => {
+                start_address = Some((value() >> 16 << 4) + (value() & 0xFFFF))
+            }
+            0x05 if data.len() == 4 => start_address = Some(value()),
+            0x02..=0x05 => return Err(error(IntelHexError::InvalidLength)),
+            record_type => return Err(error(IntelHexError::UnsupportedRecordType(record_type))),
+        }
+    }
+
+    if !end_of_file {
+        return Err(LoadError::MissingEndOfFile);
+    }
+
+    Ok((segments, start_address))
+}
+
+// Whitespace or comma separated bytes or words with an optional 0x prefix, a token
+// ending in ':' starts a new segment at that address and '#' starts a comment
+fn parse_hex_text(text: &str) -> Result<Vec<Segment>, LoadError> {
+    let mut segments = vec![Segment {
+        address: DEFAULT_PROGRAM_ADDRESS,
+        data: Vec::new(),
+    }];
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+
+        for token in line
+            .split(|character: char| character.is_whitespace() || character == ',')
+            .filter(|token| !token.is_empty())
+        {
+            let error = || LoadError::InvalidHexText {
+                line: index + 1,
+                token: token.to_string(),
+            };
+
+            if let Some(address) = token.strip_suffix(':') {
+                let address = address.trim_start_matches("0x").trim_start_matches("0X");
+                segments.push(Segment {
+                    address: usize::from_str_radix(address, 16).map_err(|_| error())?,
+                    data: Vec::new(),
+                });
+                continue;
+            }
+
+            let digits = token
+                .strip_prefix("0x")
+                .or(token.strip_prefix("0X"))
+                .unwrap_or(token)
+                .as_bytes();
+            if digits.is_empty() || digits.len() % 2 != 0 {
+                return Err(error());
+            }
+
+            let bytes = digits
+                .chunks_exact(2)
+                .map(parse_hex_byte)
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(error)?;
+            segments
+                .last_mut()
+                .expect("there is always a segment")
+                .data
+                .extend(bytes);
+        }
+    }
+
+    Ok(segments)
+}
+
+fn base64_value(character: u8) -> Option<u8> {
+    match character {
+        b'A'..=b'Z' => Some(character - b'A'),
+        b'a'..=b'z' => Some(character - b'a' + 26),
+        b'0'..=b'9' => Some(character - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+// Accepts the standard and the URL safe alphabet, padding is optional
+fn decode_base64(text: &str) -> Result<Vec<u8>, LoadError> {
+    let mut values = Vec::new();
+    let mut padding = 0;
+
+    for (offset, character) in text.char_indices() {
+        if character.is_whitespace() {
+            continue;
+        }
+        if character == '=' {
+            padding += 1;
+            continue;
+        }
+
+        let value = u8::try_from(character)
+            .ok()
+            .and_then(base64_value)
+            .filter(|_| padding == 0)
+            .ok_or(LoadError::InvalidBase64Character { character, offset })?;
+        values.push(value);
+    }
+
+    if values.len() % 4 == 1 || padding > 2 {
+        return Err(LoadError::InvalidBase64Length);
+    }
+
+    let mut bytes = Vec::with_capacity(values.len() * 3 / 4);
+    for chunk in values.chunks(4) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, value)| {
+            bits | (*value as u32) << (18 - index * 6)
+        });
+        let count = chunk.len() * 6 / 8;
+        bytes.extend_from_slice(&bits.to_be_bytes()[1..1 + count]);
+    }
+
+    Ok(bytes)
+}
@@ -75,7 +75,7 @@ impl Memory {
         self.write_unrestricted(data, write_address)
     }
 
-    pub fn read_instruction(&self, address: usize) -> Result<Instruction, ReadInstructionError> {
+    pub fn read_opcode(&self, address: usize) -> Result<u16, ReadInstructionError> {
         if address < UNPROTECTED_MEMORY_START {
             return Err(ReadInstructionError::AddressInProtectedMemoryArea(address));
         }
@@ -87,10 +87,16 @@ impl Memory {
             .try_into()
             .unwrap();
 
-        Ok(Instruction::try_from(instruction_slice)?)
+        Ok(u16::from_be_bytes(*instruction_slice))
+    }
+
+    pub fn read_instruction(&self, address: usize) -> Result<Instruction, ReadInstructionError> {
+        let opcode = self.read_opcode(address)?;
+
+        Ok(Instruction::try_from(&opcode.to_be_bytes())?)
     }
 
-    pub fn read_sprite(&self, address: usize, byte_count: usize) -> Option<BitSlicePixelView> {
+    pub fn read_sprite(&self, address: usize, byte_count: usize) -> Option<BitSlicePixelView<'_>> {
         let slice = self.raw_data.get(address..address + byte_count)?;
 
         Some(BitSlicePixelView::new_from_byte_slice(slice, 8, byte_count))
@@ -1,24 +1,71 @@
-use self::constants::{DEFAULT_PROGRAM_ADDRESS, FONT_SPRITES, FONT_SPRITE_SIZE, STACK_SIZE};
+use std::collections::{HashMap, VecDeque};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use self::constants::{
+    DEFAULT_INSTRUCTIONS_PER_FRAME, DEFAULT_PROGRAM_ADDRESS, FONT_SPRITES, FONT_SPRITE_SIZE,
+    STACK_SIZE,
+};
+use self::coverage::Coverage;
 use self::data_register::{DataRegister, DataRegisters};
-use self::graphic::Screen;
-use self::keyboard::{Key, Keyboard};
+use self::events::EventSink;
+use self::extension::{ErasedExtension, UnknownOpcodePolicy};
+use self::graphic::{Display, Screen};
+use self::hypercall::Hypercall;
+use self::input::InputEvent;
+use self::keyboard::{Key, KeyState, KeyWait, Keyboard};
 use self::memory::{Memory, WriteError};
+use self::profiler::Profiler;
+use self::quirks::Quirks;
+use self::timing::TimingModel;
 
+#[cfg(feature = "cartridge")]
+pub mod cartridge;
+pub mod cfg;
 pub mod constants;
+pub mod cosmac_vip;
+pub mod coverage;
 pub mod cpu;
+#[cfg(feature = "dap")]
+pub mod dap;
 pub mod data_register;
+#[cfg(feature = "database")]
+pub mod database;
+pub mod debugger;
+pub mod detector;
+pub mod disassembler;
+pub mod events;
+pub mod expression;
+pub mod extension;
+pub mod gdb;
 pub mod graphic;
+pub mod hypercall;
+pub mod input;
 pub mod instruction;
 pub mod keyboard;
+pub mod keymap;
+pub mod lint;
+pub mod loader;
 pub mod memory;
+pub mod patch;
+pub mod platform;
+pub mod profiler;
+pub mod quirks;
+pub mod sensitivity;
+pub mod state;
+pub mod symbols;
+pub mod timing;
 
 #[derive(PartialEq)]
 enum Blocked {
     No,
     WaitingOnKeyUp(DataRegister),
+    WaitingForKeyPress(DataRegister),
+    WaitingForKeyRelease(DataRegister, Key),
 }
 
-pub struct Chip8 {
+pub struct Chip8<D: Display = Screen> {
     pub memory: Memory,
 
     pub data_registers: DataRegisters,
@@ -29,13 +76,45 @@ pub struct Chip8 {
 
     keyboard: Keyboard,
     pub stack: Vec<usize>,
-    pub screen: Screen,
+    pub screen: D,
     in_jump: bool,
     blocked: Blocked,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    events: Option<EventSink>,
+    hypercalls: HashMap<usize, Hypercall<D>>,
+    extensions: Vec<Box<dyn ErasedExtension<D>>>,
+    unknown_opcodes: UnknownOpcodePolicy<D>,
+
+    pub quirks: Quirks,
+    pub instructions_per_frame: usize,
+    pub key_wait: KeyWait,
+    pub timing: TimingModel,
+    waiting_for_vblank: bool,
+    // What StdRng is built on, used directly so that saved states can capture its position
+    rng: ChaCha12Rng,
+    frames: u64,
+    input: VecDeque<InputEvent>,
+    // Instructions, or machine cycles with the VIP timing, of the current frame that are used up.
+    // A VIP frame starts with the cycles the last instruction of the previous one ran into it.
+    frame_position: u32,
 }
 
-impl Default for Chip8 {
+impl<D: Display + Default> Default for Chip8<D> {
     fn default() -> Self {
+        Self::with_display(D::default())
+    }
+}
+
+impl Chip8 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<D: Display> Chip8<D> {
+    // Runs the emulator with a display provided by the frontend
+    pub fn with_display(display: D) -> Self {
         Chip8 {
             data_registers: DataRegisters::new(),
             address_register: 0,
@@ -47,15 +126,24 @@ impl Default for Chip8 {
             stack: Vec::with_capacity(STACK_SIZE),
             program_counter: DEFAULT_PROGRAM_ADDRESS,
             memory: Memory::new(),
-            screen: Screen::new(),
+            screen: display,
+            profiler: None,
+            coverage: None,
+            events: None,
+            hypercalls: HashMap::new(),
+            extensions: Vec::new(),
+            unknown_opcodes: UnknownOpcodePolicy::default(),
+            quirks: Quirks::default(),
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            key_wait: KeyWait::default(),
+            timing: TimingModel::default(),
+            waiting_for_vblank: false,
+            rng: ChaCha12Rng::from_entropy(),
+            frames: 0,
+            input: VecDeque::new(),
+            frame_position: 0,
         }
     }
-}
-
-impl Chip8 {
-    pub fn new() -> Self {
-        Self::default()
-    }
 
     pub fn reset(&mut self) {
         self.data_registers.reset();
@@ -68,6 +156,19 @@ impl Chip8 {
         self.memory.clear();
         self.screen.clear();
         self.blocked = Blocked::No;
+        self.waiting_for_vblank = false;
+        self.frames = 0;
+        self.input.clear();
+        self.frame_position = 0;
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.clear_call_stack();
+        }
+    }
+
+    // Makes CXNN produce the same sequence of numbers on every run
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
     }
 
     pub fn key_up(&mut self, key: Key) {
@@ -76,7 +177,12 @@ impl Chip8 {
     }
 
     pub fn key_down(&mut self, key: Key) {
+        let fresh_press = matches!(self.keyboard.get_key_state(key), KeyState::Released);
         self.keyboard.key_down(key);
+
+        if fresh_press {
+            self.handle_key_down_interrupt(key);
+        }
     }
 
     fn load_font_sprites(&mut self) {
@@ -108,4 +214,47 @@ impl Chip8 {
     pub fn is_blocked(&self) -> bool {
         self.blocked != Blocked::No
     }
+
+    // The register FX0A stores the key in, while it waits
+    pub fn awaited_register(&self) -> Option<DataRegister> {
+        match self.blocked {
+            Blocked::No => None,
+            Blocked::WaitingOnKeyUp(vx)
+            | Blocked::WaitingForKeyPress(vx)
+            | Blocked::WaitingForKeyRelease(vx, _) => Some(vx),
+        }
+    }
+
+    // The buzzer sounds while the sound timer runs and while FX0A holds a key
+    pub fn is_sound_playing(&self) -> bool {
+        self.sound_timer > 0 || matches!(self.blocked, Blocked::WaitingForKeyRelease(..))
+    }
+
+    pub fn enable_profiler(&mut self) {
+        if self.profiler.is_none() {
+            self.profiler = Some(Profiler::new());
+        }
+    }
+
+    pub fn disable_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn enable_coverage(&mut self) {
+        if self.coverage.is_none() {
+            self.coverage = Some(Coverage::new());
+        }
+    }
+
+    pub fn disable_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
 }
@@ -0,0 +1,415 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+const IPS_MAGIC: &[u8; 5] = b"PATCH";
+const IPS_EOF: &[u8; 3] = b"EOF";
+const IPS_MAX_OFFSET: usize = 0xFFFFFF;
+const IPS_MAX_RECORD_SIZE: usize = 0xFFFF;
+
+const BPS_MAGIC: &[u8; 4] = b"BPS1";
+const BPS_FOOTER_SIZE: usize = 12;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PatchError {
+    #[error("data is neither an IPS nor a BPS patch")]
+    UnknownFormat,
+    #[error("patch is truncated")]
+    Truncated,
+    #[error("patch writes outside of the patched data at offset {0:#x}")]
+    OutOfRange(usize),
+    #[error("source is {found} bytes but the patch expects {expected}")]
+    SourceSizeMismatch { expected: usize, found: usize },
+    #[error("source checksum is {found:08x} but the patch expects {expected:08x}")]
+    SourceChecksumMismatch { expected: u32, found: u32 },
+    #[error("patched checksum is {found:08x} but the patch expects {expected:08x}")]
+    TargetChecksumMismatch { expected: u32, found: u32 },
+    #[error("patch checksum is {found:08x} but should be {expected:08x}")]
+    PatchChecksumMismatch { expected: u32, found: u32 },
+    #[error("data is too large for an IPS patch")]
+    TooLargeForIps,
+}
+
+#[derive(Error, Debug)]
+#[error("unknown patch format '{0}'")]
+pub struct UnknownPatchFormatError(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+}
+
+impl PatchFormat {
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        if patch.starts_with(IPS_MAGIC) {
+            Some(PatchFormat::Ips)
+        } else if patch.starts_with(BPS_MAGIC) {
+            Some(PatchFormat::Bps)
+        } else {
+            None
+        }
+    }
+
+    pub fn apply(&self, source: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+        match self {
+            PatchFormat::Ips => apply_ips(source, patch),
+            PatchFormat::Bps => apply_bps(source, patch),
+        }
+    }
+
+    pub fn create(&self, source: &[u8], target: &[u8]) -> Result<Vec<u8>, PatchError> {
+        match self {
+            PatchFormat::Ips => create_ips(source, target),
+            PatchFormat::Bps => Ok(create_bps(source, target)),
+        }
+    }
+}
+
+impl Display for PatchFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchFormat::Ips => write!(f, "IPS"),
+            PatchFormat::Bps => write!(f, "BPS"),
+        }
+    }
+}
+
+impl FromStr for PatchFormat {
+    type Err = UnknownPatchFormatError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "ips" => Ok(PatchFormat::Ips),
+            "bps" => Ok(PatchFormat::Bps),
+            _ => Err(UnknownPatchFormatError(name.to_string())),
+        }
+    }
+}
+
+// Applies an IPS or BPS patch depending on its header
+pub fn apply_patch(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    PatchFormat::detect(patch)
+        .ok_or(PatchError::UnknownFormat)?
+        .apply(source, patch)
+}
+
+// The CRC-32 used by BPS, PNG and zip
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| match crc & 1 {
+            1 => crc >> 1 ^ 0xEDB88320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], PatchError> {
+        let end = self
+            .offset
+            .checked_add(count)
+            .ok_or(PatchError::Truncated)?;
+        let bytes = self
+            .data
+            .get(self.offset..end)
+            .ok_or(PatchError::Truncated)?;
+        self.offset += count;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn big_endian(&mut self, count: usize) -> Result<usize, PatchError> {
+        Ok(self
+            .bytes(count)?
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as usize))
+    }
+
+    // BPS numbers store one less in every continuation byte, so each value has one encoding
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut value = 0usize;
+        let mut shift = 1usize;
+
+        loop {
+            let byte = self.u8()?;
+            value = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|part| value.checked_add(part))
+                .ok_or(PatchError::Truncated)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::Truncated)?;
+            value = value.checked_add(shift).ok_or(PatchError::Truncated)?;
+        }
+    }
+
+    fn signed_varint(&mut self) -> Result<isize, PatchError> {
+        let value = self.varint()?;
+        let magnitude = (value >> 1) as isize;
+
+        Ok(match value & 1 {
+            1 => -magnitude,
+            _ => magnitude,
+        })
+    }
+}
+
+pub fn apply_ips(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = PatchReader {
+        data: patch,
+        offset: 0,
+    };
+    if reader.bytes(IPS_MAGIC.len())? != IPS_MAGIC {
+        return Err(PatchError::UnknownFormat);
+    }
+
+    let mut target = source.to_vec();
+
+    loop {
+        let offset_bytes = reader.bytes(3)?;
+        if offset_bytes == IPS_EOF {
+            break;
+        }
+        let offset = offset_bytes
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as usize);
+
+        let (data, length) = match reader.big_endian(2)? {
+            0 => {
+                let length = reader.big_endian(2)?;
+                (None, length)
+            }
+            length => (Some(reader.bytes(length)?), length),
+        };
+
+        if target.len() < offset + length {
+            target.resize(offset + length, 0);
+        }
+        match data {
+            Some(data) => target[offset..offset + length].copy_from_slice(data),
+            None => target[offset..offset + length].fill(reader.u8()?),
+        }
+    }
+
+    // Some patchers append the size to truncate the result to
+    if reader.data.len() - reader.offset >= 3 {
+        target.truncate(reader.big_endian(3)?);
+    }
+
+    Ok(target)
+}
+
+pub fn create_ips(source: &[u8], target: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if target.len() > IPS_MAX_OFFSET {
+        return Err(PatchError::TooLargeForIps);
+    }
+
+    let mut patch = IPS_MAGIC.to_vec();
+    let differs = |index: usize| source.get(index) != target.get(index);
+
+    let mut index = 0;
+    while index < target.len() {
+        if !differs(index) {
+            index += 1;
+            continue;
+        }
+
+        // An offset spelling EOF would end the patch early, so start the record a byte sooner
+        let start = match &(index as u32).to_be_bytes()[1..] == IPS_EOF {
+            true => index - 1,
+            false => index,
+        };
+
+        let mut end = index;
+        while end < target.len() && end - start < IPS_MAX_RECORD_SIZE && differs(end) {
+            end += 1;
+        }
+
+        patch.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+        patch.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&target[start..end]);
+        index = end;
+    }
+
+    patch.extend_from_slice(IPS_EOF);
+    if target.len() < source.len() {
+        patch.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+
+    Ok(patch)
+}
+
+pub fn apply_bps(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(PatchError::Truncated);
+    }
+
+    let (body, footer) = patch.split_at(patch.len() - BPS_FOOTER_SIZE);
+    let checksum =
+        |index: usize| u32::from_le_bytes(footer[index * 4..index * 4 + 4].try_into().unwrap());
+    let (source_checksum, target_checksum, patch_checksum) =
+        (checksum(0), checksum(1), checksum(2));
+
+    let found = crc32(&patch[..patch.len() - 4]);
+    if found != patch_checksum {
+        return Err(PatchError::PatchChecksumMismatch {
+            expected: patch_checksum,
+            found,
+        });
+    }
+
+    let mut reader = PatchReader {
+        data: body,
+        offset: 0,
+    };
+    if reader.bytes(BPS_MAGIC.len())? != BPS_MAGIC {
+        return Err(PatchError::UnknownFormat);
+    }
+
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+
+    if source.len() != source_size {
+        return Err(PatchError::SourceSizeMismatch {
+            expected: source_size,
+            found: source.len(),
+        });
+    }
+    let found = crc32(source);
+    if found != source_checksum {
+        return Err(PatchError::SourceChecksumMismatch {
+            expected: source_checksum,
+            found,
+        });
+    }
+
+    // The size comes from the patch, so it only serves as a hint up to what the patch could
+    // plausibly produce without target copies
+    let mut target = Vec::with_capacity(target_size.min(source.len().saturating_add(body.len())));
+    let mut source_offset = 0isize;
+    let mut target_offset = 0isize;
+
+    while reader.offset < body.len() {
+        let action = reader.varint()?;
+        let length = (action >> 2) + 1;
+        let output_offset = target.len();
+        let out_of_range = || PatchError::OutOfRange(output_offset);
+        if output_offset
+            .checked_add(length)
+            .is_none_or(|end| end > target_size)
+        {
+            return Err(out_of_range());
+        }
+        let advance = |offset: isize, by: isize| offset.checked_add(by).ok_or_else(out_of_range);
+
+        match action & 3 {
+            // Source read
+            0 => target.extend_from_slice(
+                source
+                    .get(output_offset..output_offset + length)
+                    .ok_or_else(out_of_range)?,
+            ),
+            // Target read
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            // Source copy
+            2 => {
+                source_offset = advance(source_offset, reader.signed_varint()?)?;
+                let start = usize::try_from(source_offset).map_err(|_| out_of_range())?;
+                target.extend_from_slice(
+                    start
+                        .checked_add(length)
+                        .and_then(|end| source.get(start..end))
+                        .ok_or_else(out_of_range)?,
+                );
+                source_offset = advance(source_offset, length as isize)?;
+            }
+            // Target copy, may overlap the bytes it produces
+            _ => {
+                target_offset = advance(target_offset, reader.signed_varint()?)?;
+                for _ in 0..length {
+                    let byte = usize::try_from(target_offset)
+                        .ok()
+                        .and_then(|index| target.get(index).copied())
+                        .ok_or_else(out_of_range)?;
+                    target.push(byte);
+                    target_offset = advance(target_offset, 1)?;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(PatchError::Truncated);
+    }
+    let found = crc32(&target);
+    if found != target_checksum {
+        return Err(PatchError::TargetChecksumMismatch {
+            expected: target_checksum,
+            found,
+        });
+    }
+
+    Ok(target)
+}
+
+fn write_varint(patch: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            patch.push(byte | 0x80);
+            return;
+        }
+        patch.push(byte);
+        value -= 1;
+    }
+}
+
+// Uses source reads for unchanged bytes and target reads for everything else, which keeps
+// patches of small ROMs close to the size of their changes
+pub fn create_bps(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_MAGIC.to_vec();
+    write_varint(&mut patch, source.len());
+    write_varint(&mut patch, target.len());
+    write_varint(&mut patch, 0);
+
+    let unchanged = |index: usize| source.get(index) == target.get(index);
+
+    let mut index = 0;
+    while index < target.len() {
+        let kind = unchanged(index);
+        let mut end = index + 1;
+        while end < target.len() && unchanged(end) == kind {
+            end += 1;
+        }
+
+        let length = end - index;
+        match kind {
+            true => write_varint(&mut patch, (length - 1) << 2),
+            false => {
+                write_varint(&mut patch, (length - 1) << 2 | 1);
+                patch.extend_from_slice(&target[index..end]);
+            }
+        }
+        index = end;
+    }
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    patch
+}
@@ -0,0 +1,110 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("unknown platform '{0}'")]
+pub struct UnknownPlatformError(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    // Call depth supported by the reference interpreter of each platform
+    pub fn stack_size(&self) -> usize {
+        match self {
+            Platform::Chip8 => 12,
+            Platform::SuperChip | Platform::XoChip => 16,
+        }
+    }
+
+    // The oldest platform that defines the opcode, None for opcodes no platform defines
+    pub fn introducing(opcode: u16) -> Option<Platform> {
+        let x = (opcode >> 8) & 0xF;
+        let n = opcode & 0xF;
+
+        match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 | 0x00EE => Some(Platform::Chip8),
+                0x00FB..=0x00FF => Some(Platform::SuperChip),
+                _ if opcode & 0xFFF0 == 0x00C0 && n != 0 => Some(Platform::SuperChip),
+                _ if opcode & 0xFFF0 == 0x00D0 => Some(Platform::XoChip),
+                // Machine code routines only exist on the original hardware
+                _ => Some(Platform::Chip8),
+            },
+            0x5000 => match n {
+                0x0 => Some(Platform::Chip8),
+                0x2 | 0x3 => Some(Platform::XoChip),
+                _ => None,
+            },
+            0x8000 => match n {
+                0x0..=0x7 | 0xE => Some(Platform::Chip8),
+                _ => None,
+            },
+            0x9000 => (n == 0).then_some(Platform::Chip8),
+            0xD000 => match n {
+                0 => Some(Platform::SuperChip),
+                _ => Some(Platform::Chip8),
+            },
+            0xE000 => matches!(opcode & 0xFF, 0x9E | 0xA1).then_some(Platform::Chip8),
+            0xF000 => match opcode & 0xFF {
+                0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x33 | 0x55 | 0x65 => {
+                    Some(Platform::Chip8)
+                }
+                0x30 | 0x75 | 0x85 => Some(Platform::SuperChip),
+                0x00 if x == 0 => Some(Platform::XoChip),
+                0x01 | 0x3A => Some(Platform::XoChip),
+                0x02 if x == 0 => Some(Platform::XoChip),
+                _ => None,
+            },
+            _ => Some(Platform::Chip8),
+        }
+    }
+
+    pub fn supports(&self, opcode: u16) -> bool {
+        Platform::introducing(opcode).is_some_and(|platform| platform <= *self)
+    }
+
+    // The platform implied by the conventional ROM file extensions
+    pub fn from_extension(extension: &str) -> Option<Platform> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ch8" | "c8" => Some(Platform::Chip8),
+            "sc8" => Some(Platform::SuperChip),
+            "xo8" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "CHIP-8"),
+            Platform::SuperChip => write!(f, "SUPER-CHIP"),
+            Platform::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = UnknownPlatformError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name
+            .to_ascii_lowercase()
+            .replace(['-', '_', ' '], "")
+            .as_str()
+        {
+            "chip8" => Ok(Platform::Chip8),
+            "superchip" | "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(UnknownPlatformError(name.to_string())),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use super::instruction::Instruction;
+use super::symbols::SymbolTable;
+
+// What opcodes the built-in instructions do not execute are counted as
+pub const CUSTOM_OPCODE_NAME: &str = "CustomOpcode";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubroutineStats {
+    pub address: usize,
+    pub calls: u64,
+    pub inclusive_instructions: u64,
+    pub exclusive_instructions: u64,
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    total_instructions: u64,
+    address_counts: HashMap<usize, u64>,
+    instruction_counts: HashMap<&'static str, u64>,
+    call_counts: HashMap<usize, u64>,
+    call_stack: Vec<usize>,
+    folded_stacks: HashMap<Vec<usize>, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn clear_call_stack(&mut self) {
+        self.call_stack.clear();
+    }
+
+    pub fn record_execution(&mut self, address: usize, instruction: &Instruction) {
+        self.record(address, instruction.name());
+    }
+
+    // Opcodes run by an extension or the unknown opcode policy
+    pub fn record_custom_execution(&mut self, address: usize) {
+        self.record(address, CUSTOM_OPCODE_NAME);
+    }
+
+    fn record(&mut self, address: usize, name: &'static str) {
+        self.total_instructions += 1;
+        *self.address_counts.entry(address).or_default() += 1;
+        *self.instruction_counts.entry(name).or_default() += 1;
+
+        // The executing instruction is attributed to the subroutine it lives in
+        match self.folded_stacks.get_mut(self.call_stack.as_slice()) {
+            Some(count) => *count += 1,
+            None => {
+                self.folded_stacks.insert(self.call_stack.clone(), 1);
+            }
+        }
+    }
+
+    pub fn record_control_flow(&mut self, instruction: &Instruction) {
+        match *instruction {
+            Instruction::ExecuteSubroutine { address } => {
+                *self.call_counts.entry(address).or_default() += 1;
+                self.call_stack.push(address);
+            }
+            Instruction::ReturnFromSubroutine => {
+                self.call_stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn total_instructions(&self) -> u64 {
+        self.total_instructions
+    }
+
+    pub fn address_count(&self, address: usize) -> u64 {
+        self.address_counts.get(&address).copied().unwrap_or(0)
+    }
+
+    pub fn instruction_count(&self, name: &str) -> u64 {
+        self.instruction_counts.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn address_hot_spots(&self) -> Vec<(usize, u64)> {
+        let mut hot_spots: Vec<(usize, u64)> = self
+            .address_counts
+            .iter()
+            .map(|(address, count)| (*address, *count))
+            .collect();
+
+        hot_spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot_spots
+    }
+
+    pub fn instruction_hot_spots(&self) -> Vec<(&'static str, u64)> {
+        let mut hot_spots: Vec<(&'static str, u64)> = self
+            .instruction_counts
+            .iter()
+            .map(|(name, count)| (*name, *count))
+            .collect();
+
+        hot_spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        hot_spots
+    }
+
+    pub fn subroutine_hot_spots(&self) -> Vec<SubroutineStats> {
+        let mut stats: HashMap<usize, SubroutineStats> = HashMap::new();
+
+        for (stack, count) in &self.folded_stacks {
+            if let Some(innermost) = stack.last() {
+                Self::stats_entry(&mut stats, *innermost).exclusive_instructions += count;
+            }
+
+            // Recursive subroutines must only be counted once per stack
+            let mut seen: Vec<usize> = Vec::with_capacity(stack.len());
+            for address in stack {
+                if !seen.contains(address) {
+                    seen.push(*address);
+                    Self::stats_entry(&mut stats, *address).inclusive_instructions += count;
+                }
+            }
+        }
+
+        for (address, calls) in &self.call_counts {
+            Self::stats_entry(&mut stats, *address).calls = *calls;
+        }
+
+        let mut stats: Vec<SubroutineStats> = stats.into_values().collect();
+        stats.sort_by(|a, b| {
+            b.inclusive_instructions
+                .cmp(&a.inclusive_instructions)
+                .then(a.address.cmp(&b.address))
+        });
+        stats
+    }
+
+    fn stats_entry(
+        stats: &mut HashMap<usize, SubroutineStats>,
+        address: usize,
+    ) -> &mut SubroutineStats {
+        stats.entry(address).or_insert(SubroutineStats {
+            address,
+            calls: 0,
+            inclusive_instructions: 0,
+            exclusive_instructions: 0,
+        })
+    }
+
+    pub fn write_report<W: Write>(
+        &self,
+        writer: &mut W,
+        limit: usize,
+        symbols: Option<&SymbolTable>,
+    ) -> io::Result<()> {
+        let total = self.total_instructions.max(1) as f64;
+        let format_address = |address: usize| match symbols {
+            Some(symbols) => symbols.format_address(address),
+            None => format!("{:#05x}", address),
+        };
+
+        writeln!(writer, "total instructions: {}", self.total_instructions)?;
+
+        writeln!(writer)?;
+        writeln!(writer, "hot addresses:")?;
+        for (address, count) in self.address_hot_spots().into_iter().take(limit) {
+            writeln!(
+                writer,
+                "  {:<24} {:>12} {:>6.2}%",
+                format_address(address),
+                count,
+                count as f64 * 100.0 / total
+            )?;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "hot instructions:")?;
+        for (name, count) in self.instruction_hot_spots().into_iter().take(limit) {
+            writeln!(
+                writer,
+                "  {:<46} {:>12} {:>6.2}%",
+                name,
+                count,
+                count as f64 * 100.0 / total
+            )?;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "subroutines (calls, inclusive, exclusive):")?;
+        for stats in self.subroutine_hot_spots().into_iter().take(limit) {
+            writeln!(
+                writer,
+                "  {:<24} {:>10} {:>12} {:>6.2}% {:>12} {:>6.2}%",
+                format_address(stats.address),
+                stats.calls,
+                stats.inclusive_instructions,
+                stats.inclusive_instructions as f64 * 100.0 / total,
+                stats.exclusive_instructions,
+                stats.exclusive_instructions as f64 * 100.0 / total
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_folded_stacks<W: Write>(
+        &self,
+        writer: &mut W,
+        symbols: Option<&SymbolTable>,
+    ) -> io::Result<()> {
+        let mut stacks: Vec<(&Vec<usize>, &u64)> = self.folded_stacks.iter().collect();
+        stacks.sort();
+
+        for (stack, count) in stacks {
+            write!(writer, "main")?;
+            for address in stack {
+                match symbols.and_then(|symbols| symbols.label(*address)) {
+                    Some(label) => write!(writer, ";{}", label)?,
+                    None => write!(writer, ";{:#05x}", address)?,
+                }
+            }
+            writeln!(writer, " {}", count)?;
+        }
+
+        Ok(())
+    }
+}
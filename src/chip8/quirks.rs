@@ -0,0 +1,230 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::platform::Platform;
+
+// Named after the quirks of the community CHIP-8 database, the default matches the
+// behaviour rust8 always had
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Quirks {
+    // 8XY6 and 8XYE shift VX in place instead of storing the shifted VY in VX
+    pub shift: bool,
+    // FX55 and FX65 increment I by X instead of X + 1
+    pub memory_increment_by_x: bool,
+    // FX55 and FX65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    // Sprites wrap around the screen edges instead of being clipped
+    pub wrap: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump: bool,
+    // DXYN ends the current frame, so at most one sprite is drawn per frame
+    pub vblank: bool,
+    // 8XY1, 8XY2 and 8XY3 reset VF to zero
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+impl Quirks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: false,
+            jump: false,
+            vblank: true,
+            logic: true,
+        }
+    }
+
+    pub fn super_chip() -> Self {
+        Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: true,
+            vblank: false,
+            logic: false,
+        }
+    }
+
+    pub fn xo_chip() -> Self {
+        Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+
+    // Amount I is advanced by after FX55 or FX65 with the given X
+    pub fn memory_increment(&self, x: u8) -> usize {
+        if self.memory_leave_i_unchanged {
+            0
+        } else if self.memory_increment_by_x {
+            x as usize
+        } else {
+            x as usize + 1
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QuirkFlag {
+    Shift,
+    MemoryIncrementByX,
+    MemoryLeaveIUnchanged,
+    Wrap,
+    Jump,
+    Vblank,
+    Logic,
+}
+
+impl QuirkFlag {
+    pub const ALL: [QuirkFlag; 7] = [
+        QuirkFlag::Shift,
+        QuirkFlag::MemoryIncrementByX,
+        QuirkFlag::MemoryLeaveIUnchanged,
+        QuirkFlag::Wrap,
+        QuirkFlag::Jump,
+        QuirkFlag::Vblank,
+        QuirkFlag::Logic,
+    ];
+
+    pub fn get(&self, quirks: &Quirks) -> bool {
+        match self {
+            QuirkFlag::Shift => quirks.shift,
+            QuirkFlag::MemoryIncrementByX => quirks.memory_increment_by_x,
+            QuirkFlag::MemoryLeaveIUnchanged => quirks.memory_leave_i_unchanged,
+            QuirkFlag::Wrap => quirks.wrap,
+            QuirkFlag::Jump => quirks.jump,
+            QuirkFlag::Vblank => quirks.vblank,
+            QuirkFlag::Logic => quirks.logic,
+        }
+    }
+
+    pub fn set(&self, quirks: &mut Quirks, value: bool) {
+        match self {
+            QuirkFlag::Shift => quirks.shift = value,
+            QuirkFlag::MemoryIncrementByX => quirks.memory_increment_by_x = value,
+            QuirkFlag::MemoryLeaveIUnchanged => quirks.memory_leave_i_unchanged = value,
+            QuirkFlag::Wrap => quirks.wrap = value,
+            QuirkFlag::Jump => quirks.jump = value,
+            QuirkFlag::Vblank => quirks.vblank = value,
+            QuirkFlag::Logic => quirks.logic = value,
+        }
+    }
+}
+
+// Uses the names of the community database
+impl Display for QuirkFlag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            QuirkFlag::Shift => write!(f, "shift"),
+            QuirkFlag::MemoryIncrementByX => write!(f, "memoryIncrementByX"),
+            QuirkFlag::MemoryLeaveIUnchanged => write!(f, "memoryLeaveIUnchanged"),
+            QuirkFlag::Wrap => write!(f, "wrap"),
+            QuirkFlag::Jump => write!(f, "jump"),
+            QuirkFlag::Vblank => write!(f, "vblank"),
+            QuirkFlag::Logic => write!(f, "logic"),
+        }
+    }
+}
+
+impl Quirks {
+    // Every combination of the quirk flags
+    pub fn all_combinations() -> impl Iterator<Item = Quirks> {
+        (0..1u32 << QuirkFlag::ALL.len()).map(|bits| {
+            let mut quirks = Quirks::default();
+            for (index, flag) in QuirkFlag::ALL.iter().enumerate() {
+                flag.set(&mut quirks, bits & (1 << index) != 0);
+            }
+            quirks
+        })
+    }
+}
+
+impl Display for Quirks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let enabled: Vec<String> = QuirkFlag::ALL
+            .iter()
+            .filter(|flag| flag.get(self))
+            .map(|flag| flag.to_string())
+            .collect();
+
+        match enabled.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", enabled.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuirksProfile {
+    CosmacVip,
+    SuperChip,
+    XoChip,
+}
+
+impl QuirksProfile {
+    pub const ALL: [QuirksProfile; 3] = [
+        QuirksProfile::CosmacVip,
+        QuirksProfile::SuperChip,
+        QuirksProfile::XoChip,
+    ];
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            QuirksProfile::CosmacVip => Quirks::cosmac_vip(),
+            QuirksProfile::SuperChip => Quirks::super_chip(),
+            QuirksProfile::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    // The profile of the reference interpreter of the platform
+    pub fn for_platform(platform: Platform) -> QuirksProfile {
+        match platform {
+            Platform::Chip8 => QuirksProfile::CosmacVip,
+            Platform::SuperChip => QuirksProfile::SuperChip,
+            Platform::XoChip => QuirksProfile::XoChip,
+        }
+    }
+
+    pub fn platform(&self) -> Platform {
+        match self {
+            QuirksProfile::CosmacVip => Platform::Chip8,
+            QuirksProfile::SuperChip => Platform::SuperChip,
+            QuirksProfile::XoChip => Platform::XoChip,
+        }
+    }
+}
+
+impl Display for QuirksProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            QuirksProfile::CosmacVip => write!(f, "COSMAC VIP"),
+            QuirksProfile::SuperChip => write!(f, "SUPER-CHIP"),
+            QuirksProfile::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+
+use super::graphic::{Display, Pixel, Screen};
+use super::keyboard::Key;
+use super::quirks::{QuirkFlag, Quirks};
+use super::Chip8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScriptedInput {
+    pub frame: usize,
+    pub key: Key,
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Outcome {
+    pub framebuffer_hash: u64,
+    pub memory_hash: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuirkRun {
+    pub quirks: Quirks,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlagSensitivity {
+    pub flag: QuirkFlag,
+    // Pairs of combinations that only differ in this flag and produce different outcomes
+    pub changed_pairs: usize,
+    pub total_pairs: usize,
+}
+
+// The programs run on a display of type D
+pub struct SensitivityAnalyzer<D: Display + Default = Screen> {
+    frames: usize,
+    seed: u64,
+    inputs: Vec<ScriptedInput>,
+    display: PhantomData<fn() -> D>,
+}
+
+impl<D: Display + Default> Default for SensitivityAnalyzer<D> {
+    fn default() -> Self {
+        SensitivityAnalyzer {
+            frames: 600,
+            seed: 0,
+            inputs: Vec::new(),
+            display: PhantomData,
+        }
+    }
+}
+
+impl SensitivityAnalyzer {
+    pub fn new(frames: usize) -> Self {
+        Self::default().with_frames(frames)
+    }
+}
+
+impl<D: Display + Default> SensitivityAnalyzer<D> {
+    pub fn with_frames(mut self, frames: usize) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_input(mut self, frame: usize, key: Key, pressed: bool) -> Self {
+        self.inputs.push(ScriptedInput {
+            frame,
+            key,
+            pressed,
+        });
+        self
+    }
+
+    pub fn run(&self, program: &[u8], quirks: Quirks) -> Outcome {
+        let mut chip8 = Chip8::with_display(D::default());
+        chip8.quirks = quirks;
+        chip8.seed_rng(self.seed);
+
+        let mut error = chip8.load_program(program).err().map(|err| err.to_string());
+
+        for frame in 0..self.frames {
+            if error.is_some() {
+                break;
+            }
+
+            for input in self.inputs.iter().filter(|input| input.frame == frame) {
+                match input.pressed {
+                    true => chip8.key_down(input.key),
+                    false => chip8.key_up(input.key),
+                }
+            }
+
+            if let Err(err) = chip8.run_frame() {
+                error = Some(format!(
+                    "{} at {:#05x} in frame {}",
+                    err, chip8.program_counter, frame
+                ));
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        let resolution = chip8.screen.resolution();
+        for y in 0..resolution.height() {
+            for x in 0..resolution.width() {
+                (chip8.screen.pixel(x, y) == Pixel::On).hash(&mut hasher);
+            }
+        }
+        let framebuffer_hash = hasher.finish();
+
+        let mut hasher = DefaultHasher::new();
+        chip8.memory.raw_data.hash(&mut hasher);
+        let memory_hash = hasher.finish();
+
+        Outcome {
+            framebuffer_hash,
+            memory_hash,
+            error,
+        }
+    }
+
+    pub fn analyze(&self, program: &[u8]) -> SensitivityReport {
+        let runs = Quirks::all_combinations()
+            .map(|quirks| QuirkRun {
+                quirks,
+                outcome: self.run(program, quirks),
+            })
+            .collect();
+
+        SensitivityReport { runs }
+    }
+}
+
+pub struct SensitivityReport {
+    runs: Vec<QuirkRun>,
+}
+
+impl SensitivityReport {
+    pub fn runs(&self) -> &[QuirkRun] {
+        &self.runs
+    }
+
+    pub fn outcome(&self, quirks: &Quirks) -> Option<&Outcome> {
+        self.runs
+            .iter()
+            .find(|run| run.quirks == *quirks)
+            .map(|run| &run.outcome)
+    }
+
+    pub fn sensitivities(&self) -> Vec<FlagSensitivity> {
+        QuirkFlag::ALL
+            .iter()
+            .map(|flag| {
+                let mut changed_pairs = 0;
+                let mut total_pairs = 0;
+
+                for run in self.runs.iter().filter(|run| !flag.get(&run.quirks)) {
+                    let mut toggled = run.quirks;
+                    flag.set(&mut toggled, true);
+
+                    if let Some(outcome) = self.outcome(&toggled) {
+                        total_pairs += 1;
+                        if *outcome != run.outcome {
+                            changed_pairs += 1;
+                        }
+                    }
+                }
+
+                FlagSensitivity {
+                    flag: *flag,
+                    changed_pairs,
+                    total_pairs,
+                }
+            })
+            .collect()
+    }
+
+    pub fn sensitive_flags(&self) -> Vec<QuirkFlag> {
+        self.sensitivities()
+            .into_iter()
+            .filter(|sensitivity| sensitivity.changed_pairs > 0)
+            .map(|sensitivity| sensitivity.flag)
+            .collect()
+    }
+
+    // Groups of quirk combinations that produce identical output, largest group first
+    pub fn equivalence_classes(&self) -> Vec<(Outcome, Vec<Quirks>)> {
+        let mut classes: BTreeMap<usize, (Outcome, Vec<Quirks>)> = BTreeMap::new();
+        let mut outcomes: Vec<&Outcome> = Vec::new();
+
+        for run in &self.runs {
+            let index = match outcomes.iter().position(|outcome| **outcome == run.outcome) {
+                Some(index) => index,
+                None => {
+                    outcomes.push(&run.outcome);
+                    outcomes.len() - 1
+                }
+            };
+
+            classes
+                .entry(index)
+                .or_insert_with(|| (run.outcome.clone(), Vec::new()))
+                .1
+                .push(run.quirks);
+        }
+
+        let mut classes: Vec<(Outcome, Vec<Quirks>)> = classes.into_values().collect();
+        classes.sort_by_key(|(_, quirks)| std::cmp::Reverse(quirks.len()));
+        classes
+    }
+
+    pub fn write_report<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "quirk sensitivity:")?;
+        for sensitivity in self.sensitivities() {
+            writeln!(
+                writer,
+                "  {:<22} {:>3}/{} pairs changed{}",
+                sensitivity.flag.to_string(),
+                sensitivity.changed_pairs,
+                sensitivity.total_pairs,
+                match sensitivity.changed_pairs {
+                    0 => "",
+                    _ => "  <- affects this ROM",
+                }
+            )?;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "identical outcomes:")?;
+        for (index, (outcome, quirks)) in self.equivalence_classes().iter().enumerate() {
+            write!(
+                writer,
+                "  #{} {} combinations, framebuffer {:016x}, memory {:016x}",
+                index,
+                quirks.len(),
+                outcome.framebuffer_hash,
+                outcome.memory_hash
+            )?;
+            match &outcome.error {
+                Some(error) => writeln!(writer, ", error: {}", error)?,
+                None => writeln!(writer)?,
+            }
+
+            // Only the flags that are the same across the whole group describe it
+            let fixed: Vec<String> = QuirkFlag::ALL
+                .iter()
+                .filter_map(|flag| {
+                    let value = flag.get(&quirks[0]);
+                    quirks
+                        .iter()
+                        .all(|quirks| flag.get(quirks) == value)
+                        .then(|| format!("{}={}", flag, value))
+                })
+                .collect();
+            writeln!(
+                writer,
+                "     requires: {}",
+                match fixed.is_empty() {
+                    true => "nothing".to_string(),
+                    false => fixed.join(" "),
+                }
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use thiserror::Error;
+
+use super::constants::{MEMORY_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_SIZE};
+use super::data_register::DataRegister;
+use super::graphic::{BitSlicePixelView, Display, Pixel};
+use super::input::InputEvent;
+use super::keyboard::{Key, KeyState, KeyWait};
+use super::quirks::Quirks;
+use super::timing::TimingModel;
+use super::{Blocked, Chip8};
+
+const STATE_MAGIC: &[u8; 4] = b"R8ST";
+const STATE_VERSION: u8 = 1;
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("data is not a saved state")]
+    InvalidMagic,
+    #[error("unsupported state version {0}")]
+    UnsupportedVersion(u8),
+    #[error("saved state is truncated")]
+    Truncated,
+    #[error("saved state contains an invalid value at offset {0}")]
+    InvalidValue(usize),
+}
+
+struct StateReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> StateReader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + count)
+            .ok_or(StateError::Truncated)?;
+        self.offset += count;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<usize, StateError> {
+        let bytes = self.bytes(2)?;
+
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    // The events that are still queued, with the time they are due
+    fn input(&mut self) -> Result<VecDeque<InputEvent>, StateError> {
+        let size = u32::from_be_bytes(self.array()?);
+        let mut input = VecDeque::new();
+        for _ in 0..size {
+            let seconds = u64::from_be_bytes(self.array()?);
+            let nanoseconds = u32::from_be_bytes(self.array()?);
+            let offset = self.offset;
+            let (key, pressed) = (self.u8()?, self.u8()?);
+            if nanoseconds >= 1_000_000_000 || pressed > 1 {
+                return Err(StateError::InvalidValue(offset));
+            }
+
+            input.push_back(InputEvent {
+                time: Duration::new(seconds, nanoseconds),
+                key: Key::try_from(key).map_err(|_| StateError::InvalidValue(offset))?,
+                pressed: pressed == 1,
+            });
+        }
+
+        Ok(input)
+    }
+}
+
+// The configuration and everything else that follows the screen
+struct MachineState {
+    quirks: Quirks,
+    key_wait: KeyWait,
+    instructions_per_frame: usize,
+    rng: ChaCha12Rng,
+    keys: u16,
+    unobserved_keys: u16,
+    waiting_for_vblank: bool,
+    timing: TimingModel,
+    frame_position: u32,
+    frames: u64,
+    input: VecDeque<InputEvent>,
+}
+
+const QUIRK_COUNT: u32 = 7;
+
+fn quirk_bits(quirks: &Quirks) -> u8 {
+    [
+        quirks.shift,
+        quirks.memory_increment_by_x,
+        quirks.memory_leave_i_unchanged,
+        quirks.wrap,
+        quirks.jump,
+        quirks.vblank,
+        quirks.logic,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (index, quirk)| bits | (*quirk as u8) << index)
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let quirk = |index: u32| bits & 1 << index != 0;
+
+    Quirks {
+        shift: quirk(0),
+        memory_increment_by_x: quirk(1),
+        memory_leave_i_unchanged: quirk(2),
+        wrap: quirk(3),
+        jump: quirk(4),
+        vblank: quirk(5),
+        logic: quirk(6),
+    }
+}
+
+impl MachineState {
+    fn read(reader: &mut StateReader) -> Result<Self, StateError> {
+        let offset = reader.offset;
+        let bits = reader.u8()?;
+        if bits >> QUIRK_COUNT != 0 {
+            return Err(StateError::InvalidValue(offset));
+        }
+        let quirks = quirks_from_bits(bits);
+
+        let offset = reader.offset;
+        let key_wait = match reader.u8()? {
+            0 => KeyWait::Accurate,
+            1 => KeyWait::Legacy,
+            _ => return Err(StateError::InvalidValue(offset)),
+        };
+
+        let instructions_per_frame = u32::from_be_bytes(reader.array()?) as usize;
+
+        let mut rng = ChaCha12Rng::from_seed(reader.array()?);
+        rng.set_stream(u64::from_be_bytes(reader.array()?));
+        rng.set_word_pos(u128::from_be_bytes(reader.array()?));
+
+        let keys = reader.u16()? as u16;
+        let unobserved_keys = reader.u16()? as u16;
+
+        let offset = reader.offset;
+        let waiting_for_vblank = match reader.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(StateError::InvalidValue(offset)),
+        };
+
+        let offset = reader.offset;
+        let timing = match reader.u8()? {
+            0 => TimingModel::Fixed,
+            1 => TimingModel::CosmacVip,
+            _ => return Err(StateError::InvalidValue(offset)),
+        };
+        let frame_position = u32::from_be_bytes(reader.array()?);
+        let frames = u64::from_be_bytes(reader.array()?);
+        let input = reader.input()?;
+
+        Ok(MachineState {
+            quirks,
+            key_wait,
+            instructions_per_frame,
+            rng,
+            keys,
+            unobserved_keys,
+            waiting_for_vblank,
+            timing,
+            frame_position,
+            frames,
+            input,
+        })
+    }
+}
+
+impl<D: Display> Chip8<D> {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(MEMORY_SIZE + 512);
+
+        state.extend_from_slice(STATE_MAGIC);
+        state.push(STATE_VERSION);
+
+        state.extend_from_slice(&self.memory.raw_data);
+        for register in 0..16u8 {
+            state.push(self.data_registers[DataRegister::try_from(register).unwrap()]);
+        }
+        state.extend_from_slice(&(self.address_register as u16).to_be_bytes());
+        state.extend_from_slice(&(self.program_counter as u16).to_be_bytes());
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+
+        state.push(self.stack.len() as u8);
+        for address in &self.stack {
+            state.extend_from_slice(&(*address as u16).to_be_bytes());
+        }
+
+        match self.blocked {
+            Blocked::No => state.extend_from_slice(&[0, 0, 0]),
+            Blocked::WaitingOnKeyUp(vx) => state.extend_from_slice(&[1, u8::from(vx), 0]),
+            Blocked::WaitingForKeyPress(vx) => state.extend_from_slice(&[2, u8::from(vx), 0]),
+            Blocked::WaitingForKeyRelease(vx, key) => {
+                state.extend_from_slice(&[3, u8::from(vx), u8::from(key)])
+            }
+        }
+
+        // The screen is stored in the 64x32 resolution of the original CHIP-8
+        for y in 0..SCREEN_HEIGHT {
+            for x in (0..SCREEN_WIDTH).step_by(8) {
+                let byte = (x..x + 8).fold(0u8, |byte, x| {
+                    byte << 1 | (self.screen.pixel(x, y) == Pixel::On) as u8
+                });
+                state.push(byte);
+            }
+        }
+
+        state.push(quirk_bits(&self.quirks));
+        state.push(match self.key_wait {
+            KeyWait::Accurate => 0,
+            KeyWait::Legacy => 1,
+        });
+        state.extend_from_slice(&(self.instructions_per_frame as u32).to_be_bytes());
+
+        state.extend_from_slice(&self.rng.get_seed());
+        state.extend_from_slice(&self.rng.get_stream().to_be_bytes());
+        state.extend_from_slice(&self.rng.get_word_pos().to_be_bytes());
+
+        let keys = (0..16u8)
+            .filter(|key| {
+                let key = Key::try_from(*key).unwrap();
+                matches!(self.keyboard.get_key_state(key), KeyState::Pressed)
+            })
+            .fold(0u16, |keys, key| keys | 1 << key);
+        state.extend_from_slice(&keys.to_be_bytes());
+        state.extend_from_slice(&self.keyboard.unobserved().to_be_bytes());
+
+        state.push(self.waiting_for_vblank as u8);
+
+        state.push(match self.timing {
+            TimingModel::Fixed => 0,
+            TimingModel::CosmacVip => 1,
+        });
+        state.extend_from_slice(&self.frame_position.to_be_bytes());
+        state.extend_from_slice(&self.frames.to_be_bytes());
+
+        state.extend_from_slice(&(self.input.len() as u32).to_be_bytes());
+        for event in &self.input {
+            state.extend_from_slice(&event.time.as_secs().to_be_bytes());
+            state.extend_from_slice(&event.time.subsec_nanos().to_be_bytes());
+            state.push(u8::from(event.key));
+            state.push(event.pressed as u8);
+        }
+
+        state
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader {
+            data: state,
+            offset: 0,
+        };
+
+        if reader.bytes(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err(StateError::InvalidMagic);
+        }
+        let version = reader.u8()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        // Everything is read before the machine is touched, so a broken state changes nothing
+        let memory = reader.bytes(MEMORY_SIZE)?;
+        let data_registers = reader.bytes(16)?;
+        let address_register = reader.u16()?;
+        let program_counter = reader.u16()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+
+        let stack_size_offset = reader.offset;
+        let stack_size = reader.u8()? as usize;
+        if stack_size > STACK_SIZE {
+            return Err(StateError::InvalidValue(stack_size_offset));
+        }
+        let stack = (0..stack_size)
+            .map(|_| reader.u16())
+            .collect::<Result<Vec<usize>, StateError>>()?;
+
+        let blocked_offset = reader.offset;
+        let (kind, vx, key) = (reader.u8()?, reader.u8()?, reader.u8()?);
+        let invalid = || StateError::InvalidValue(blocked_offset);
+        let blocked = match kind {
+            0 => Blocked::No,
+            1 => Blocked::WaitingOnKeyUp(DataRegister::try_from(vx).map_err(|_| invalid())?),
+            2 => Blocked::WaitingForKeyPress(DataRegister::try_from(vx).map_err(|_| invalid())?),
+            3 => Blocked::WaitingForKeyRelease(
+                DataRegister::try_from(vx).map_err(|_| invalid())?,
+                Key::try_from(key).map_err(|_| invalid())?,
+            ),
+            _ => return Err(StateError::InvalidValue(blocked_offset)),
+        };
+
+        let framebuffer = reader.bytes(SCREEN_WIDTH * SCREEN_HEIGHT / 8)?;
+
+        let machine = MachineState::read(&mut reader)?;
+
+        self.memory.raw_data.copy_from_slice(memory);
+        for (register, value) in data_registers.iter().enumerate() {
+            self.data_registers[DataRegister::try_from(register as u8).unwrap()] = *value;
+        }
+        self.address_register = address_register;
+        self.program_counter = program_counter;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.stack = stack;
+        self.blocked = blocked;
+        self.in_jump = false;
+        // Drawn as one screen sized sprite onto the cleared display, which leaves exactly the
+        // saved pixels on
+        self.screen.clear();
+        self.screen.draw_sprite(
+            0,
+            0,
+            &BitSlicePixelView::new_from_byte_slice(framebuffer, SCREEN_WIDTH, SCREEN_HEIGHT),
+            false,
+        );
+
+        self.quirks = machine.quirks;
+        self.key_wait = machine.key_wait;
+        self.instructions_per_frame = machine.instructions_per_frame;
+        self.rng = machine.rng;
+        for index in 0..16u8 {
+            let key = Key::try_from(index).unwrap();
+            match machine.keys & 1 << index != 0 {
+                true => self.keyboard.key_down(key),
+                false => self.keyboard.key_up(key),
+            }
+        }
+        self.keyboard.set_unobserved(machine.unobserved_keys);
+        self.waiting_for_vblank = machine.waiting_for_vblank;
+        self.timing = machine.timing;
+        self.frame_position = machine.frame_position;
+        self.frames = machine.frames;
+        self.input = machine.input;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::cpu::CycleError;
+use super::disassembler::DisassembledInstruction;
+use super::graphic;
+use super::Chip8;
+
+#[derive(Error, Debug)]
+pub enum SymbolError {
+    #[error("invalid symbol on line {0}")]
+    InvalidLine(usize),
+    #[error("invalid address '{1}' on line {0}")]
+    InvalidAddress(usize, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    labels: BTreeMap<usize, String>,
+    addresses: HashMap<String, usize>,
+    locations: BTreeMap<usize, SourceLocation>,
+}
+
+fn parse_address(value: &str) -> Option<usize> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Each line is either `<address> <label>`, `<address> <file>:<line>` or an Octo style
+    // `:const <label> <address>` directive, `#` starts a comment
+    pub fn parse(source: &str) -> Result<Self, SymbolError> {
+        let mut symbols = SymbolTable::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (address, name) = match words.as_slice() {
+                [":const" | ":label" | ":breakpoint", name, address] => (*address, *name),
+                [address, name] if !address.starts_with(':') => (*address, *name),
+                _ => return Err(SymbolError::InvalidLine(line_number)),
+            };
+
+            let address = parse_address(address)
+                .ok_or_else(|| SymbolError::InvalidAddress(line_number, address.to_string()))?;
+
+            let location = name.rsplit_once(':').and_then(|(file, line)| {
+                Some(SourceLocation {
+                    file: file.to_string(),
+                    line: line.parse().ok()?,
+                })
+            });
+
+            match location {
+                Some(location) => symbols.insert_location(address, location),
+                None => symbols.insert_label(address, name),
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    pub fn insert_label(&mut self, address: usize, label: &str) {
+        // The first label of an address is kept for display, every label can be looked up
+        self.labels
+            .entry(address)
+            .or_insert_with(|| label.to_string());
+        self.addresses.insert(label.to_string(), address);
+    }
+
+    pub fn insert_location(&mut self, address: usize, location: SourceLocation) {
+        self.locations.insert(address, location);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.locations.is_empty()
+    }
+
+    pub fn label(&self, address: usize) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    pub fn address(&self, label: &str) -> Option<usize> {
+        self.addresses.get(label).copied()
+    }
+
+    pub fn location(&self, address: usize) -> Option<&SourceLocation> {
+        self.locations.get(&address)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.labels
+            .iter()
+            .map(|(address, label)| (*address, label.as_str()))
+    }
+
+    pub fn locations(&self) -> impl Iterator<Item = (usize, &SourceLocation)> {
+        self.locations
+            .iter()
+            .map(|(address, location)| (*address, location))
+    }
+
+    // The closest label at or before the address together with the offset from it
+    pub fn symbolize(&self, address: usize) -> Option<(&str, usize)> {
+        self.labels
+            .range(..=address)
+            .next_back()
+            .map(|(label_address, label)| (label.as_str(), address - label_address))
+    }
+
+    pub fn format_address(&self, address: usize) -> String {
+        match self.symbolize(address) {
+            Some((label, 0)) => format!("{:#05x} <{}>", address, label),
+            Some((label, offset)) => format!("{:#05x} <{}+{:#x}>", address, label, offset),
+            None => format!("{:#05x}", address),
+        }
+    }
+
+    pub fn format_instruction(&self, instruction: &DisassembledInstruction) -> String {
+        let text = instruction.to_string();
+
+        // Only exact matches are substituted, so the listing stays re-assemblable
+        let target = instruction
+            .instruction
+            .and_then(|instruction| instruction.target_address());
+        match target.and_then(|target| Some((target, self.label(target)?))) {
+            Some((target, label)) => text.replacen(&format!("{:#05x}", target), label, 1),
+            None => text,
+        }
+    }
+
+    pub fn write_listing<W: Write>(
+        &self,
+        writer: &mut W,
+        listing: &[DisassembledInstruction],
+    ) -> io::Result<()> {
+        for instruction in listing {
+            if let Some(label) = self.label(instruction.address) {
+                writeln!(writer, ": {}", label)?;
+            }
+
+            write!(
+                writer,
+                "  {:#05x}: {:04x}  {}",
+                instruction.address,
+                instruction.opcode,
+                self.format_instruction(instruction)
+            )?;
+            match self.location(instruction.address) {
+                Some(location) => writeln!(writer, "  # {}", location)?,
+                None => writeln!(writer)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    // The current program counter followed by the return address of every active call
+    pub fn backtrace<D: graphic::Display>(&self, chip8: &Chip8<D>) -> Vec<String> {
+        std::iter::once(chip8.program_counter)
+            .chain(chip8.stack.iter().rev().copied())
+            .map(|address| self.describe_address(address))
+            .collect()
+    }
+
+    pub fn write_backtrace<W: Write, D: graphic::Display>(
+        &self,
+        writer: &mut W,
+        chip8: &Chip8<D>,
+    ) -> io::Result<()> {
+        for (index, frame) in self.backtrace(chip8).into_iter().enumerate() {
+            writeln!(writer, "#{} {}", index, frame)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn describe_error<D: graphic::Display>(
+        &self,
+        err: &CycleError,
+        chip8: &Chip8<D>,
+    ) -> String {
+        let mut description = format!(
+            "{} at {}",
+            err,
+            self.describe_address(chip8.program_counter)
+        );
+
+        let mut source = std::error::Error::source(err);
+        while let Some(err) = source {
+            description.push_str(&format!(": {}", err));
+            source = err.source();
+        }
+
+        description
+    }
+
+    fn describe_address(&self, address: usize) -> String {
+        match self.location(address) {
+            Some(location) => format!("{} at {}", self.format_address(address), location),
+            None => self.format_address(address),
+        }
+    }
+}
+
+impl FromStr for SymbolTable {
+    type Err = SymbolError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
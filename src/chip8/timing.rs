@@ -0,0 +1,158 @@
+use super::constants::INSTRUCTION_SIZE;
+use super::cpu::CycleError;
+use super::data_register::DataRegisters;
+use super::graphic::Display;
+use super::instruction::Instruction;
+use super::Chip8;
+
+// The 1802 of the VIP runs at 1.7609 MHz and takes 8 clock cycles per machine cycle
+pub const VIP_MACHINE_CYCLES_PER_FRAME: u32 = 3668;
+// The 1861 fetches 8 bytes by DMA on each of the 128 lines it displays, one machine cycle each
+pub const VIP_DMA_CYCLES_PER_FRAME: u32 = 1024;
+// The display interrupt routine that sets up the DMA and counts down the timers
+pub const VIP_INTERRUPT_CYCLES: u32 = 29;
+// What is left for the interpreter in every frame
+pub const VIP_INSTRUCTION_CYCLES_PER_FRAME: u32 =
+    VIP_MACHINE_CYCLES_PER_FRAME - VIP_DMA_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES;
+// Extra cost of a skip that is taken
+pub const VIP_SKIP_CYCLES: u32 = 4;
+// Charged for every opcode an extension or the unknown opcode policy takes, built-in ones that
+// an extension redefines included
+pub const VIP_CUSTOM_OPCODE_CYCLES: u32 = 23;
+// Time that passes between two checks for queued input while the interpreter idles
+const VIP_IDLE_CYCLES: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TimingModel {
+    // instructions_per_frame instructions, whatever they are
+    #[default]
+    Fixed,
+    // As many instructions as the original interpreter gets through on a COSMAC VIP
+    CosmacVip,
+}
+
+// Machine cycles the original interpreter needs for the instruction, fetch and decode included,
+// before a skip is taken. Depends on the registers as they are before it is executed.
+pub fn vip_machine_cycles(instruction: &Instruction, registers: &DataRegisters) -> u32 {
+    match *instruction {
+        Instruction::ExecuteMachineLanguageSubroutine { .. } => 23,
+        Instruction::ClearScreen => 24,
+        Instruction::ReturnFromSubroutine => 23,
+        Instruction::JumpToAddress { .. } => 23,
+        Instruction::ExecuteSubroutine { .. } => 23,
+        Instruction::SkipIfVxEqualsNum { .. } | Instruction::SkipIfVxNotEqualNum { .. } => 12,
+        Instruction::SkipIfVxEqualsVy { .. } | Instruction::SkipIfVxNotEqualVy { .. } => 16,
+        Instruction::StoreNumInVx { .. } => 6,
+        Instruction::AddNumToVx { .. } => 10,
+        Instruction::StoreVyInVx { .. }
+        | Instruction::SetVxToVxOrVy { .. }
+        | Instruction::SetVxToVxAndVy { .. }
+        | Instruction::SetVxToVxXorVy { .. }
+        | Instruction::AddVyToVx { .. }
+        | Instruction::SubtractVyFromVx { .. }
+        | Instruction::ShiftVyRightStoreInVx { .. }
+        | Instruction::SetVxToVyMinusVx { .. }
+        | Instruction::ShiftVyLeftStoreInVx { .. } => 44,
+        Instruction::StoreAddressInAddressRegister { .. } => 12,
+        Instruction::JumpToAddressPlusV0 { .. } => 23,
+        Instruction::SetVxToRandomWithMask { .. } => 36,
+        Instruction::DrawSpriteAtVxVy { vx, byte_count, .. } => {
+            // Every row is shifted into place one bit at a time, and a row that is not byte
+            // aligned touches two bytes of the display buffer
+            let shift = registers[vx] as u32 % 8;
+            let row = match shift {
+                0 => 14,
+                _ => 20 + 2 * shift,
+            };
+            26 + byte_count as u32 * row
+        }
+        Instruction::SkipIfKeyInVxPressed { .. } | Instruction::SkipIfKeyInVxNotPressed { .. } => {
+            16
+        }
+        Instruction::StoreDelayTimerInVx { .. } => 10,
+        Instruction::WaitForKeypressStoreInVx { .. } => 10,
+        Instruction::SetDelayTimerToVx { .. } | Instruction::SetSoundTimerToVx { .. } => 10,
+        Instruction::AddVxToAddressRegister { .. } => 19,
+        Instruction::SetAddressRegisterToSpriteAddressOfSpriteInVx { .. } => 20,
+        Instruction::StoreBCDOfVx { vx } => {
+            // Each digit is found by repeated subtraction
+            let num = registers[vx] as u32;
+            40 + 16 * (num / 100 + num / 10 % 10 + num % 10)
+        }
+        Instruction::StoreRegistersInMemory { vx }
+        | Instruction::FillRegistersFromMemory { vx } => 30 + 14 * (u8::from(vx) as u32 + 1),
+    }
+}
+
+fn is_skip(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::SkipIfVxEqualsNum { .. }
+            | Instruction::SkipIfVxNotEqualNum { .. }
+            | Instruction::SkipIfVxEqualsVy { .. }
+            | Instruction::SkipIfVxNotEqualVy { .. }
+            | Instruction::SkipIfKeyInVxPressed { .. }
+            | Instruction::SkipIfKeyInVxNotPressed { .. }
+    )
+}
+
+impl<D: Display> Chip8<D> {
+    // Executes one instruction and returns what it cost on the VIP
+    fn vip_cycle(&mut self) -> Result<u32, CycleError> {
+        let address = self.program_counter;
+        let built_in = self
+            .memory
+            .read_instruction(address)
+            .ok()
+            .map(|instruction| {
+                let cycles = vip_machine_cycles(&instruction, &self.data_registers);
+                (instruction, cycles)
+            });
+
+        // Whatever a custom opcode does, it is not charged like the built-in instruction
+        let custom = self.execute_next()?;
+        let Some((instruction, mut cycles)) = built_in.filter(|_| !custom) else {
+            return Ok(VIP_CUSTOM_OPCODE_CYCLES);
+        };
+
+        if is_skip(&instruction) && self.program_counter == address + 2 * INSTRUCTION_SIZE {
+            cycles += VIP_SKIP_CYCLES;
+        }
+
+        Ok(cycles)
+    }
+
+    // Runs the next instruction before the display interrupt, an instruction that is still
+    // running when it comes finishes at the expense of the next frame
+    pub(crate) fn step_vip_frame(&mut self) -> Result<bool, CycleError> {
+        let budget = VIP_INSTRUCTION_CYCLES_PER_FRAME;
+
+        while self.frame_position < budget {
+            if !self.input.is_empty() {
+                self.apply_input(self.frame_time(self.frame_position as u64, budget as u64));
+            }
+
+            // The interpreter idles until the interrupt, queued input may still unblock it
+            if self.waiting_for_vblank || self.is_blocked() {
+                match self.input.is_empty() {
+                    true => {
+                        self.frame_position = 0;
+                        return Ok(false);
+                    }
+                    false => {
+                        self.frame_position = (self.frame_position + VIP_IDLE_CYCLES).min(budget);
+                        continue;
+                    }
+                }
+            }
+
+            self.frame_position += self.vip_cycle()?;
+
+            return Ok(true);
+        }
+
+        self.frame_position -= budget;
+
+        Ok(false)
+    }
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "cartridge")]
+
+use serde_json::json;
+
+use rust8::cartridge::{Cartridge, CartridgeError, OctoOptions};
+use rust8::quirks::Quirks;
+
+fn encoded(cartridge: &Cartridge) -> Result<Vec<u8>, CartridgeError> {
+    let mut image = Vec::new();
+    cartridge.encode(&mut image)?;
+    Ok(image)
+}
+
+#[test]
+fn cartridges_survive_a_round_trip() {
+    let mut options = OctoOptions::from_quirks(&Quirks::cosmac_vip(), 15);
+    options.fill_color = Some("#FFCC00".to_string());
+    options.extra.insert("screenRotation".to_string(), json!(0));
+    let cartridge = Cartridge::new(": main\n\tv0 := 1\n\tloop again\n".to_string(), options);
+
+    let image = encoded(&cartridge).unwrap();
+    assert!(image.starts_with(b"GIF89a"));
+
+    let decoded = Cartridge::decode(image.as_slice()).unwrap();
+    assert_eq!(decoded, cartridge);
+    assert_eq!(decoded.options.quirks(), Quirks::cosmac_vip());
+}
+
+#[test]
+fn programs_are_disassembled_into_cartridges() {
+    // v0 := 5, jump to itself
+    let cartridge = Cartridge::from_program(&[0x60, 0x05, 0x12, 0x02], &Quirks::new(), 20);
+    let decoded = Cartridge::decode(encoded(&cartridge).unwrap().as_slice()).unwrap();
+
+    assert!(
+        decoded.program.contains("v0 := 0x05"),
+        "{}",
+        decoded.program
+    );
+    assert_eq!(decoded.options.tickrate, Some(20));
+}
+
+#[test]
+fn oversized_payloads_are_rejected() {
+    // An image is at most 65535 rows of 128 pixels with 2 bits each
+    let cartridge = Cartridge::new("#".repeat(128 * 65535 / 4), OctoOptions::new());
+
+    assert!(matches!(
+        encoded(&cartridge),
+        Err(CartridgeError::TooLarge(_))
+    ));
+}
+
+#[test]
+fn images_without_a_payload_are_rejected() {
+    let empty = Cartridge::new(String::new(), OctoOptions::new());
+    let mut image = encoded(&empty).unwrap();
+    // Cuts the image data short after the header and the palette
+    image.truncate(13 + 12);
+
+    assert!(Cartridge::decode(image.as_slice()).is_err());
+}
@@ -0,0 +1,59 @@
+use std::collections::BTreeSet;
+
+use rust8::cfg::{ControlFlowGraph, Edge, EdgeKind, Finding, Subroutine};
+
+// v0 := 0, calls the subroutine at 0x20a unless v0 is 0 and then loops at 0x208, the
+// subroutine returns right away
+const PROGRAM: [u8; 12] = [
+    0x60, 0x00, 0x30, 0x00, 0x22, 0x0A, 0x12, 0x08, 0x12, 0x08, 0x00, 0xEE,
+];
+
+fn edge(from: usize, to: usize, kind: EdgeKind) -> Edge {
+    Edge { from, to, kind }
+}
+
+fn successors(graph: &ControlFlowGraph, block: usize) -> Vec<Edge> {
+    graph.successors(block).copied().collect()
+}
+
+#[test]
+fn skips_and_calls_have_both_successors() {
+    let graph = ControlFlowGraph::from_program(&PROGRAM);
+
+    let starts: Vec<usize> = graph.blocks().map(|block| block.start).collect();
+    assert_eq!(starts, [0x200, 0x204, 0x206, 0x208, 0x20A]);
+
+    assert_eq!(
+        successors(&graph, 0x200),
+        [
+            edge(0x200, 0x204, EdgeKind::Fallthrough),
+            edge(0x200, 0x206, EdgeKind::Skip)
+        ]
+    );
+    assert_eq!(
+        successors(&graph, 0x204),
+        [
+            edge(0x204, 0x206, EdgeKind::Fallthrough),
+            edge(0x204, 0x20A, EdgeKind::Call)
+        ]
+    );
+    assert_eq!(
+        successors(&graph, 0x20A),
+        [edge(0x20A, 0x206, EdgeKind::Return)]
+    );
+    assert_eq!(
+        successors(&graph, 0x208),
+        [edge(0x208, 0x208, EdgeKind::Jump)]
+    );
+
+    assert_eq!(
+        graph.subroutine(0x20A),
+        Some(&Subroutine {
+            entry: 0x20A,
+            blocks: BTreeSet::from([0x20A]),
+            callees: BTreeSet::new(),
+            returns: true,
+        })
+    );
+    assert_eq!(graph.findings(), [Finding::InfiniteLoop { address: 0x208 }]);
+}
@@ -0,0 +1,270 @@
+use rust8::cosmac_vip::{Bus, Cdp1802, CosmacVip, VipError};
+use rust8::graphic::{Display, Pixel};
+
+const DATA_ADDRESS: u16 = 0x40;
+
+// 64k of RAM, flags that are set by the test and a record of the output ports
+struct TestBus {
+    memory: Vec<u8>,
+    flags: [bool; 4],
+    outputs: Vec<(u8, u8)>,
+}
+
+impl TestBus {
+    fn new(program: &[u8]) -> Self {
+        let mut memory = vec![0; 0x10000];
+        memory[..program.len()].copy_from_slice(program);
+
+        TestBus {
+            memory,
+            flags: [false; 4],
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl Bus for TestBus {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        0x40 | port
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        self.outputs.push((port, value));
+    }
+
+    fn flag(&self, number: u8) -> bool {
+        self.flags[number as usize - 1]
+    }
+}
+
+// Runs the program from 0 for the given number of instructions, returning the cycles taken
+fn run(cpu: &mut Cdp1802, bus: &mut TestBus, steps: usize) -> u32 {
+    (0..steps).map(|_| cpu.step(bus)).sum()
+}
+
+// Executes an instruction that works on D, DF and the byte at R(X)
+fn arithmetic(opcode: u8, d: u8, df: bool, memory: u8) -> (u8, bool) {
+    let mut bus = TestBus::new(&[opcode]);
+    bus.memory[DATA_ADDRESS as usize] = memory;
+
+    let mut cpu = Cdp1802::new();
+    cpu.registers[5] = DATA_ADDRESS;
+    cpu.x = 5;
+    cpu.d = d;
+    cpu.df = df;
+    cpu.step(&mut bus);
+
+    (cpu.d, cpu.df)
+}
+
+// Where the program counter ends up after the branch at 0, and what it cost
+fn branch(program: &[u8], d: u8) -> (u16, u32) {
+    let mut bus = TestBus::new(program);
+    bus.flags[0] = true;
+
+    let mut cpu = Cdp1802::new();
+    cpu.d = d;
+    let cycles = cpu.step(&mut bus);
+
+    (cpu.program_counter(), cycles)
+}
+
+#[test]
+fn subtractions_set_df_when_there_is_no_borrow() {
+    // SD: M(R(X)) - D
+    assert_eq!(arithmetic(0xF5, 0x10, false, 0x30), (0x20, true));
+    assert_eq!(arithmetic(0xF5, 0x30, true, 0x10), (0xE0, false));
+    // SM: D - M(R(X))
+    assert_eq!(arithmetic(0xF7, 0x30, false, 0x10), (0x20, true));
+    assert_eq!(arithmetic(0xF7, 0x10, true, 0x30), (0xE0, false));
+    // SDB and SMB also take the borrow of DF
+    assert_eq!(arithmetic(0x75, 0x10, false, 0x30), (0x1F, true));
+    assert_eq!(arithmetic(0x75, 0x10, true, 0x30), (0x20, true));
+    assert_eq!(arithmetic(0x77, 0x10, false, 0x10), (0xFF, false));
+    assert_eq!(arithmetic(0x77, 0x10, true, 0x10), (0x00, true));
+    // ADC
+    assert_eq!(arithmetic(0x74, 0xFF, true, 0x01), (0x01, true));
+}
+
+#[test]
+fn shifts_through_df() {
+    // SHRC and SHLC rotate through DF, SHR and SHL shift in a zero
+    assert_eq!(arithmetic(0x76, 0x03, true, 0), (0x81, true));
+    assert_eq!(arithmetic(0x76, 0x02, false, 0), (0x01, false));
+    assert_eq!(arithmetic(0x7E, 0x80, false, 0), (0x00, true));
+    assert_eq!(arithmetic(0x7E, 0x40, true, 0), (0x81, false));
+    assert_eq!(arithmetic(0xF6, 0x03, true, 0), (0x01, true));
+    assert_eq!(arithmetic(0xFE, 0x81, false, 0), (0x02, true));
+}
+
+#[test]
+fn short_branches_replace_the_low_byte() {
+    // BR, BZ, BNZ and SKP, which skips its byte
+    assert_eq!(branch(&[0x30, 0x10], 0), (0x10, 2));
+    assert_eq!(branch(&[0x32, 0x10], 0), (0x10, 2));
+    assert_eq!(branch(&[0x32, 0x10], 1), (0x02, 2));
+    assert_eq!(branch(&[0x3A, 0x10], 1), (0x10, 2));
+    assert_eq!(branch(&[0x38, 0x10], 0), (0x02, 2));
+    // B1 and BN1 test EF1, which the bus asserts
+    assert_eq!(branch(&[0x34, 0x10], 0), (0x10, 2));
+    assert_eq!(branch(&[0x3C, 0x10], 0), (0x02, 2));
+
+    // The page is the one of the target byte
+    let mut bus = TestBus::new(&[]);
+    bus.memory[0xFF] = 0x30;
+    bus.memory[0x100] = 0x20;
+    let mut cpu = Cdp1802::new();
+    cpu.registers[0] = 0xFF;
+    cpu.step(&mut bus);
+    assert_eq!(cpu.program_counter(), 0x120);
+}
+
+#[test]
+fn long_branches_and_skips_take_three_cycles() {
+    // LBR, LBZ, LBNZ
+    assert_eq!(branch(&[0xC0, 0x12, 0x34], 0), (0x1234, 3));
+    assert_eq!(branch(&[0xC2, 0x12, 0x34], 1), (0x0003, 3));
+    assert_eq!(branch(&[0xCA, 0x12, 0x34], 1), (0x1234, 3));
+    // LSKP always skips two bytes, NOP never does
+    assert_eq!(branch(&[0xC8], 0), (0x0003, 3));
+    assert_eq!(branch(&[0xC4], 0), (0x0001, 3));
+    // LSZ, LSNZ and LSIE
+    assert_eq!(branch(&[0xCE], 0), (0x0003, 3));
+    assert_eq!(branch(&[0xCE], 1), (0x0001, 3));
+    assert_eq!(branch(&[0xC6], 1), (0x0003, 3));
+    assert_eq!(branch(&[0xCC], 0), (0x0003, 3));
+}
+
+#[test]
+fn mark_and_ret_call_a_subroutine() {
+    // MARK, SEP 3; the subroutine at 0x50 does SEX 2, INC 2, RET; then DIS at 2
+    let mut bus = TestBus::new(&[0x79, 0xD3, 0x71]);
+    bus.memory[0x50..0x53].copy_from_slice(&[0xE2, 0x12, 0x70]);
+    bus.memory[0x81] = 0x25;
+
+    let mut cpu = Cdp1802::new();
+    cpu.registers[2] = 0x80;
+    cpu.registers[3] = 0x50;
+    cpu.x = 2;
+
+    run(&mut cpu, &mut bus, 2);
+    assert_eq!((cpu.t, bus.memory[0x80]), (0x20, 0x20));
+    assert_eq!((cpu.p, cpu.x, cpu.registers[2]), (3, 0, 0x7F));
+
+    run(&mut cpu, &mut bus, 3);
+    assert_eq!((cpu.p, cpu.x, cpu.registers[2]), (0, 2, 0x81));
+    assert_eq!(cpu.program_counter(), 0x02);
+    assert!(cpu.ie);
+
+    run(&mut cpu, &mut bus, 1);
+    assert_eq!((cpu.p, cpu.x, cpu.registers[2]), (5, 2, 0x82));
+    assert!(!cpu.ie);
+}
+
+#[test]
+fn interrupts_save_x_and_p_for_sav() {
+    // IDL, then SAV once the interrupt moved to R1
+    let mut bus = TestBus::new(&[0x00]);
+    bus.memory[0x30] = 0x78;
+
+    let mut cpu = Cdp1802::new();
+    cpu.registers[1] = 0x30;
+    cpu.registers[2] = DATA_ADDRESS;
+    cpu.x = 7;
+
+    run(&mut cpu, &mut bus, 1);
+    assert!(cpu.is_idle());
+    assert_eq!(run(&mut cpu, &mut bus, 3), 3);
+
+    assert!(cpu.interrupt());
+    assert!(!cpu.is_idle());
+    assert_eq!((cpu.t, cpu.p, cpu.x), (0x70, 1, 2));
+    assert!(!cpu.interrupt());
+
+    run(&mut cpu, &mut bus, 1);
+    assert_eq!(bus.memory[DATA_ADDRESS as usize], 0x70);
+}
+
+#[test]
+fn input_and_output_go_through_r_x() {
+    // SEX 5, OUT 3, INP 2
+    let mut bus = TestBus::new(&[0xE5, 0x63, 0x6A]);
+    bus.memory[DATA_ADDRESS as usize] = 0x99;
+
+    let mut cpu = Cdp1802::new();
+    cpu.registers[5] = DATA_ADDRESS;
+    run(&mut cpu, &mut bus, 3);
+
+    assert_eq!(bus.outputs, [(3, 0x99)]);
+    assert_eq!(cpu.d, 0x42);
+    assert_eq!(bus.memory[DATA_ADDRESS as usize + 1], 0x42);
+}
+
+// Jumps into the ROM, points R1 at the interrupt routine, R2 at the stack and R4 at a counter,
+// then continues with R3 as the program counter, since R0 belongs to the DMA, turns the display
+// on and loops. The interrupt routine points R0 at 0x100 and counts the frames, taking more
+// than the two lines the interrupt is held for.
+const MONITOR: [u8; 0x33] = [
+    0xC0, 0x80, 0x03, // LBR 8003
+    0xF8, 0x80, 0xB1, 0xB3, 0xF8, 0x21, 0xA1, // R1 = 8021
+    0xF8, 0x0F, 0xB2, 0xB4, 0xF8, 0xFF, 0xA2, 0xF8, 0x00, 0xA4, // R2 = 0FFF, R4 = 0F00
+    0xF8, 0x18, 0xA3, 0xD3, // R3 = 8018, SEP 3
+    0xE2, 0x69, // SEX 2, INP 1
+    0x30, 0x1A, // BR 801A
+    0x00, 0x00, 0x00, 0x00, //
+    0x70, // RET
+    0x22, 0x78, // DEC 2, SAV
+    0xF8, 0x01, 0xB0, 0xF8, 0x00, 0xA0, // R0 = 0100
+    0xE4, 0xF0, 0xFC, 0x01, 0x54, 0xE2, // SEX 4, LDX, ADI 1, STR 4, SEX 2
+    0xC4, 0xC4, // NOP, NOP
+    0x30, 0x20, // BR 8020
+];
+
+#[test]
+fn frames_interrupt_once_and_fetch_the_display_by_dma() {
+    let mut vip = CosmacVip::new(&MONITOR, &[]).unwrap();
+    // Each of the 128 lines fetches the next 8 bytes, the last line of every row is shown
+    for line in 0..128 {
+        vip.memory_mut()[0x100 + line * 8] = line as u8;
+    }
+
+    for _ in 0..3 {
+        vip.run_frame();
+    }
+
+    assert_eq!(vip.memory()[0xF00], 3);
+    for y in 0..32 {
+        let row = 4 * y as u8 + 3;
+        for x in 0..8 {
+            let expected = match row & 0x80 >> x != 0 {
+                true => Pixel::On,
+                false => Pixel::Off,
+            };
+            assert_eq!(vip.screen.pixel(x, y), expected, "pixel {} of row {}", x, y);
+        }
+        assert_eq!(vip.screen.pixel(8, y), Pixel::Off);
+    }
+}
+
+#[test]
+fn programs_must_fit_into_memory() {
+    let mut vip = CosmacVip::new(&MONITOR, &[]).unwrap();
+
+    assert!(vip.load_program_to_address(&[1, 2], 0xFFE).is_ok());
+    assert!(matches!(
+        vip.load_program_to_address(&[1, 2], 0xFFF),
+        Err(VipError::DoesNotFit { address: 0xFFF, .. })
+    ));
+    assert!(matches!(
+        vip.load_program_to_address(&[1, 2], usize::MAX),
+        Err(VipError::DoesNotFit { .. })
+    ));
+}
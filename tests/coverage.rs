@@ -0,0 +1,43 @@
+use rust8::coverage::MemoryAccess;
+use rust8::Chip8;
+
+// Stores v0 and v1 at 0x20c, loads v0 back from there and jumps to itself
+const PROGRAM: [u8; 14] = [
+    0xA2, 0x0C, 0xF1, 0x55, 0xA2, 0x0C, 0xF0, 0x65, 0x12, 0x08, 0x00, 0x00, 0x00, 0x00,
+];
+
+fn access(read: bool, written: bool, executed: bool) -> MemoryAccess {
+    MemoryAccess {
+        read,
+        written,
+        executed,
+    }
+}
+
+#[test]
+fn reads_writes_and_executions_are_marked() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&PROGRAM).unwrap();
+    chip8.enable_coverage();
+    for _ in 0..6 {
+        chip8.cycle().unwrap();
+    }
+    let coverage = chip8.disable_coverage().unwrap();
+
+    assert_eq!(
+        coverage.access_ranges(0x200..0x20E),
+        [
+            (0x200..0x20A, access(false, false, true)),
+            (0x20A..0x20C, access(false, false, false)),
+            (0x20C..0x20D, access(true, true, false)),
+            (0x20D..0x20E, access(false, true, false)),
+        ]
+    );
+    assert!(coverage.access(0x1000).is_untouched());
+
+    let mut report = Vec::new();
+    coverage.write_report(&mut report, 0x200..0x20E).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.starts_with("bytes:         14\nexecuted:      10  71.43%\n"));
+    assert!(report.ends_with("0x20c..0x20d rw- 1\n0x20d..0x20e -w- 1\n"));
+}
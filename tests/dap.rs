@@ -0,0 +1,305 @@
+#![cfg(feature = "dap")]
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use rust8::dap::DapServer;
+use serde_json::{json, Value};
+
+// main: v0 := 0, call sub, v0 += 1, jump back to the call
+// sub: v1 := 5, return
+const PROGRAM: [u8; 12] = [
+    0x60, 0x00, 0x22, 0x08, 0x70, 0x01, 0x12, 0x02, 0x61, 0x05, 0x00, 0xEE,
+];
+
+// main.8o ends in in.8o but is a different file
+const SYMBOLS: &str = "\
+0x200 main.8o:1
+0x202 main.8o:2
+0x204 main.8o:3
+0x206 main.8o:4
+0x208 sub
+0x208 main.8o:6
+0x20a main.8o:7
+0x20c in.8o:9
+";
+
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            // A closed channel ends the input
+            self.pending = self.receiver.recv().unwrap_or_default();
+        }
+
+        let count = buf.len().min(self.pending.len());
+        buf[..count].copy_from_slice(&self.pending[..count]);
+        self.pending.drain(..count);
+
+        Ok(count)
+    }
+}
+
+struct ChannelWriter(Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = self.0.send(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Talks to a server on its own thread, one request at a time like an editor would
+struct Client {
+    requests: Sender<Vec<u8>>,
+    output: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    sequence: u64,
+    directory: PathBuf,
+}
+
+impl Client {
+    fn launch(name: &str) -> Self {
+        let directory =
+            std::env::temp_dir().join(format!("rust8-dap-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("main.ch8"), PROGRAM).unwrap();
+        fs::write(directory.join("main.sym"), SYMBOLS).unwrap();
+
+        let (requests, receiver) = mpsc::channel();
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            let reader = ChannelReader {
+                receiver,
+                pending: Vec::new(),
+            };
+            DapServer::new(reader, ChannelWriter(sender)).run().unwrap();
+        });
+
+        let mut client = Client {
+            requests,
+            output,
+            buffer: Vec::new(),
+            sequence: 0,
+            directory,
+        };
+
+        client.request("initialize", json!({}));
+        client.event("initialized");
+        let arguments = json!({
+            "program": client.path("main.ch8"),
+            "symbols": client.path("main.sym"),
+            "source": client.path("main.8o"),
+        });
+        assert_eq!(client.request("launch", arguments)["success"], true);
+
+        client
+    }
+
+    fn path(&self, file: &str) -> String {
+        self.directory.join(file).to_string_lossy().into_owned()
+    }
+
+    fn receive(&mut self) -> Value {
+        loop {
+            let text = String::from_utf8_lossy(&self.buffer).into_owned();
+            if let Some((header, rest)) = text.split_once("\r\n\r\n") {
+                let length: usize = header
+                    .trim_start_matches("Content-Length: ")
+                    .parse()
+                    .unwrap();
+                if rest.len() >= length {
+                    let message = serde_json::from_str(&rest[..length]).unwrap();
+                    self.buffer.drain(..header.len() + 4 + length);
+                    return message;
+                }
+            }
+
+            let chunk = self
+                .output
+                .recv_timeout(Duration::from_secs(10))
+                .expect("the server did not answer");
+            self.buffer.extend(chunk);
+        }
+    }
+
+    // Skips events until the response to the request comes
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.sequence += 1;
+        let content = json!({
+            "seq": self.sequence,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+        self.requests.send(message.into_bytes()).unwrap();
+
+        loop {
+            let message = self.receive();
+            if message["type"] == "response" && message["request_seq"] == self.sequence {
+                return message;
+            }
+        }
+    }
+
+    fn event(&mut self, event: &str) -> Value {
+        loop {
+            let message = self.receive();
+            if message["type"] == "event" && message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn stopped(&mut self) -> String {
+        self.event("stopped")["reason"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn stack_trace(&mut self) -> Vec<(String, String, u64)> {
+        let response = self.request("stackTrace", json!({ "threadId": 1 }));
+
+        response["body"]["stackFrames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| {
+                (
+                    frame["name"].as_str().unwrap().to_string(),
+                    frame["instructionPointerReference"]
+                        .as_str()
+                        .unwrap()
+                        .to_string(),
+                    frame["line"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+fn frame(name: &str, address: &str, line: u64) -> (String, String, u64) {
+    (name.to_string(), address.to_string(), line)
+}
+
+#[test]
+fn breakpoints_resolve_lines_of_the_source_only() {
+    let mut client = Client::launch("breakpoints");
+
+    let arguments = json!({
+        "source": { "path": client.path("main.8o") },
+        "breakpoints": [{ "line": 5 }, { "line": 9 }],
+    });
+    let response = client.request("setBreakpoints", arguments);
+    let breakpoints = &response["body"]["breakpoints"];
+
+    // Line 5 has no code and moves to the next line that has
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[0]["line"], 6);
+    assert_eq!(breakpoints[0]["instructionReference"], "0x208");
+    assert_eq!(breakpoints[1]["verified"], false);
+}
+
+#[test]
+fn stops_report_the_stack_and_stepping_goes_on_from_them() {
+    let mut client = Client::launch("stepping");
+    let arguments = json!({
+        "source": { "path": client.path("main.8o") },
+        "breakpoints": [{ "line": 6 }],
+    });
+    client.request("setBreakpoints", arguments);
+
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.stopped(), "breakpoint");
+    assert_eq!(
+        client.stack_trace(),
+        [frame("sub", "0x208", 6), frame("main", "0x202", 2)]
+    );
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.stack_trace()[0], frame("sub", "0x20a", 7));
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.stopped(), "step");
+    assert_eq!(client.stack_trace(), [frame("main", "0x204", 3)]);
+
+    let response = client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(response["body"]["allThreadsContinued"], true);
+    assert_eq!(client.stopped(), "breakpoint");
+    assert_eq!(client.stack_trace()[0], frame("sub", "0x208", 6));
+
+    let response = client.request(
+        "evaluate",
+        json!({ "expression": "v0", "context": "watch" }),
+    );
+    assert_eq!(response["body"]["result"], "1 (0x1)");
+}
+
+#[test]
+fn memory_reads_stop_at_the_end_of_memory() {
+    let mut client = Client::launch("memory");
+
+    let response = client.request(
+        "readMemory",
+        json!({ "memoryReference": "0x200", "count": 2 }),
+    );
+    assert_eq!(response["body"]["data"], "YAA=");
+    assert_eq!(response["body"]["unreadableBytes"], 0);
+
+    let response = client.request(
+        "readMemory",
+        json!({ "memoryReference": "0xff0", "offset": 14, "count": 4 }),
+    );
+    assert_eq!(response["body"]["address"], "0xffe");
+    assert_eq!(response["body"]["data"], "AAA=");
+    assert_eq!(response["body"]["unreadableBytes"], 2);
+
+    let response = client.request("readMemory", json!({ "memoryReference": "x" }));
+    assert_eq!(response["success"], false);
+}
+
+#[test]
+fn disassembly_is_labelled_and_marks_what_is_outside_of_memory() {
+    let mut client = Client::launch("disassemble");
+
+    let response = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0x206", "instructionCount": 2 }),
+    );
+    let instructions = &response["body"]["instructions"];
+    assert_eq!(instructions[0]["address"], "0x206");
+    assert_eq!(instructions[0]["instructionBytes"], "1202");
+    assert_eq!(instructions[0]["line"], 4);
+    assert_eq!(instructions[1]["symbol"], "sub");
+    assert_eq!(instructions[1]["instructionBytes"], "6105");
+
+    let response = client.request(
+        "disassemble",
+        json!({ "memoryReference": "0xffe", "instructionCount": 2 }),
+    );
+    let instructions = &response["body"]["instructions"];
+    assert_eq!(instructions[0]["address"], "0xffe");
+    assert_eq!(instructions[1]["presentationHint"], "invalid");
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "database")]
+
+use rust8::database::{program_hash, Database};
+use rust8::platform::Platform;
+use rust8::quirks::Quirks;
+
+const PROGRAMS: &str = r#"[
+    {
+        "title": "Alphabet",
+        "authors": ["Someone"],
+        "roms": {
+            "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                "file": "abc.ch8",
+                "platforms": ["superchip"],
+                "quirkyPlatforms": { "superchip": { "wrap": true } },
+                "tickrate": 30
+            }
+        }
+    }
+]"#;
+
+#[test]
+fn programs_are_found_by_their_sha1() {
+    let database = Database::from_json(PROGRAMS, None).unwrap();
+    assert_eq!(
+        program_hash(b"abc"),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+
+    let config = database.lookup(b"abc").unwrap();
+    assert_eq!(config.title, "Alphabet");
+    assert_eq!(config.file.as_deref(), Some("abc.ch8"));
+    assert_eq!(config.platform, Platform::SuperChip);
+    assert_eq!(
+        config.quirks,
+        Quirks {
+            wrap: true,
+            ..Quirks::super_chip()
+        }
+    );
+    assert_eq!(config.tickrate, Some(30));
+
+    assert_eq!(
+        database.lookup_hash("a9993e364706816aba3e25717850c26c9cd0d89d"),
+        Some(config)
+    );
+    assert!(database.lookup(b"abd").is_none());
+}
+
+#[test]
+fn platforms_file_overrides_the_built_in_quirks() {
+    let platforms = r#"[{ "id": "superchip", "quirks": { "logic": true } }]"#;
+    let database = Database::from_json(PROGRAMS, Some(platforms)).unwrap();
+
+    let quirks = database.lookup(b"abc").unwrap().quirks;
+    assert!(quirks.logic);
+    assert!(quirks.wrap);
+}
@@ -0,0 +1,47 @@
+use rust8::detector::Detector;
+use rust8::graphic::EventDisplay;
+use rust8::quirks::QuirksProfile;
+
+fn reasons(program: &[u8], profile: QuirksProfile) -> Vec<String> {
+    Detector::new()
+        .detect(program)
+        .into_iter()
+        .find(|candidate| candidate.profile == profile)
+        .unwrap()
+        .reasons
+}
+
+#[test]
+fn reachable_opcodes_of_newer_platforms_count() {
+    // 00FF enables the high resolution mode of the SUPER-CHIP
+    let program = [0x00, 0xFF, 0x12, 0x02];
+
+    assert!(reasons(&program, QuirksProfile::SuperChip)
+        .contains(&"opcode 00ff at 0x200 requires SUPER-CHIP".to_string()));
+    assert!(reasons(&program, QuirksProfile::CosmacVip).is_empty());
+}
+
+#[test]
+fn data_that_looks_like_newer_opcodes_is_ignored() {
+    // Loops forever in front of a sprite whose rows read 00FF
+    let program = [0x12, 0x00, 0x00, 0xFF];
+
+    for profile in QuirksProfile::ALL {
+        assert!(reasons(&program, profile).is_empty(), "{}", profile);
+    }
+}
+
+#[test]
+fn trials_run_on_any_display() {
+    // Draws the font sprite of 0 and loops
+    let program = [0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04];
+    let candidates = Detector::<EventDisplay>::default()
+        .with_trial_runs(10)
+        .detect(&program);
+
+    for candidate in candidates {
+        assert!(candidate
+            .reasons
+            .contains(&"ran 10 frames without errors".to_string()));
+    }
+}
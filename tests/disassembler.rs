@@ -0,0 +1,51 @@
+use rust8::disassembler::{ByteKind, RecursiveDisassembly};
+
+// Draws the sprite at 0x20c, calls a subroutine unless v0 is 0 and jumps to itself, the two
+// bytes after the sprite are never reached
+const PROGRAM: [u8; 16] = [
+    0xA2, 0x0C, 0xD0, 0x12, 0x30, 0x00, 0x22, 0x0A, 0x12, 0x08, 0x00, 0xEE, 0xFF, 0x81, 0x12, 0x34,
+];
+
+#[test]
+fn code_is_told_apart_from_data() {
+    let disassembly = RecursiveDisassembly::from_program(&PROGRAM);
+
+    let kinds: Vec<ByteKind> = disassembly
+        .range()
+        .map(|address| disassembly.kind(address).unwrap())
+        .collect();
+    let mut expected = vec![ByteKind::Code; 12];
+    expected.extend([ByteKind::Sprite, ByteKind::Sprite]);
+    expected.extend([ByteKind::Unknown, ByteKind::Unknown]);
+    assert_eq!(kinds, expected);
+
+    assert_eq!(disassembly.instructions().count(), 6);
+    assert!(disassembly.instruction(0x20E).is_none());
+    assert_eq!(disassembly.labels().label(0x20A), Some("sub_20a"));
+    assert_eq!(disassembly.labels().label(0x20C), Some("sprite_20c"));
+
+    let mut listing = Vec::new();
+    disassembly.write_listing(&mut listing).unwrap();
+    assert_eq!(
+        String::from_utf8(listing).unwrap(),
+        "
+: main
+\ti := sprite_20c
+\tsprite v0 v1 2
+\tif v0 != 0x00 then
+\t:call sub_20a
+
+: label_208
+\tjump label_208
+
+: sub_20a
+\treturn
+
+: sprite_20c
+\t0xff  # ########
+\t0x81  # #......#
+\t0x12
+\t0x34
+"
+    );
+}
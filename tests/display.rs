@@ -0,0 +1,150 @@
+use rust8::graphic::{
+    BitSlicePixelView, Display, DisplayEvent, EventDisplay, Pixel, PixelView, Resolution, Screen,
+    Scroll,
+};
+use rust8::Chip8;
+
+// Clears the screen, draws the font sprite of 0 at 0,0 and jumps to itself
+const PROGRAM: [u8; 8] = [0x00, 0xE0, 0xA0, 0x00, 0xD0, 0x05, 0x12, 0x06];
+
+// Keeps count of what the emulator asked for
+#[derive(Default)]
+struct CountingDisplay {
+    screen: Screen,
+    clears: usize,
+    sprites: usize,
+    presents: usize,
+}
+
+impl Display for CountingDisplay {
+    fn clear(&mut self) {
+        self.clears += 1;
+        self.screen.clear();
+    }
+
+    fn draw_sprite(&mut self, x: usize, y: usize, sprite: &dyn PixelView, wrap: bool) -> bool {
+        self.sprites += 1;
+        self.screen.draw_sprite(x, y, sprite, wrap)
+    }
+
+    fn scroll(&mut self, scroll: Scroll) {
+        self.screen.scroll(scroll);
+    }
+
+    fn set_resolution(&mut self, _resolution: Resolution) {}
+
+    fn resolution(&self) -> Resolution {
+        Resolution::Low
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Pixel {
+        self.screen.pixel(x, y)
+    }
+
+    fn present(&mut self) {
+        self.presents += 1;
+    }
+}
+
+fn lit(display: &dyn Display) -> Vec<(usize, usize)> {
+    let resolution = display.resolution();
+
+    (0..resolution.height())
+        .flat_map(|y| (0..resolution.width()).map(move |x| (x, y)))
+        .filter(|(x, y)| display.pixel(*x, *y) == Pixel::On)
+        .collect()
+}
+
+#[test]
+fn frontends_provide_their_own_display() {
+    let mut chip8 = Chip8::with_display(CountingDisplay::default());
+    chip8.load_program(&PROGRAM).unwrap();
+    chip8.run_frame().unwrap();
+    chip8.run_frame().unwrap();
+
+    // Loading clears the screen as well
+    assert_eq!(chip8.screen.clears, 2);
+    assert_eq!(chip8.screen.sprites, 1);
+    assert_eq!(chip8.screen.presents, 2);
+    assert_eq!(chip8.screen.pixel(0, 0), Pixel::On);
+}
+
+#[test]
+fn scrolling_moves_pixels_and_drops_what_leaves_the_screen() {
+    let mut screen = Screen::new();
+    let rows = [0b1000_0001];
+    screen.draw_sprite(
+        0,
+        0,
+        &BitSlicePixelView::new_from_byte_slice(&rows, 8, 1),
+        false,
+    );
+
+    screen.scroll(Scroll::Right(4));
+    assert_eq!(lit(&screen), [(4, 0), (11, 0)]);
+
+    screen.scroll(Scroll::Down(3));
+    assert_eq!(lit(&screen), [(4, 3), (11, 3)]);
+
+    screen.scroll(Scroll::Left(6));
+    assert_eq!(lit(&screen), [(5, 3)]);
+
+    screen.scroll(Scroll::Up(4));
+    assert!(lit(&screen).is_empty());
+}
+
+#[test]
+fn sprites_are_clipped_unless_they_wrap() {
+    let rows = [0xFF, 0xFF];
+    let sprite = BitSlicePixelView::new_from_byte_slice(&rows, 8, 2);
+
+    let mut screen = Screen::new();
+    assert!(!screen.draw_sprite(60, 31, &sprite, false));
+    assert_eq!(lit(&screen), [(60, 31), (61, 31), (62, 31), (63, 31)]);
+
+    let mut screen = Screen::new();
+    assert!(!screen.draw_sprite(60, 31, &sprite, true));
+    let pixels = lit(&screen);
+    assert_eq!(pixels.len(), 16);
+    assert!(pixels.contains(&(0, 0)));
+    assert!(pixels.contains(&(3, 31)));
+
+    // Without wrapping only the pixels inside the screen are drawn again and erased
+    assert!(screen.draw_sprite(60, 31, &sprite, false));
+    assert_eq!(lit(&screen).len(), 12);
+    assert!(!lit(&screen).contains(&(60, 31)));
+}
+
+#[test]
+fn event_display_records_and_passes_everything_on() {
+    let mut chip8: Chip8<EventDisplay> = Chip8::default();
+    chip8.load_program(&PROGRAM).unwrap();
+    chip8.screen.drain_events().for_each(drop);
+
+    chip8.run_frame().unwrap();
+
+    let events: Vec<DisplayEvent> = chip8.screen.drain_events().collect();
+    let font_zero = [0xF0, 0x90, 0x90, 0x90, 0xF0];
+    let pixels: Vec<Pixel> = font_zero
+        .iter()
+        .flat_map(|row| (0..8).map(move |bit| Pixel::from(row & 0x80 >> bit != 0)))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            DisplayEvent::Clear,
+            DisplayEvent::Sprite {
+                x: 0,
+                y: 0,
+                width: 8,
+                height: 5,
+                pixels,
+                wrap: true,
+                collision: false,
+            },
+            DisplayEvent::Present,
+        ]
+    );
+    assert!(!chip8.screen.has_events());
+    assert_eq!(lit(chip8.screen.display()).len(), 14);
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+use rust8::data_register::DataRegister;
+use rust8::events::Event;
+use rust8::keyboard::Key;
+use rust8::Chip8;
+
+// Clears the screen, draws the same row twice, calls a subroutine that returns right away and
+// jumps to itself
+const PROGRAM: [u8; 15] = [
+    0x00, 0xE0, 0xA2, 0x0E, 0xD0, 0x11, 0xD0, 0x11, 0x22, 0x0C, 0x12, 0x0A, 0x00, 0xEE, 0xF0,
+];
+
+// v0 := 2, sound := v0, v1 := key, jump to itself
+const SOUND_PROGRAM: [u8; 8] = [0x60, 0x02, 0xF0, 0x18, 0xF1, 0x0A, 0x12, 0x06];
+
+fn expected_events() -> Vec<Event> {
+    vec![
+        Event::ScreenCleared,
+        Event::SpriteDrawn {
+            x: 0,
+            y: 0,
+            rows: vec![0xF0],
+            collision: false,
+        },
+        Event::SpriteDrawn {
+            x: 0,
+            y: 0,
+            rows: vec![0xF0],
+            collision: true,
+        },
+        Event::SubroutineCalled {
+            from: 0x208,
+            to: 0x20C,
+        },
+        Event::SubroutineReturned { to: 0x20A },
+        Event::Halted { address: 0x20A },
+    ]
+}
+
+fn run(chip8: &mut Chip8, program: &[u8], cycles: usize) {
+    chip8.load_program(program).unwrap();
+    for _ in 0..cycles {
+        chip8.cycle().unwrap();
+    }
+}
+
+#[test]
+fn queued_events_are_drained_in_order() {
+    let mut chip8 = Chip8::new();
+    chip8.enable_events();
+    run(&mut chip8, &PROGRAM, 10);
+
+    // The jump to itself ran four times but is reported once
+    assert_eq!(chip8.drain_events().collect::<Vec<_>>(), expected_events());
+    assert_eq!(chip8.drain_events().count(), 0);
+}
+
+#[test]
+fn callbacks_get_the_events_instead_of_the_queue() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&received);
+
+    let mut chip8 = Chip8::new();
+    chip8.set_event_callback(move |event| sink.lock().unwrap().push(event.clone()));
+    run(&mut chip8, &PROGRAM, 10);
+
+    assert_eq!(chip8.drain_events().count(), 0);
+    assert_eq!(*received.lock().unwrap(), expected_events());
+}
+
+#[test]
+fn sound_stops_once_the_held_key_is_released() {
+    let mut chip8 = Chip8::new();
+    chip8.enable_events();
+    run(&mut chip8, &SOUND_PROGRAM, 3);
+    chip8.key_down(Key::Num5);
+
+    // The timer runs out while the key still sounds the buzzer
+    chip8.update_timers();
+    chip8.update_timers();
+    assert_eq!(chip8.sound_timer, 0);
+    assert!(chip8.is_sound_playing());
+    assert_eq!(
+        chip8.drain_events().collect::<Vec<_>>(),
+        [Event::SoundStarted, Event::WaitingForKey(DataRegister::V1)]
+    );
+
+    chip8.key_up(Key::Num5);
+    assert!(!chip8.is_sound_playing());
+    assert_eq!(
+        chip8.drain_events().collect::<Vec<_>>(),
+        [
+            Event::SoundStopped,
+            Event::KeyConsumed {
+                key: Key::Num5,
+                register: DataRegister::V1
+            }
+        ]
+    );
+}
@@ -0,0 +1,116 @@
+use rust8::data_register::DataRegister;
+use rust8::expression::{EvaluationError, Expression, ExpressionError, LogMessage};
+use rust8::Chip8;
+
+fn chip8() -> Chip8 {
+    let mut chip8 = Chip8::new();
+    // v0 := 0x12, i := 0x300
+    chip8.load_program(&[0x60, 0x12, 0xA3, 0x00]).unwrap();
+    chip8.memory.raw_data[0x300] = 0xAB;
+    chip8.memory.raw_data[0x301] = 0x02;
+    chip8.data_registers[DataRegister::VA] = 3;
+    chip8.delay_timer = 7;
+    chip8
+}
+
+fn evaluate(source: &str) -> Result<i64, EvaluationError> {
+    Expression::parse(source).unwrap().evaluate(&chip8())
+}
+
+#[test]
+fn operators_follow_the_precedence_of_c() {
+    assert_eq!(evaluate("1 + 2 * 3"), Ok(7));
+    assert_eq!(evaluate("(1 + 2) * 3"), Ok(9));
+    assert_eq!(evaluate("10 - 4 - 3"), Ok(3));
+    assert_eq!(evaluate("1 << 2 + 1"), Ok(8));
+    assert_eq!(evaluate("6 & 3 == 3"), Ok(0));
+    assert_eq!(evaluate("1 | 2 ^ 3 & 1"), Ok(3));
+    assert_eq!(evaluate("0 || 2 && 3"), Ok(1));
+    assert_eq!(evaluate("-2 * -3"), Ok(6));
+    assert_eq!(evaluate("!0 + ~0"), Ok(0));
+}
+
+#[test]
+fn registers_are_read_from_the_machine() {
+    assert_eq!(evaluate("v0"), Ok(0));
+    assert_eq!(evaluate("VA + dt"), Ok(10));
+    assert_eq!(evaluate("pc"), Ok(0x200));
+    assert_eq!(evaluate("opcode"), Ok(0x6012));
+    assert_eq!(evaluate("sp"), Ok(0));
+    assert_eq!(
+        Expression::parse("vg"),
+        Err(ExpressionError::UnknownIdentifier("vg".to_string()))
+    );
+}
+
+#[test]
+fn memory_is_read_with_brackets() {
+    assert_eq!(evaluate("[0x300]"), Ok(0xAB));
+    assert_eq!(evaluate("[0x300 + [0x301] - 1]"), Ok(0x02));
+    assert_eq!(
+        evaluate("[0x1000]"),
+        Err(EvaluationError::MemoryOutOfRange(0x1000))
+    );
+    assert_eq!(evaluate("[-1]"), Err(EvaluationError::MemoryOutOfRange(-1)));
+}
+
+#[test]
+fn division_by_zero_is_an_error() {
+    assert_eq!(
+        evaluate("1 / (va - 3)"),
+        Err(EvaluationError::DivisionByZero)
+    );
+    assert_eq!(evaluate("1 % 0"), Err(EvaluationError::DivisionByZero));
+    assert_eq!(evaluate("0 && 1 / 0"), Ok(0));
+}
+
+#[test]
+fn nesting_is_limited() {
+    let nested = format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000));
+    assert!(matches!(
+        Expression::parse(&nested),
+        Err(ExpressionError::TooDeeplyNested(_))
+    ));
+
+    assert!(matches!(
+        Expression::parse(&"-".repeat(10_000)),
+        Err(ExpressionError::TooDeeplyNested(_))
+    ));
+
+    let chain = vec!["1"; 10_000].join(" + ");
+    assert!(matches!(
+        Expression::parse(&chain),
+        Err(ExpressionError::TooDeeplyNested(_))
+    ));
+
+    assert_eq!(
+        Expression::parse(&format!("{}1{}", "(".repeat(20), ")".repeat(20)))
+            .unwrap()
+            .evaluate(&chip8()),
+        Ok(1)
+    );
+}
+
+#[test]
+fn log_messages_format_their_placeholders() {
+    let message = LogMessage::parse("v0={v0} i={i:x} va={va:b} {{literal}}").unwrap();
+    let mut chip8 = chip8();
+    chip8.cycle().unwrap();
+    chip8.cycle().unwrap();
+
+    assert_eq!(message.format(&chip8), "v0=18 i=0x300 va=0b11 {literal}");
+}
+
+#[test]
+fn log_messages_show_evaluation_errors_inline() {
+    let message = LogMessage::parse("value: {[0x2000]}").unwrap();
+
+    assert_eq!(
+        message.format(&chip8()),
+        "value: <memory address 0x2000 is outside of the memory>"
+    );
+    assert_eq!(
+        LogMessage::parse("{v0"),
+        Err(ExpressionError::UnterminatedPlaceholder)
+    );
+}
@@ -0,0 +1,251 @@
+use std::sync::{Arc, Mutex};
+
+use rust8::cpu::execute::InstructionExecutionError;
+use rust8::cpu::CycleError;
+use rust8::data_register::DataRegister;
+use rust8::events::Event;
+use rust8::extension::{
+    ExtensionError, OpcodeExtension, ProgramCounterUpdate, UnknownOpcodePolicy,
+};
+use rust8::instruction::parser::InstructionParsingError;
+use rust8::memory::ReadInstructionError;
+use rust8::profiler::CUSTOM_OPCODE_NAME;
+use rust8::Chip8;
+
+// 8XYF: vx := vx * vy, failing on overflow
+struct Multiply;
+
+impl OpcodeExtension for Multiply {
+    type Instruction = (DataRegister, DataRegister);
+
+    fn mask(&self) -> u16 {
+        0xF00F
+    }
+
+    fn pattern(&self) -> u16 {
+        0x800F
+    }
+
+    fn decode(&self, opcode: u16) -> Option<Self::Instruction> {
+        let vx = DataRegister::try_from((opcode >> 8 & 0xF) as u8).ok()?;
+        let vy = DataRegister::try_from((opcode >> 4 & 0xF) as u8).ok()?;
+        Some((vx, vy))
+    }
+
+    fn execute(
+        &mut self,
+        chip8: &mut Chip8,
+        (vx, vy): Self::Instruction,
+    ) -> Result<ProgramCounterUpdate, ExtensionError> {
+        let product = chip8.data_registers[vx]
+            .checked_mul(chip8.data_registers[vy])
+            .ok_or("multiplication overflowed")?;
+        chip8.data_registers[vx] = product;
+        Ok(ProgramCounterUpdate::Advance)
+    }
+}
+
+// 00FF: jumps to the address in i, taking the place of the machine code routine
+struct JumpToI;
+
+impl OpcodeExtension for JumpToI {
+    type Instruction = ();
+
+    fn mask(&self) -> u16 {
+        0xFFFF
+    }
+
+    fn pattern(&self) -> u16 {
+        0x00FF
+    }
+
+    fn decode(&self, _: u16) -> Option<Self::Instruction> {
+        Some(())
+    }
+
+    fn execute(
+        &mut self,
+        chip8: &mut Chip8,
+        _: Self::Instruction,
+    ) -> Result<ProgramCounterUpdate, ExtensionError> {
+        chip8.program_counter = chip8.address_register;
+        Ok(ProgramCounterUpdate::Handled)
+    }
+}
+
+// 00FE: waits at the opcode until v0 is no longer 0, counting down v0 and v1 until then
+struct WaitForV0;
+
+impl OpcodeExtension for WaitForV0 {
+    type Instruction = ();
+
+    fn mask(&self) -> u16 {
+        0xFFFF
+    }
+
+    fn pattern(&self) -> u16 {
+        0x00FE
+    }
+
+    fn decode(&self, _: u16) -> Option<Self::Instruction> {
+        Some(())
+    }
+
+    fn execute(
+        &mut self,
+        chip8: &mut Chip8,
+        _: Self::Instruction,
+    ) -> Result<ProgramCounterUpdate, ExtensionError> {
+        if chip8.data_registers[DataRegister::V0] != 0 {
+            return Ok(ProgramCounterUpdate::Advance);
+        }
+
+        chip8.data_registers[DataRegister::V1] -= 1;
+        if chip8.data_registers[DataRegister::V1] == 0 {
+            chip8.data_registers[DataRegister::V0] = 1;
+        }
+        Ok(ProgramCounterUpdate::Handled)
+    }
+}
+
+fn chip8_with(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(program).unwrap();
+    chip8
+}
+
+#[test]
+fn extension_executes_its_opcodes() {
+    // v0 := 6, v1 := 7, v0 *= v1
+    let mut chip8 = chip8_with(&[0x60, 0x06, 0x61, 0x07, 0x80, 0x1F]);
+    chip8.register_extension(Multiply);
+
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+
+    assert_eq!(chip8.data_registers[DataRegister::V0], 42);
+    assert_eq!(chip8.program_counter, 0x206);
+}
+
+#[test]
+fn extension_errors_stop_the_machine() {
+    // v0 := 100, v1 := 3, v0 *= v1
+    let mut chip8 = chip8_with(&[0x60, 0x64, 0x61, 0x03, 0x80, 0x1F]);
+    chip8.register_extension(Multiply);
+
+    for _ in 0..2 {
+        chip8.cycle().unwrap();
+    }
+
+    match chip8.cycle().unwrap_err() {
+        CycleError::ExecutionError(InstructionExecutionError::ExtensionFailed(opcode, error)) => {
+            assert_eq!(opcode, 0x801F);
+            assert_eq!(error.0, "multiplication overflowed");
+        }
+        error => panic!("unexpected error {error}"),
+    }
+    assert_eq!(chip8.program_counter, 0x204);
+}
+
+#[test]
+fn extension_comes_before_built_in_instructions() {
+    // i := 0x206, 00FF, v0 := 1, v0 := 2
+    let mut chip8 = chip8_with(&[0xA2, 0x06, 0x00, 0xFF, 0x60, 0x01, 0x60, 0x02]);
+    chip8.register_extension(JumpToI);
+
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+
+    assert_eq!(chip8.data_registers[DataRegister::V0], 2);
+    assert_eq!(chip8.program_counter, 0x208);
+}
+
+#[test]
+fn unknown_opcodes_are_errors_by_default() {
+    let mut chip8 = chip8_with(&[0x80, 0x1F]);
+
+    assert!(matches!(
+        chip8.cycle(),
+        Err(CycleError::FetchError(ReadInstructionError::ParseError(
+            InstructionParsingError::InvalidInstruction(0x801F)
+        )))
+    ));
+}
+
+#[test]
+fn unknown_opcodes_can_be_ignored() {
+    // 801F, v0 := 1
+    let mut chip8 = chip8_with(&[0x80, 0x1F, 0x60, 0x01]);
+    chip8.set_unknown_opcode_policy(UnknownOpcodePolicy::Ignore);
+
+    chip8.cycle().unwrap();
+    chip8.cycle().unwrap();
+
+    assert_eq!(chip8.data_registers[DataRegister::V0], 1);
+}
+
+#[test]
+fn unknown_opcodes_go_to_the_callback() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    // 5AB1, 801F, v0 := 1
+    let mut chip8 = chip8_with(&[0x5A, 0xB1, 0x80, 0x1F, 0x60, 0x01]);
+    chip8.register_extension(Multiply);
+
+    let log = seen.clone();
+    chip8.set_unknown_opcode_handler(move |chip8, opcode| {
+        log.lock().unwrap().push(opcode);
+        // Skips the following instruction as well
+        chip8.program_counter += 2;
+        Ok(ProgramCounterUpdate::Advance)
+    });
+
+    chip8.cycle().unwrap();
+    chip8.cycle().unwrap();
+
+    assert_eq!(*seen.lock().unwrap(), vec![0x5AB1]);
+    assert_eq!(chip8.data_registers[DataRegister::V0], 1);
+}
+
+#[test]
+fn extensions_can_wait_at_their_opcode() {
+    // v1 := 2, 00FE, v2 := 1
+    let mut chip8 = chip8_with(&[0x61, 0x02, 0x00, 0xFE, 0x62, 0x01]);
+    chip8.register_extension(WaitForV0);
+    chip8.enable_events();
+
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.program_counter, 0x202);
+    }
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.program_counter, 0x204);
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.data_registers[DataRegister::V2], 1);
+
+    let halts: Vec<Event> = chip8
+        .drain_events()
+        .filter(|event| matches!(event, Event::Halted { .. }))
+        .collect();
+    assert_eq!(halts, [Event::Halted { address: 0x202 }]);
+}
+
+#[test]
+fn extension_opcodes_are_profiled() {
+    // v0 := 6, v1 := 7, v0 *= v1, i := 0x200, 00FF
+    let mut chip8 = chip8_with(&[0x60, 0x06, 0x61, 0x07, 0x80, 0x1F, 0xA2, 0x00, 0x00, 0xFF]);
+    chip8.register_extension(Multiply);
+    chip8.register_extension(JumpToI);
+    chip8.enable_profiler();
+
+    for _ in 0..10 {
+        chip8.cycle().unwrap();
+    }
+
+    let profiler = chip8.profiler().unwrap();
+    assert_eq!(profiler.total_instructions(), 10);
+    assert_eq!(profiler.instruction_count(CUSTOM_OPCODE_NAME), 4);
+    assert_eq!(profiler.address_count(0x204), 2);
+    assert_eq!(profiler.address_count(0x208), 2);
+}
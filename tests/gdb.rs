@@ -0,0 +1,189 @@
+use std::io::{self, Cursor, Read, Write};
+
+use rust8::data_register::DataRegister;
+use rust8::gdb::{Connection, GdbServer};
+use rust8::Chip8;
+
+// Plays back what the debugger sent and records the replies
+struct ScriptedConnection {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+fn framed(packets: &[&[u8]]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for packet in packets {
+        let checksum = packet.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        input.push(b'$');
+        input.extend_from_slice(packet);
+        input.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+    }
+
+    input
+}
+
+impl ScriptedConnection {
+    fn new(input: Vec<u8>) -> Self {
+        ScriptedConnection {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Read for ScriptedConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for ScriptedConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The debugger interrupts a running machine as soon as it is asked whether to
+impl Connection for ScriptedConnection {
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+fn run(chip8: &mut Chip8, input: Vec<u8>) -> String {
+    let mut server = GdbServer::new(chip8, ScriptedConnection::new(input));
+    server.run().unwrap();
+
+    String::from_utf8_lossy(&server.into_connection().output).into_owned()
+}
+
+fn replies(packets: &[&[u8]]) -> String {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&[0x60, 0x01]).unwrap();
+
+    run(&mut chip8, framed(packets))
+}
+
+// Strips the acknowledgements and checksums off the replies
+fn payloads(output: &str) -> Vec<&str> {
+    output
+        .split('$')
+        .skip(1)
+        .map(|packet| packet.split_once('#').unwrap().0)
+        .collect()
+}
+
+// v0 := 60, delay := v0, v0 += 1 forever
+const COUNTING_PROGRAM: [u8; 8] = [0x60, 0x3C, 0xF0, 0x15, 0x70, 0x01, 0x12, 0x04];
+
+#[test]
+fn non_ascii_command_gets_an_empty_reply() {
+    assert_eq!(replies(&[b"\xffx", b"\xc3\xa9"]), "+$#00+$#00");
+}
+
+#[test]
+fn non_ascii_arguments_are_rejected() {
+    assert_eq!(replies(&[b"m\xff,2"]), "+$E01#a6");
+}
+
+#[test]
+fn known_commands_still_work() {
+    assert_eq!(replies(&[b"?", b"m200,2"]), "+$S05#b8+$6001#c7");
+}
+
+#[test]
+fn registers_are_read_and_written_as_a_block() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&COUNTING_PROGRAM).unwrap();
+
+    // v0 to vf, i, pc, dt, st and sp, little endian
+    let written = format!("12{}45030402050600", "00".repeat(15));
+    let output = run(
+        &mut chip8,
+        framed(&[b"g", format!("G{}", written).as_bytes(), b"g"]),
+    );
+
+    assert_eq!(
+        payloads(&output),
+        [
+            format!("{}00000002000000", "00".repeat(16)).as_str(),
+            "OK",
+            written.as_str()
+        ]
+    );
+    assert_eq!(chip8.data_registers[DataRegister::V0], 0x12);
+    assert_eq!(chip8.address_register, 0x345);
+    assert_eq!(chip8.program_counter, 0x204);
+    assert_eq!((chip8.delay_timer, chip8.sound_timer), (5, 6));
+}
+
+#[test]
+fn memory_is_read_and_written_within_bounds() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&COUNTING_PROGRAM).unwrap();
+
+    let output = run(
+        &mut chip8,
+        framed(&[
+            b"M300,2:abcd",
+            b"m300,3",
+            b"mfff,2",
+            b"m1000,1",
+            b"Mfff,2:0102",
+            b"M300,2:ab",
+        ]),
+    );
+
+    assert_eq!(
+        payloads(&output),
+        ["OK", "abcd00", "00", "E01", "E01", "E01"]
+    );
+    assert_eq!(chip8.memory.raw_data[0x300..0x302], [0xAB, 0xCD]);
+    assert_eq!(chip8.memory.raw_data[0xFFF], 0x00);
+}
+
+#[test]
+fn breakpoints_stop_continue_until_they_are_removed() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&COUNTING_PROGRAM).unwrap();
+
+    let output = run(
+        &mut chip8,
+        framed(&[b"Z0,204,2", b"c", b"z0,204,2", b"s", b"s"]),
+    );
+
+    assert_eq!(
+        payloads(&output),
+        ["OK", "T05swbreak:;", "OK", "S05", "S05"]
+    );
+    assert_eq!(chip8.program_counter, 0x204);
+    assert_eq!(chip8.data_registers[DataRegister::V0], 61);
+}
+
+#[test]
+fn continue_runs_whole_frames_until_interrupted() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&COUNTING_PROGRAM).unwrap();
+    chip8.instructions_per_frame = 1000;
+
+    let output = run(&mut chip8, framed(&[b"c"]));
+
+    // The interrupt comes after 1024 instructions, one frame and a bit
+    assert_eq!(payloads(&output), ["S02"]);
+    assert_eq!(chip8.delay_timer, 59);
+}
+
+#[test]
+fn packets_with_a_wrong_checksum_are_rejected() {
+    let mut input = b"$m200,2#00".to_vec();
+    input.extend(framed(&[b"m200,2"]));
+
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&COUNTING_PROGRAM).unwrap();
+
+    assert_eq!(run(&mut chip8, input), "-+$603c#fc");
+}
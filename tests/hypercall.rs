@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+
+use rust8::cpu::execute::InstructionExecutionError;
+use rust8::cpu::CycleError;
+use rust8::data_register::DataRegister;
+use rust8::Chip8;
+
+// Fails the ROM unless v0 equals v1
+fn assert_v0_equals_v1(chip8: &mut Chip8) -> Result<(), rust8::hypercall::HypercallError> {
+    let (v0, v1) = (
+        chip8.data_registers[DataRegister::V0],
+        chip8.data_registers[DataRegister::V1],
+    );
+    match v0 == v1 {
+        true => Ok(()),
+        false => Err(format!("expected v0 == v1, got {v0} and {v1}").into()),
+    }
+}
+
+#[test]
+fn handler_sees_and_changes_the_machine() {
+    let printed = Arc::new(Mutex::new(Vec::new()));
+    let mut chip8 = Chip8::new();
+    // v0 := 42, i := 0x300, call 0x080, v1 := v0, jump to itself
+    chip8
+        .load_program(&[0x60, 0x2A, 0xA3, 0x00, 0x00, 0x80, 0x81, 0x00, 0x12, 0x08])
+        .unwrap();
+
+    let log = printed.clone();
+    chip8.register_hypercall(0x080, move |chip8| {
+        log.lock()
+            .unwrap()
+            .push(chip8.data_registers[DataRegister::V0]);
+        chip8.data_registers[DataRegister::V0] = 7;
+        chip8.memory.raw_data[chip8.address_register] = 0x99;
+        Ok(())
+    });
+
+    chip8.run_frame().unwrap();
+
+    assert_eq!(*printed.lock().unwrap(), vec![42]);
+    assert_eq!(chip8.data_registers[DataRegister::V1], 7);
+    assert_eq!(chip8.memory.raw_data[0x300], 0x99);
+    assert_eq!(chip8.program_counter, 0x208);
+}
+
+#[test]
+fn failing_handler_stops_the_machine() {
+    let mut chip8 = Chip8::new();
+    // v0 := 1, v1 := 2, assert, jump to itself
+    chip8
+        .load_program(&[0x60, 0x01, 0x61, 0x02, 0x01, 0x00, 0x12, 0x06])
+        .unwrap();
+    chip8.register_hypercall(0x100, assert_v0_equals_v1);
+
+    let error = chip8.run_frame().unwrap_err();
+
+    match error {
+        CycleError::ExecutionError(InstructionExecutionError::HypercallFailed(address, error)) => {
+            assert_eq!(address, 0x100);
+            assert_eq!(error.0, "expected v0 == v1, got 1 and 2");
+        }
+        error => panic!("unexpected error {error}"),
+    }
+    assert_eq!(chip8.program_counter, 0x204);
+}
+
+#[test]
+fn passing_handler_lets_the_program_go_on() {
+    let mut chip8 = Chip8::new();
+    // v0 := 3, v1 := 3, assert, jump to itself
+    chip8
+        .load_program(&[0x60, 0x03, 0x61, 0x03, 0x01, 0x00, 0x12, 0x06])
+        .unwrap();
+    chip8.register_hypercall(0x100, assert_v0_equals_v1);
+
+    chip8.run_frame().unwrap();
+
+    assert_eq!(chip8.program_counter, 0x206);
+    assert!(chip8.has_hypercall(0x100));
+}
+
+#[test]
+fn calls_without_a_handler_are_ignored() {
+    let mut chip8 = Chip8::new();
+    chip8
+        .load_program(&[0x60, 0x01, 0x61, 0x02, 0x01, 0x00, 0x12, 0x06])
+        .unwrap();
+    chip8.register_hypercall(0x100, assert_v0_equals_v1);
+    assert!(chip8.unregister_hypercall(0x100));
+    assert!(!chip8.unregister_hypercall(0x100));
+
+    chip8.run_frame().unwrap();
+
+    assert_eq!(chip8.program_counter, 0x206);
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use rust8::data_register::DataRegister;
+use rust8::input::InputEvent;
+use rust8::keyboard::{Key, KeyWait};
+use rust8::timing::TimingModel;
+use rust8::Chip8;
+
+// Draws, then jumps to 0x206 if key 0 is held and back to the start otherwise
+const VBLANK_PROGRAM: [u8; 8] = [0xD0, 0x01, 0xE0, 0x9E, 0x12, 0x00, 0x12, 0x06];
+
+// v3 := key, then loops in place
+const WAIT_PROGRAM: [u8; 4] = [0xF3, 0x0A, 0x12, 0x02];
+
+fn tap(chip8: &mut Chip8, time: Duration, key: Key) {
+    chip8.push_input(InputEvent::key_down(time, key));
+    chip8.push_input(InputEvent::key_up(time, key));
+}
+
+#[test]
+fn taps_while_waiting_for_vblank_reach_the_program() {
+    for timing in [TimingModel::Fixed, TimingModel::CosmacVip] {
+        let mut chip8 = Chip8::new();
+        chip8.quirks.vblank = true;
+        chip8.timing = timing;
+        chip8.load_program(&VBLANK_PROGRAM).unwrap();
+        tap(&mut chip8, Duration::from_millis(1), Key::Num0);
+
+        for _ in 0..4 {
+            chip8.run_frame().unwrap();
+        }
+
+        assert_eq!(chip8.pending_input(), 0);
+        assert_eq!(chip8.program_counter, 0x206, "{:?}", timing);
+    }
+}
+
+#[test]
+fn observed_taps_are_not_seen_twice() {
+    // Skips unless key 0 is held, counting the frames it was seen in v1
+    let program = [0xE0, 0xA1, 0x71, 0x01, 0x12, 0x00];
+    let mut chip8 = Chip8::new();
+    chip8.instructions_per_frame = 3;
+    chip8.load_program(&program).unwrap();
+    tap(&mut chip8, Duration::ZERO, Key::Num0);
+
+    for _ in 0..3 {
+        chip8.run_frame().unwrap();
+    }
+
+    assert_eq!(chip8.data_registers[DataRegister::V1], 1);
+}
+
+#[test]
+fn events_apply_in_time_order() {
+    let mut chip8 = Chip8::new();
+    chip8.key_wait = KeyWait::Legacy;
+    chip8.load_program(&WAIT_PROGRAM).unwrap();
+
+    chip8.push_input(InputEvent::key_up(Duration::from_millis(25), Key::Num5));
+    chip8.push_input(InputEvent::key_down(Duration::from_millis(40), Key::Num7));
+    chip8.push_input(InputEvent::key_down(Duration::from_millis(10), Key::Num5));
+    assert_eq!(chip8.pending_input(), 3);
+
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.pending_input(), 2);
+    assert!(chip8.is_blocked());
+
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.pending_input(), 1);
+    assert!(!chip8.is_blocked());
+    assert_eq!(chip8.data_registers[DataRegister::V3], 5);
+
+    assert_eq!(chip8.emulated_time(), Duration::from_nanos(33_333_333));
+    chip8.clear_input();
+    assert_eq!(chip8.pending_input(), 0);
+}
+
+#[test]
+fn taps_survive_a_saved_state() {
+    let mut chip8 = Chip8::new();
+    chip8.quirks.vblank = true;
+    chip8.load_program(&VBLANK_PROGRAM).unwrap();
+    chip8.run_frame().unwrap();
+
+    // Nothing runs between the press and the release
+    chip8.key_down(Key::Num0);
+    chip8.key_up(Key::Num0);
+
+    let mut restored = Chip8::new();
+    restored.load_state(&chip8.save_state()).unwrap();
+    restored.run_frame().unwrap();
+
+    assert_eq!(restored.program_counter, 0x206);
+}
@@ -0,0 +1,148 @@
+use rust8::data_register::DataRegister;
+use rust8::keyboard::{Key, KeyWait};
+use rust8::Chip8;
+
+// v0 := 30, delay := v0, v3 := key, jump to itself
+const WAIT_PROGRAM: [u8; 8] = [0x60, 0x1E, 0xF0, 0x15, 0xF3, 0x0A, 0x12, 0x06];
+
+fn waiting_chip8(key_wait: KeyWait) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.key_wait = key_wait;
+    chip8.load_program(&WAIT_PROGRAM).unwrap();
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+    assert!(chip8.is_blocked());
+
+    chip8
+}
+
+#[test]
+fn press_and_release_ends_the_wait() {
+    let mut chip8 = waiting_chip8(KeyWait::Accurate);
+    assert_eq!(chip8.awaited_register(), Some(DataRegister::V3));
+
+    chip8.key_down(Key::Num7);
+    assert!(chip8.is_blocked());
+
+    chip8.key_up(Key::Num7);
+    assert!(!chip8.is_blocked());
+    assert_eq!(chip8.awaited_register(), None);
+    assert_eq!(chip8.data_registers[DataRegister::V3], 0x7);
+}
+
+#[test]
+fn key_held_before_the_wait_is_ignored() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&WAIT_PROGRAM).unwrap();
+    chip8.key_down(Key::A);
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+
+    chip8.key_up(Key::A);
+    assert!(chip8.is_blocked());
+
+    chip8.key_down(Key::A);
+    chip8.key_up(Key::A);
+    assert!(!chip8.is_blocked());
+    assert_eq!(chip8.data_registers[DataRegister::V3], 0xA);
+}
+
+#[test]
+fn repeated_key_down_is_not_a_fresh_press() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&WAIT_PROGRAM).unwrap();
+    chip8.key_down(Key::B);
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+
+    chip8.key_down(Key::B);
+    chip8.key_up(Key::B);
+    assert!(chip8.is_blocked());
+}
+
+#[test]
+fn only_the_first_pressed_key_counts() {
+    let mut chip8 = waiting_chip8(KeyWait::Accurate);
+
+    chip8.key_down(Key::Num1);
+    chip8.key_down(Key::Num2);
+    chip8.key_up(Key::Num2);
+    assert!(chip8.is_blocked());
+
+    chip8.key_up(Key::Num1);
+    assert!(!chip8.is_blocked());
+    assert_eq!(chip8.data_registers[DataRegister::V3], 0x1);
+}
+
+#[test]
+fn releasing_a_key_held_before_the_wait_does_not_end_it() {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&WAIT_PROGRAM).unwrap();
+    chip8.key_down(Key::C);
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+
+    chip8.key_down(Key::D);
+    chip8.key_up(Key::C);
+    assert!(chip8.is_blocked());
+
+    chip8.key_up(Key::D);
+    assert_eq!(chip8.data_registers[DataRegister::V3], 0xD);
+}
+
+#[test]
+fn timers_keep_running_while_waiting() {
+    let mut chip8 = waiting_chip8(KeyWait::Accurate);
+
+    chip8.run_frame().unwrap();
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.delay_timer, 28);
+}
+
+#[test]
+fn sound_plays_while_the_key_is_held() {
+    let mut chip8 = waiting_chip8(KeyWait::Accurate);
+    assert!(!chip8.is_sound_playing());
+
+    chip8.key_down(Key::F);
+    assert!(chip8.is_sound_playing());
+
+    chip8.key_up(Key::F);
+    assert!(!chip8.is_sound_playing());
+}
+
+#[test]
+fn legacy_wait_ends_on_any_key_up_and_pauses_timers() {
+    let mut chip8 = Chip8::new();
+    chip8.key_wait = KeyWait::Legacy;
+    chip8.load_program(&WAIT_PROGRAM).unwrap();
+    chip8.key_down(Key::E);
+    for _ in 0..3 {
+        chip8.cycle().unwrap();
+    }
+
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.delay_timer, 30);
+
+    chip8.key_up(Key::E);
+    assert!(!chip8.is_blocked());
+    assert_eq!(chip8.data_registers[DataRegister::V3], 0xE);
+}
+
+#[test]
+fn wait_survives_save_and_load() {
+    let mut chip8 = waiting_chip8(KeyWait::Accurate);
+    chip8.key_down(Key::Num9);
+    let state = chip8.save_state();
+
+    let mut restored = Chip8::new();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.awaited_register(), Some(DataRegister::V3));
+
+    restored.key_up(Key::Num9);
+    assert_eq!(restored.data_registers[DataRegister::V3], 0x9);
+}
@@ -0,0 +1,131 @@
+use rust8::keyboard::Key;
+use rust8::keymap::{KeyConfig, KeyMap, KeyMapError, Layout};
+
+#[test]
+fn layouts_bind_every_key() {
+    for layout in Layout::ALL {
+        let keymap = KeyMap::from_layout(layout);
+        for key in 0..16 {
+            let key = Key::try_from(key).unwrap();
+            assert!(
+                keymap.host_keys(key).next().is_some(),
+                "{} leaves {:?} unbound",
+                layout,
+                key
+            );
+        }
+        assert_eq!(layout.to_string().parse::<Layout>().unwrap(), layout);
+    }
+}
+
+#[test]
+fn layouts_follow_the_keypad_positions() {
+    let qwerty = KeyMap::from_layout(Layout::Qwerty);
+    assert_eq!(qwerty.key("1"), Some(Key::Num1));
+    assert_eq!(qwerty.key("4"), Some(Key::C));
+    assert_eq!(qwerty.key("x"), Some(Key::Num0));
+    assert_eq!(qwerty.key("V"), Some(Key::F));
+
+    let azerty = KeyMap::from_layout(Layout::Azerty);
+    assert_eq!(azerty.key("a"), Some(Key::Num4));
+    assert_eq!(azerty.key("é"), Some(Key::Num2));
+    assert_eq!(azerty.key("w"), Some(Key::A));
+
+    let dvorak = KeyMap::from_layout(Layout::Dvorak);
+    assert_eq!(dvorak.key_for_char(','), Some(Key::Num5));
+    assert_eq!(dvorak.key_for_char(';'), Some(Key::A));
+
+    let numpad = KeyMap::from_layout(Layout::NumericKeypad);
+    assert_eq!(numpad.key("Numpad7"), Some(Key::Num7));
+    assert_eq!(numpad.key("NumpadEnter"), Some(Key::E));
+
+    assert_eq!(
+        "Numeric-Keypad".parse::<Layout>().unwrap(),
+        Layout::NumericKeypad
+    );
+    assert!("colemak".parse::<Layout>().is_err());
+}
+
+#[test]
+fn config_overrides_the_layout_per_rom() {
+    let config = KeyConfig::parse(
+        r#"
+        layout = "azerty"   # a comment
+        5 = ["z", "ArrowUp"]
+        0xA = 'q#'
+
+        [roms."pong.ch8"]
+        1 = "ArrowUp"
+
+        [roms."invaders.ch8"]
+        layout = "qwerty"
+        "#,
+    )
+    .unwrap();
+
+    let keymap = config.keymap();
+    assert_eq!(keymap.key("arrowup"), Some(Key::Num5));
+    assert_eq!(keymap.key("z"), Some(Key::Num5));
+    assert_eq!(keymap.key("q#"), Some(Key::A));
+    // The keys of the layout bound to the overridden keys are gone
+    assert_eq!(keymap.key("w"), None);
+    assert_eq!(keymap.key("a"), Some(Key::Num4));
+
+    let pong = config.keymap_for_rom("pong.ch8");
+    assert_eq!(pong.key("ArrowUp"), Some(Key::Num1));
+    assert_eq!(pong.key("z"), Some(Key::Num5));
+    assert_eq!(pong.key("1"), None);
+
+    let invaders = config.keymap_for_rom("invaders.ch8");
+    assert_eq!(invaders.key("q"), Some(Key::Num4));
+    assert_eq!(invaders.key("ArrowUp"), None);
+
+    assert_eq!(config.keymap_for_rom("other.ch8"), keymap);
+}
+
+#[test]
+fn config_strings_may_escape_characters() {
+    let config: KeyConfig = r#"0 = ["\"", "\\", '\']"#.parse().unwrap();
+    let keymap = config.keymap();
+
+    assert_eq!(keymap.key("\""), Some(Key::Num0));
+    assert_eq!(keymap.key("\\"), Some(Key::Num0));
+    assert_eq!(keymap.key("x"), None);
+}
+
+#[test]
+fn config_errors_name_the_line() {
+    assert_eq!(
+        KeyConfig::parse("layout = \"qwerty\"\nnonsense"),
+        Err(KeyMapError::InvalidLine(2))
+    );
+    assert_eq!(
+        KeyConfig::parse("layout = \"colemak\""),
+        Err(KeyMapError::UnknownLayout {
+            line: 1,
+            name: "colemak".to_string()
+        })
+    );
+    assert_eq!(
+        KeyConfig::parse("\n\n10 = \"x\""),
+        Err(KeyMapError::InvalidKey {
+            line: 3,
+            name: "10".to_string()
+        })
+    );
+    assert_eq!(
+        KeyConfig::parse("0x0x1 = \"x\""),
+        Err(KeyMapError::InvalidKey {
+            line: 1,
+            name: "0x0x1".to_string()
+        })
+    );
+    assert_eq!(
+        KeyConfig::parse("1 = [\"x\" \"y\"]"),
+        Err(KeyMapError::InvalidValue(1))
+    );
+    assert_eq!(
+        KeyConfig::parse("[tools]"),
+        Err(KeyMapError::InvalidLine(1))
+    );
+}
@@ -0,0 +1,31 @@
+use rust8::cfg::{Finding, Severity};
+use rust8::lint::{Lint, LintKind, Linter};
+
+// v1 := 5, v0 := v1 >> 1, v2 >>= 1 and jumps to itself, only the first shift reads another
+// register
+const PROGRAM: [u8; 8] = [0x61, 0x05, 0x80, 0x16, 0x82, 0x26, 0x12, 0x06];
+
+#[test]
+fn shifts_of_another_register_depend_on_the_quirk() {
+    let lints = Linter::default().lint_program(&PROGRAM);
+
+    assert_eq!(
+        lints,
+        [
+            Lint {
+                address: 0x202,
+                severity: Severity::Warning,
+                kind: LintKind::ShiftQuirk,
+            },
+            Lint {
+                address: 0x206,
+                severity: Severity::Info,
+                kind: LintKind::ControlFlow(Finding::InfiniteLoop { address: 0x206 }),
+            },
+        ]
+    );
+    assert_eq!(
+        lints[0].to_string(),
+        "warning 0x202: shift result depends on the shift quirk"
+    );
+}
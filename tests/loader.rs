@@ -0,0 +1,211 @@
+use rust8::loader::{IntelHexError, LoadError, LoadedProgram, ProgramFormat, Segment};
+use rust8::quirks::Quirks;
+use rust8::Chip8;
+
+fn segment(address: usize, data: &[u8]) -> Segment {
+    Segment {
+        address,
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn intel_hex_records_become_segments() {
+    let text = "\
+:040200006001120285
+:01030000AA52
+:0400000500000202F3
+:00000001FF
+";
+    let program = LoadedProgram::parse(text.as_bytes(), Some("hex")).unwrap();
+
+    assert_eq!(program.format, ProgramFormat::IntelHex);
+    assert_eq!(
+        program.segments,
+        vec![
+            segment(0x200, &[0x60, 0x01, 0x12, 0x02]),
+            segment(0x300, &[0xAA])
+        ]
+    );
+    assert_eq!(program.start_address, 0x202);
+}
+
+#[test]
+fn intel_hex_errors_name_the_line() {
+    let intel_hex_error =
+        |text: &str| match LoadedProgram::parse_as(text.as_bytes(), ProgramFormat::IntelHex) {
+            Err(LoadError::IntelHex { line, source }) => (line, source),
+            result => panic!("unexpected result {:?}", result),
+        };
+
+    assert_eq!(
+        intel_hex_error(":040200006001120286\n:00000001FF"),
+        (
+            1,
+            IntelHexError::ChecksumMismatch {
+                expected: 0x85,
+                found: 0x86
+            }
+        )
+    );
+    assert_eq!(
+        intel_hex_error(":040200006001120285\n:00000006FA\n:00000001FF"),
+        (2, IntelHexError::UnsupportedRecordType(0x06))
+    );
+    assert_eq!(
+        intel_hex_error(":0402000060011202\n:00000001FF"),
+        (1, IntelHexError::InvalidLength)
+    );
+    assert_eq!(
+        intel_hex_error(":00000001FF\n:01030000AA52"),
+        (2, IntelHexError::DataAfterEndOfFile)
+    );
+    assert_eq!(
+        intel_hex_error("040200006001120285\n:00000001FF"),
+        (1, IntelHexError::MissingStartCode)
+    );
+    assert!(matches!(
+        LoadedProgram::parse_as(b":040200006001120285", ProgramFormat::IntelHex),
+        Err(LoadError::MissingEndOfFile)
+    ));
+}
+
+#[test]
+fn hex_text_supports_addresses_and_comments() {
+    let text = "\
+# a tiny program
+6001 0x12, 02
+300: aa  # data
+";
+    let program = LoadedProgram::parse(text.as_bytes(), None).unwrap();
+
+    assert_eq!(program.format, ProgramFormat::HexText);
+    assert_eq!(
+        program.segments,
+        vec![
+            segment(0x200, &[0x60, 0x01, 0x12, 0x02]),
+            segment(0x300, &[0xAA])
+        ]
+    );
+}
+
+#[test]
+fn hex_text_rejects_odd_tokens() {
+    match LoadedProgram::parse_as(b"6001\n12 020", ProgramFormat::HexText) {
+        Err(LoadError::InvalidHexText { line, token }) => {
+            assert_eq!((line, token.as_str()), (2, "020"));
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn base64_accepts_both_alphabets() {
+    let bytes = [0xFB, 0xFF, 0x60];
+    for text in ["+/9g", "-_9g", "+/9g\n"] {
+        let program = LoadedProgram::parse_as(text.as_bytes(), ProgramFormat::Base64).unwrap();
+        assert_eq!(program.segments, vec![segment(0x200, &bytes)]);
+    }
+
+    let program = LoadedProgram::parse_as(b"YAE=", ProgramFormat::Base64).unwrap();
+    assert_eq!(program.segments, vec![segment(0x200, &[0x60, 0x01])]);
+}
+
+#[test]
+fn base64_errors() {
+    assert!(matches!(
+        LoadedProgram::parse_as(b"YA*E", ProgramFormat::Base64),
+        Err(LoadError::InvalidBase64Character {
+            character: '*',
+            offset: 2
+        })
+    ));
+    assert!(matches!(
+        LoadedProgram::parse_as(b"YAE=A", ProgramFormat::Base64),
+        Err(LoadError::InvalidBase64Character {
+            character: 'A',
+            offset: 4
+        })
+    ));
+    assert!(matches!(
+        LoadedProgram::parse_as(b"YAEBY", ProgramFormat::Base64),
+        Err(LoadError::InvalidBase64Length)
+    ));
+}
+
+#[test]
+fn formats_are_detected_from_the_contents() {
+    assert_eq!(
+        ProgramFormat::detect(&[0x60, 0x01, 0x00, 0xE0]),
+        ProgramFormat::Binary
+    );
+    assert_eq!(ProgramFormat::detect(&[0xFF, 0xFE]), ProgramFormat::Binary);
+    assert_eq!(
+        ProgramFormat::detect(b":040200006001120285\n:00000001FF\n"),
+        ProgramFormat::IntelHex
+    );
+    assert_eq!(
+        ProgramFormat::detect(b"60 01 12 02"),
+        ProgramFormat::HexText
+    );
+    assert_eq!(ProgramFormat::detect(b"6001 1z02"), ProgramFormat::Binary);
+    assert_eq!(
+        ProgramFormat::detect(b"GAME OVER, PLAYER 1"),
+        ProgramFormat::Binary
+    );
+    assert_eq!(ProgramFormat::detect(b"YAESAg=="), ProgramFormat::Base64);
+    assert_eq!(ProgramFormat::detect(b"hello!"), ProgramFormat::Binary);
+}
+
+#[test]
+fn hex_digits_alone_are_told_apart() {
+    // Single case and decodes into instructions
+    assert_eq!(
+        ProgramFormat::detect(b"6001A300D015"),
+        ProgramFormat::HexText
+    );
+    assert_eq!(ProgramFormat::detect(b"0x6001"), ProgramFormat::HexText);
+
+    // Base64 of 79 cd 00 7c 3f 3a, which happens to only use hex digits
+    let program = LoadedProgram::parse(b"ec0AfD86", None).unwrap();
+    assert_eq!(program.format, ProgramFormat::Base64);
+    assert_eq!(
+        program.segments,
+        vec![segment(0x200, &[0x79, 0xCD, 0x00, 0x7C, 0x3F, 0x3A])]
+    );
+}
+
+#[test]
+fn images_fill_gaps_and_reject_segments_before_the_program() {
+    let program = LoadedProgram::parse(b"6001\n0x204: 1202", None).unwrap();
+    assert_eq!(
+        program.image().unwrap(),
+        [0x60, 0x01, 0x00, 0x00, 0x12, 0x02]
+    );
+
+    let program = LoadedProgram::parse(b"6001\n100: aa", None).unwrap();
+    assert!(matches!(
+        program.image(),
+        Err(LoadError::SegmentBeforeProgram(0x100))
+    ));
+}
+
+#[test]
+fn loading_applies_the_platform_of_the_extension() {
+    let mut chip8 = Chip8::new();
+
+    let program = LoadedProgram::parse(&[0x60, 0x01], Some("sc8")).unwrap();
+    chip8.load(&program).unwrap();
+    assert_eq!(chip8.quirks, Quirks::super_chip());
+    assert_eq!(chip8.memory.raw_data[0x200..0x202], [0x60, 0x01]);
+
+    let program = LoadedProgram::parse(&[0x60, 0x01], Some("ch8")).unwrap();
+    chip8.load(&program).unwrap();
+    assert_eq!(chip8.quirks, Quirks::cosmac_vip());
+
+    // Without a platform the quirks are left alone
+    chip8.quirks = Quirks::xo_chip();
+    let program = LoadedProgram::parse(&[0x60, 0x01], Some("bin")).unwrap();
+    chip8.load(&program).unwrap();
+    assert_eq!(chip8.quirks, Quirks::xo_chip());
+}
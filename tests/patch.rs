@@ -0,0 +1,144 @@
+use rust8::patch::{apply_patch, crc32, PatchError, PatchFormat};
+
+fn round_trip(format: PatchFormat, source: &[u8], target: &[u8]) {
+    let patch = format.create(source, target).unwrap();
+    assert_eq!(PatchFormat::detect(&patch), Some(format));
+    assert_eq!(apply_patch(source, &patch).unwrap(), target, "{}", format);
+}
+
+fn write_varint(patch: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            patch.push(byte | 0x80);
+            return;
+        }
+        patch.push(byte);
+        value -= 1;
+    }
+}
+
+// A BPS patch with the given header sizes and actions, and valid checksums for the patch
+// itself so that the actions are looked at
+fn bps(source: &[u8], target_size: usize, actions: &[u8]) -> Vec<u8> {
+    let mut patch = b"BPS1".to_vec();
+    write_varint(&mut patch, source.len());
+    write_varint(&mut patch, target_size);
+    write_varint(&mut patch, 0);
+    patch.extend_from_slice(actions);
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&0u32.to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    patch
+}
+
+#[test]
+fn patches_reproduce_their_target() {
+    let source: Vec<u8> = (0..=255).collect();
+    let mut changed = source.clone();
+    changed[3] = 0;
+    changed[100..110].fill(0xAA);
+
+    for format in [PatchFormat::Ips, PatchFormat::Bps] {
+        round_trip(format, &source, &changed);
+        round_trip(format, &source, &source);
+        round_trip(format, &source, &source[..200]);
+        round_trip(format, &source[..10], &source);
+        round_trip(format, &[], &source);
+    }
+}
+
+#[test]
+fn ips_records_never_start_at_eof() {
+    // Offset 0x454f46 spells EOF
+    let source = vec![0; 0x454F50];
+    let mut target = source.clone();
+    target[0x454F46] = 1;
+
+    round_trip(PatchFormat::Ips, &source, &target);
+}
+
+#[test]
+fn malformed_ips_patches_are_rejected() {
+    assert_eq!(apply_patch(&[], b"PATCX"), Err(PatchError::UnknownFormat));
+    assert_eq!(apply_patch(&[], b"PATCH"), Err(PatchError::Truncated));
+    // A record announcing more data than follows
+    assert_eq!(
+        apply_patch(&[], b"PATCH\x00\x00\x10\x00\x04ab"),
+        Err(PatchError::Truncated)
+    );
+    // A run length record missing its value
+    assert_eq!(
+        apply_patch(&[], b"PATCH\x00\x00\x10\x00\x00\x00\x04"),
+        Err(PatchError::Truncated)
+    );
+    assert_eq!(
+        PatchFormat::Ips.create(&[], &vec![0; 0x1000000]),
+        Err(PatchError::TooLargeForIps)
+    );
+}
+
+#[test]
+fn malformed_bps_patches_are_rejected() {
+    let source = b"source";
+
+    assert_eq!(apply_patch(source, b"BPS1"), Err(PatchError::Truncated));
+
+    let mut corrupted = PatchFormat::Bps.create(source, b"target").unwrap();
+    corrupted[6] ^= 1;
+    assert!(matches!(
+        apply_patch(source, &corrupted),
+        Err(PatchError::PatchChecksumMismatch { .. })
+    ));
+
+    let patch = PatchFormat::Bps.create(source, b"target").unwrap();
+    assert_eq!(
+        apply_patch(b"sourc", &patch),
+        Err(PatchError::SourceSizeMismatch {
+            expected: 6,
+            found: 5
+        })
+    );
+    assert!(matches!(
+        apply_patch(b"SOURCE", &patch),
+        Err(PatchError::SourceChecksumMismatch { .. })
+    ));
+
+    // A huge target size is only a hint and does not allocate
+    assert_eq!(
+        apply_patch(source, &bps(source, usize::MAX >> 8, &[])),
+        Err(PatchError::Truncated)
+    );
+
+    // Writing past the target size
+    assert_eq!(
+        apply_patch(source, &bps(source, 2, &[0x88])),
+        Err(PatchError::OutOfRange(0))
+    );
+
+    // Source copies before the start and far beyond the end of the source
+    assert_eq!(
+        apply_patch(source, &bps(source, 1, &[0x82, 0x83])),
+        Err(PatchError::OutOfRange(0))
+    );
+    // Copies one byte, then moves the source offset so far that it would overflow
+    let mut far = vec![0x82, 0x80, 0x82];
+    write_varint(&mut far, (isize::MAX as usize) << 1);
+    assert_eq!(
+        apply_patch(source, &bps(source, 2, &far)),
+        Err(PatchError::OutOfRange(1))
+    );
+
+    // Target copies of bytes that were not written yet
+    assert_eq!(
+        apply_patch(source, &bps(source, 1, &[0x83, 0x80])),
+        Err(PatchError::OutOfRange(0))
+    );
+
+    // Actions with a truncated length
+    assert_eq!(
+        apply_patch(source, &bps(source, 1, &[0x01])),
+        Err(PatchError::Truncated)
+    );
+}
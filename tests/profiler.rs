@@ -0,0 +1,76 @@
+use rust8::profiler::{Profiler, SubroutineStats};
+use rust8::symbols::SymbolTable;
+use rust8::Chip8;
+
+// Calls the subroutine at 0x206 twice and jumps to itself, the subroutine sets v0 and returns
+const PROGRAM: [u8; 10] = [0x22, 0x06, 0x22, 0x06, 0x12, 0x04, 0x60, 0x01, 0x00, 0xEE];
+
+fn profiled() -> Profiler {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&PROGRAM).unwrap();
+    chip8.enable_profiler();
+    for _ in 0..10 {
+        chip8.cycle().unwrap();
+    }
+
+    chip8.disable_profiler().unwrap()
+}
+
+fn symbols() -> SymbolTable {
+    SymbolTable::parse("0x206 sub").unwrap()
+}
+
+#[test]
+fn instructions_are_attributed_to_their_subroutine() {
+    let profiler = profiled();
+
+    assert_eq!(profiler.total_instructions(), 10);
+    assert_eq!(profiler.address_hot_spots()[0], (0x204, 4));
+    assert_eq!(profiler.instruction_count("ExecuteSubroutine"), 2);
+    assert_eq!(
+        profiler.subroutine_hot_spots(),
+        [SubroutineStats {
+            address: 0x206,
+            calls: 2,
+            inclusive_instructions: 4,
+            exclusive_instructions: 4,
+        }]
+    );
+}
+
+#[test]
+fn report_lists_the_hot_spots_with_labels() {
+    let mut report = Vec::new();
+    profiled()
+        .write_report(&mut report, 1, Some(&symbols()))
+        .unwrap();
+    let report = String::from_utf8(report).unwrap();
+    let lines: Vec<&str> = report.lines().map(str::trim_end).collect();
+
+    assert_eq!(lines[0], "total instructions: 10");
+    assert_eq!(
+        lines[3].split_whitespace().collect::<Vec<_>>(),
+        ["0x204", "4", "40.00%"]
+    );
+    assert_eq!(lines[6].split_whitespace().nth(1), Some("4"));
+    assert_eq!(
+        lines[9].split_whitespace().collect::<Vec<_>>(),
+        ["0x206", "<sub>", "2", "4", "40.00%", "4", "40.00%"]
+    );
+    assert_eq!(lines.len(), 10);
+}
+
+#[test]
+fn folded_stacks_name_subroutines_by_label() {
+    let profiler = profiled();
+
+    let mut folded = Vec::new();
+    profiler.write_folded_stacks(&mut folded, None).unwrap();
+    assert_eq!(String::from_utf8(folded).unwrap(), "main 6\nmain;0x206 4\n");
+
+    let mut folded = Vec::new();
+    profiler
+        .write_folded_stacks(&mut folded, Some(&symbols()))
+        .unwrap();
+    assert_eq!(String::from_utf8(folded).unwrap(), "main 6\nmain;sub 4\n");
+}
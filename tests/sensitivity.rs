@@ -0,0 +1,24 @@
+use rust8::quirks::QuirkFlag;
+use rust8::sensitivity::SensitivityAnalyzer;
+
+// v1 := 5, v0 := v1 >> 1, stores v0 at 0x20c and jumps to itself
+const PROGRAM: [u8; 14] = [
+    0x61, 0x05, 0x80, 0x16, 0xA2, 0x0C, 0xF0, 0x55, 0x12, 0x08, 0x00, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn only_the_shift_quirk_changes_a_shift_of_another_register() {
+    let report = SensitivityAnalyzer::new(2).analyze(&PROGRAM);
+
+    assert_eq!(report.sensitive_flags(), [QuirkFlag::Shift]);
+    let shift = report.sensitivities()[0];
+    assert_eq!(shift.changed_pairs, shift.total_pairs);
+    assert_eq!(report.equivalence_classes().len(), 2);
+
+    let mut text = Vec::new();
+    report.write_report(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("shift                   64/64 pairs changed  <- affects this ROM\n"));
+    assert!(text.contains("logic                    0/64 pairs changed\n"));
+    assert!(text.contains("requires: shift=false\n"));
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use rust8::constants::STACK_SIZE;
+use rust8::cpu::execute::InstructionExecutionError;
+use rust8::cpu::CycleError;
+use rust8::data_register::DataRegister;
+use rust8::graphic::EventDisplay;
+use rust8::input::InputEvent;
+use rust8::keyboard::{Key, KeyWait};
+use rust8::quirks::Quirks;
+use rust8::state::StateError;
+use rust8::timing::TimingModel;
+use rust8::Chip8;
+
+// v0 := random, skip unless key 0 is held, v2 += 1, draw the font sprite of 0, loop
+const PROGRAM: [u8; 12] = [
+    0xC0, 0xFF, 0xE3, 0xA1, 0x72, 0x01, 0xA0, 0x00, 0xD0, 0x15, 0x12, 0x00,
+];
+
+fn configured() -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&PROGRAM).unwrap();
+    chip8.quirks = Quirks::cosmac_vip();
+    chip8.key_wait = KeyWait::Legacy;
+    chip8.timing = TimingModel::CosmacVip;
+    chip8.instructions_per_frame = 20;
+    chip8.seed_rng(7);
+    chip8
+}
+
+#[test]
+fn restored_machine_continues_like_the_original() {
+    let mut original = configured();
+    original.key_down(Key::Num0);
+    original.push_input(InputEvent::key_up(Duration::from_millis(70), Key::Num0));
+    original.push_input(InputEvent::key_down(Duration::from_millis(90), Key::Num0));
+    for _ in 0..3 {
+        original.run_frame().unwrap();
+    }
+
+    let state = original.save_state();
+    let mut restored = Chip8::new();
+    restored.load_state(&state).unwrap();
+
+    assert_eq!(restored.quirks, Quirks::cosmac_vip());
+    assert_eq!(restored.key_wait, KeyWait::Legacy);
+    assert_eq!(restored.timing, TimingModel::CosmacVip);
+    assert_eq!(restored.instructions_per_frame, 20);
+    assert_eq!(restored.pending_input(), original.pending_input());
+    assert_eq!(restored.emulated_time(), original.emulated_time());
+    assert_eq!(restored.save_state(), state);
+
+    // The random numbers, the held key and the queued input all carry on
+    for _ in 0..5 {
+        original.run_frame().unwrap();
+        restored.run_frame().unwrap();
+        assert_eq!(restored.save_state(), original.save_state());
+    }
+    assert_ne!(original.data_registers[DataRegister::V2], 0);
+}
+
+#[test]
+fn broken_states_leave_the_machine_unchanged() {
+    let mut chip8 = configured();
+    chip8.run_frame().unwrap();
+    let state = chip8.save_state();
+
+    let mut target = Chip8::new();
+    let before = target.save_state();
+
+    assert!(matches!(
+        target.load_state(&state[..state.len() - 1]),
+        Err(StateError::Truncated)
+    ));
+
+    let mut invalid = state.clone();
+    // After the screen: quirks, key wait, instructions per frame, RNG, keys, unobserved keys and
+    // vblank wait
+    let timing_offset = 5 + 4096 + 16 + 2 + 2 + 2 + 1 + 3 + 256 + 1 + 1 + 4 + 56 + 2 + 2 + 1;
+    invalid[timing_offset] = 2;
+    assert!(matches!(
+        target.load_state(&invalid),
+        Err(StateError::InvalidValue(offset)) if offset == timing_offset
+    ));
+
+    let mut future = state;
+    future[4] = 2;
+    assert!(matches!(
+        target.load_state(&future),
+        Err(StateError::UnsupportedVersion(2))
+    ));
+
+    assert_eq!(target.save_state().len(), before.len());
+    assert_eq!(target.program_counter, 0x200);
+    assert_eq!(target.timing, TimingModel::Fixed);
+}
+
+#[test]
+fn states_move_between_displays() {
+    let mut original = configured();
+    original.run_frame().unwrap();
+    let state = original.save_state();
+
+    let mut restored: Chip8<EventDisplay> = Chip8::default();
+    restored.load_state(&state).unwrap();
+
+    assert_eq!(
+        restored.screen.display().framebuffer(),
+        original.screen.framebuffer()
+    );
+    assert_eq!(restored.save_state(), state);
+}
+
+#[test]
+fn calls_beyond_the_stack_fail_instead_of_growing_it() {
+    // A subroutine that calls itself
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&[0x22, 0x00]).unwrap();
+
+    for _ in 0..STACK_SIZE {
+        chip8.cycle().unwrap();
+    }
+    assert!(matches!(
+        chip8.cycle(),
+        Err(CycleError::ExecutionError(
+            InstructionExecutionError::StackOverflow
+        ))
+    ));
+    assert_eq!(chip8.stack.len(), STACK_SIZE);
+
+    let mut restored = Chip8::new();
+    restored.load_state(&chip8.save_state()).unwrap();
+    assert_eq!(restored.stack, chip8.stack);
+}
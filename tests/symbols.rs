@@ -0,0 +1,64 @@
+use rust8::disassembler::disassemble;
+use rust8::symbols::{SymbolError, SymbolTable};
+use rust8::Chip8;
+
+// Calls the subroutine at 0x206 and jumps to itself, the subroutine returns right away
+const PROGRAM: [u8; 8] = [0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x00, 0xEE];
+
+const SYMBOLS: &str = "\
+# labels from the assembler
+:const start 0x200
+0x206 draw
+0x206 game.8o:12
+";
+
+#[test]
+fn labels_and_locations_are_looked_up_both_ways() {
+    let symbols = SymbolTable::parse(SYMBOLS).unwrap();
+
+    assert_eq!(symbols.label(0x206), Some("draw"));
+    assert_eq!(symbols.address("start"), Some(0x200));
+    assert_eq!(symbols.location(0x206).unwrap().to_string(), "game.8o:12");
+    assert_eq!(symbols.format_address(0x203), "0x203 <start+0x3>");
+    assert_eq!(symbols.format_address(0x206), "0x206 <draw>");
+
+    assert!(matches!(
+        SymbolTable::parse("0x200"),
+        Err(SymbolError::InvalidLine(1))
+    ));
+    assert!(matches!(
+        SymbolTable::parse("\nfoo start"),
+        Err(SymbolError::InvalidAddress(2, address)) if address == "foo"
+    ));
+}
+
+#[test]
+fn listings_and_backtraces_use_the_labels() {
+    let symbols = SymbolTable::parse(SYMBOLS).unwrap();
+
+    let mut memory = vec![0; 0x200];
+    memory.extend_from_slice(&PROGRAM);
+    let mut listing = Vec::new();
+    symbols
+        .write_listing(&mut listing, &disassemble(&memory, 0x200..0x208))
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(listing).unwrap(),
+        "\
+: start
+  0x200: 2206  :call draw
+  0x202: 1202  jump 0x202
+  0x204: 0000  native 0x000
+: draw
+  0x206: 00ee  return  # game.8o:12
+"
+    );
+
+    let mut chip8 = Chip8::new();
+    chip8.load_program(&PROGRAM).unwrap();
+    chip8.cycle().unwrap();
+    assert_eq!(
+        symbols.backtrace(&chip8),
+        ["0x206 <draw> at game.8o:12", "0x202 <start+0x2>"]
+    );
+}
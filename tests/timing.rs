@@ -0,0 +1,160 @@
+use rust8::data_register::{DataRegister, DataRegisters};
+use rust8::extension::{ExtensionError, OpcodeExtension, ProgramCounterUpdate};
+use rust8::instruction::Instruction;
+use rust8::timing::{
+    vip_machine_cycles, TimingModel, VIP_CUSTOM_OPCODE_CYCLES, VIP_INSTRUCTION_CYCLES_PER_FRAME,
+};
+use rust8::Chip8;
+
+fn cycles(opcode: u16, registers: &DataRegisters) -> u32 {
+    let instruction = Instruction::try_from(&opcode.to_be_bytes()).unwrap();
+    vip_machine_cycles(&instruction, registers)
+}
+
+fn vip_chip8(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.timing = TimingModel::CosmacVip;
+    chip8.load_program(program).unwrap();
+    chip8
+}
+
+// How far v1 got in each of the frames
+fn v1_per_frame(chip8: &mut Chip8, frames: usize) -> Vec<u8> {
+    (0..frames)
+        .map(|_| {
+            let before = chip8.data_registers[DataRegister::V1];
+            chip8.run_frame().unwrap();
+            chip8.data_registers[DataRegister::V1].wrapping_sub(before)
+        })
+        .collect()
+}
+
+#[test]
+fn machine_cycles_of_fixed_cost_instructions() {
+    let registers = DataRegisters::new();
+
+    assert_eq!(cycles(0x00E0, &registers), 24);
+    assert_eq!(cycles(0x1200, &registers), 23);
+    assert_eq!(cycles(0x6012, &registers), 6);
+    assert_eq!(cycles(0x7001, &registers), 10);
+    assert_eq!(cycles(0x8014, &registers), 44);
+    assert_eq!(cycles(0x3000, &registers), 12);
+    assert_eq!(cycles(0x5010, &registers), 16);
+    assert_eq!(cycles(0xF355, &registers), 30 + 14 * 4);
+    assert_eq!(cycles(0xFF65, &registers), 30 + 14 * 16);
+}
+
+#[test]
+fn sprites_cost_more_when_not_byte_aligned() {
+    let mut registers = DataRegisters::new();
+
+    registers[DataRegister::V0] = 8;
+    assert_eq!(cycles(0xD015, &registers), 26 + 5 * 14);
+
+    registers[DataRegister::V0] = 3;
+    assert_eq!(cycles(0xD015, &registers), 26 + 5 * 26);
+    // Only the horizontal position matters
+    assert_eq!(cycles(0xD105, &registers), 26 + 5 * 14);
+
+    registers[DataRegister::V0] = 7;
+    assert_eq!(cycles(0xD01F, &registers), 26 + 15 * 34);
+}
+
+#[test]
+fn bcd_costs_depend_on_the_digits() {
+    let mut registers = DataRegisters::new();
+
+    registers[DataRegister::V2] = 0;
+    assert_eq!(cycles(0xF233, &registers), 40);
+
+    registers[DataRegister::V2] = 100;
+    assert_eq!(cycles(0xF233, &registers), 40 + 16);
+
+    registers[DataRegister::V2] = 255;
+    assert_eq!(cycles(0xF233, &registers), 40 + 16 * 12);
+}
+
+#[test]
+fn vip_frames_run_as_many_instructions_as_fit() {
+    // v1 += 1, jump back, 33 cycles a round: 79 rounds and the add that starts at cycle 2607
+    let mut chip8 = vip_chip8(&[0x71, 0x01, 0x12, 0x00]);
+
+    assert_eq!(v1_per_frame(&mut chip8, 1), [80]);
+}
+
+#[test]
+fn overrun_cycles_are_taken_from_the_next_frame() {
+    // 33 frames hold exactly as many 33 cycle rounds as a frame has cycles, which only adds
+    // up when no frame gets more than its share
+    let mut chip8 = vip_chip8(&[0x71, 0x01, 0x12, 0x00]);
+    let rounds: u32 = v1_per_frame(&mut chip8, 33)
+        .iter()
+        .map(|count| *count as u32)
+        .sum();
+
+    assert_eq!(rounds, VIP_INSTRUCTION_CYCLES_PER_FRAME);
+}
+
+#[test]
+fn taken_skips_cost_extra() {
+    // A skip that is not taken: 12 + 10 + 23 cycles a round
+    let mut not_taken = vip_chip8(&[0x30, 0x01, 0x71, 0x01, 0x12, 0x00]);
+    assert_eq!(v1_per_frame(&mut not_taken, 1), [58]);
+
+    // A taken skip over a jump that is never run: 16 + 10 + 23 cycles a round
+    let mut taken = vip_chip8(&[0x30, 0x00, 0x1F, 0xFF, 0x71, 0x01, 0x12, 0x00]);
+    assert_eq!(v1_per_frame(&mut taken, 1), [54]);
+
+    assert_eq!(VIP_INSTRUCTION_CYCLES_PER_FRAME, 2615);
+}
+
+#[test]
+fn fixed_frames_run_instructions_per_frame() {
+    let mut chip8 = Chip8::new();
+    chip8.instructions_per_frame = 11;
+    chip8.load_program(&[0x71, 0x01, 0x12, 0x00]).unwrap();
+
+    assert_eq!(v1_per_frame(&mut chip8, 3), [6, 5, 6]);
+}
+
+// 3X00: skips the next instruction when vx is 0, taking the place of the built-in skip
+struct SkipIfZero;
+
+impl OpcodeExtension for SkipIfZero {
+    type Instruction = DataRegister;
+
+    fn mask(&self) -> u16 {
+        0xF0FF
+    }
+
+    fn pattern(&self) -> u16 {
+        0x3000
+    }
+
+    fn decode(&self, opcode: u16) -> Option<Self::Instruction> {
+        DataRegister::try_from((opcode >> 8 & 0xF) as u8).ok()
+    }
+
+    fn execute(
+        &mut self,
+        chip8: &mut Chip8,
+        vx: Self::Instruction,
+    ) -> Result<ProgramCounterUpdate, ExtensionError> {
+        chip8.program_counter += match chip8.data_registers[vx] {
+            0 => 4,
+            _ => 2,
+        };
+        Ok(ProgramCounterUpdate::Handled)
+    }
+}
+
+#[test]
+fn redefined_opcodes_cost_what_custom_opcodes_do() {
+    // A taken skip over a jump that is never run: 23 + 10 + 23 cycles a round, instead of the
+    // 16 + 10 + 23 of the built-in skip
+    let mut chip8 = vip_chip8(&[0x30, 0x00, 0x1F, 0xFF, 0x71, 0x01, 0x12, 0x00]);
+    chip8.register_extension(SkipIfZero);
+
+    assert_eq!(v1_per_frame(&mut chip8, 1), [47]);
+    assert_eq!(VIP_CUSTOM_OPCODE_CYCLES, 23);
+}